@@ -1,6 +1,9 @@
 #import bevy_pbr::mesh_functions::{get_world_from_local, mesh_position_local_to_world, mesh_normal_local_to_world};
 #import bevy_pbr::view_transformations::position_world_to_clip;
-#import "shaders/chunk_util.wgsl"::{unpack, Vertex};
+#import "shaders/chunk_util.wgsl"::{unpack, block_color, Vertex};
+#ifdef QUAD_STORAGE
+#import "shaders/chunk_util.wgsl"::{quad_attributes, unpack_quad_attributes, unpack_quad_position, quad_corner_uvs, quad_ao};
+#endif
 
 #import bevy_pbr::pbr_functions::{calculate_view, prepare_world_normal};
 #import bevy_pbr::mesh_bindings::mesh;
@@ -17,35 +20,68 @@
     forward_io::{FragmentOutput},
     pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
 };
+#ifdef DISTANCE_FOG
+#import bevy_pbr::{mesh_view_bindings::{fog, view}, pbr_functions::apply_fog};
+#import "shaders/chunk_fog.wgsl"::height_fog;
+#endif
 #endif
 
+// `ChunkFog` in `fog.rs`
+struct ChunkFog {
+    distance_fog: f32,
+    height_density: f32,
+    height_falloff: f32,
+    base_height: f32,
+};
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(1) var<uniform> chunk_fog: ChunkFog;
+// scale of the baked sky light, lower at night
+@group(#{MATERIAL_BIND_GROUP}) @binding(2) var<uniform> sky_light: f32;
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) world_normal: vec3<f32>,
     @location(1) world_position: vec4<f32>,
-    @location(2) blend_color: vec3<f32>,
+    @location(2) local_position: vec3<f32>,
     @location(3) ambient: f32,
     @location(4) instance_index: u32,
+    @location(5) @interpolate(flat) block: u32,
+#ifdef QUAD_STORAGE
+    @location(6) @interpolate(flat) quad: u32,
+    @location(7) quad_uv: vec2<f32>,
+#endif
 };
 
 @vertex
 fn vertex(vertex: Vertex) -> VertexOutput {
-    let data = unpack(vertex.data);
     var out: VertexOutput;
+#ifdef QUAD_STORAGE
+    // every quad has four vertices of its own, in the order of its corners
+    let quad_vertex = vertex.vertex_index - mesh[vertex.instance_index].first_vertex_index;
+    let position = unpack_quad_position(vertex.data);
+    let normal = unpack_quad_attributes(quad_attributes[quad_vertex / 4u]).normal;
+    out.quad = quad_vertex / 4u;
+    out.quad_uv = quad_corner_uvs[quad_vertex % 4u];
+#else
+    let data = unpack(vertex.data);
+    let position = data.position;
+    let normal = data.normal;
+    out.block = data.block;
+#endif
 
     let world_to_local = get_world_from_local(vertex.instance_index);
     out.world_position = mesh_position_local_to_world(
         world_to_local,
-        data.position
+        position
     );
     out.position = position_world_to_clip(out.world_position.xyz);
     out.world_normal = mesh_normal_local_to_world(
-        data.normal,
+        normal,
         vertex.instance_index
     );
     out.instance_index = vertex.instance_index;
 
-    out.blend_color = vec3<f32>(0.0, 0.2, 0.0);
+    out.local_position = position.xyz;
     out.ambient = 1.0;
 
     return out;
@@ -72,7 +108,25 @@ fn fragment(input: VertexOutput) -> FragmentOutput {
   pbr_input.N = normalize(pbr_input.world_normal);
 #endif
 
-  pbr_input.material.base_color = vec4<f32>(input.blend_color * input.ambient, 1.0);
+  // the constant part of the light stands in for block light, which isn't baked,
+  // so covered faces stay lit when the sky light is low
+#ifdef QUAD_STORAGE
+  let quad = unpack_quad_attributes(quad_attributes[input.quad]);
+  let block = quad.block;
+  let ambient = input.ambient * (0.4 + 0.6 * quad_ao(quad.ao, input.quad_uv)) * (0.3 + 0.7 * quad.light * sky_light);
+#else
+  // packed vertices have no baked light, every face counts as open to the sky
+  let block = input.block;
+  let ambient = input.ambient * (0.3 + 0.7 * sky_light);
+#endif
+
+  let color = block_color(block, input.local_position, pbr_input.world_normal);
+#ifdef MAY_DISCARD
+  if color.a < 0.5 {
+    discard;
+  }
+#endif
+  pbr_input.material.base_color = vec4<f32>(color.rgb * ambient, color.a);
 
   //pbr_input.material.reflectance = chunk_material.reflectance;
   //pbr_input.material.perceptual_roughness = chunk_material.perceptual_roughness;
@@ -86,6 +140,18 @@ fn fragment(input: VertexOutput) -> FragmentOutput {
   var out: FragmentOutput;
   // apply lighting
   out.color = apply_pbr_lighting(pbr_input);
+#ifdef DISTANCE_FOG
+  // height fog first, so the distance fog still fully covers the edge of the world
+  out.color = height_fog(
+    out.color,
+    input.world_position.xyz,
+    chunk_fog.height_density,
+    chunk_fog.height_falloff,
+    chunk_fog.base_height,
+  );
+  let fogged = apply_fog(fog, out.color, input.world_position.xyz, view.world_position.xyz);
+  out.color = mix(out.color, fogged, chunk_fog.distance_fog);
+#endif
   out.color = main_pass_post_lighting_processing(pbr_input, out.color);
 #endif
 
@@ -0,0 +1,46 @@
+#import bevy_pbr::mesh_view_bindings::{fog, view};
+#import bevy_pbr::mesh_view_types::{FOG_MODE_LINEAR, FOG_MODE_EXPONENTIAL, FOG_MODE_EXPONENTIAL_SQUARED, FOG_MODE_ATMOSPHERIC};
+#import bevy_pbr::fog::{linear_fog, exponential_fog, exponential_squared_fog, atmospheric_fog};
+
+// Fog of the voxel terrain, in the colour of the `DistanceFog` of the view. Only
+// imported when the view has one, with `DISTANCE_FOG` defined.
+
+// The view's distance fog without light scattering, for the shaders that don't
+// run the PBR lighting.
+fn view_fog(color: vec4<f32>, world_position: vec3<f32>) -> vec4<f32> {
+  let distance = length(world_position - view.world_position);
+  let scattering = vec3<f32>(0.0);
+  if fog.mode == FOG_MODE_LINEAR {
+    return linear_fog(fog, color, distance, scattering);
+  } else if fog.mode == FOG_MODE_EXPONENTIAL {
+    return exponential_fog(fog, color, distance, scattering);
+  } else if fog.mode == FOG_MODE_EXPONENTIAL_SQUARED {
+    return exponential_squared_fog(fog, color, distance, scattering);
+  } else if fog.mode == FOG_MODE_ATMOSPHERIC {
+    return atmospheric_fog(fog, color, distance, scattering);
+  }
+  return color;
+}
+
+// Exponential height fog, `density` at `base_height` and thinning out by
+// `falloff` per block above it. The density is integrated along the view ray, see
+// https://iquilezles.org/articles/fog/.
+fn height_fog(
+  color: vec4<f32>,
+  world_position: vec3<f32>,
+  density: f32,
+  falloff: f32,
+  base_height: f32,
+) -> vec4<f32> {
+  let ray = world_position - view.world_position;
+  let rate = max(falloff, 1e-4);
+  let view_density = density * exp(-rate * (view.world_position.y - base_height));
+  let rise = ray.y * rate;
+  // the average density along the ray relative to the one at the view
+  var average = 1.0;
+  if abs(rise) > 1e-3 {
+    average = (1.0 - exp(-rise)) / rise;
+  }
+  let amount = 1.0 - exp(-view_density * length(ray) * average);
+  return vec4<f32>(mix(color.rgb, fog.base_color.rgb, saturate(amount) * fog.base_color.a), color.a);
+}
@@ -0,0 +1,65 @@
+#import bevy_pbr::view_transformations::position_world_to_clip;
+#import "shaders/chunk_util.wgsl"::{unpack, unpack_quad_corner, block_color};
+#ifdef DISTANCE_FOG
+#import "shaders/chunk_fog.wgsl"::view_fog;
+#endif
+
+#ifdef VERTEX_PULLING
+@group(2) @binding(0) var<storage, read> quads: array<u32>;
+
+struct ChunkVertex {
+  @builtin(vertex_index) vertex_index: u32,
+  @location(1) chunk_translation: vec3<f32>,
+};
+#else
+struct ChunkVertex {
+  @location(0) data: u32,
+  @location(1) chunk_translation: vec3<f32>,
+};
+#endif
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) world_normal: vec3<f32>,
+  @location(1) local_position: vec3<f32>,
+  @location(2) @interpolate(flat) block: u32,
+  @location(3) world_position: vec3<f32>,
+};
+
+const light_direction: vec3<f32> = vec3<f32>(0.3, 1.0, 0.5);
+
+@vertex
+fn vertex(vertex: ChunkVertex) -> VertexOutput {
+#ifdef VERTEX_PULLING
+  // the base vertex of every chunk is four times its first quad
+  let data = unpack_quad_corner(quads[vertex.vertex_index >> 2u], vertex.vertex_index & 3u);
+#else
+  let data = unpack(vertex.data);
+#endif
+  var out: VertexOutput;
+
+  let world_position = data.position.xyz + vertex.chunk_translation;
+  out.position = position_world_to_clip(world_position);
+  out.world_normal = data.normal;
+  out.local_position = data.position.xyz;
+  out.block = data.block;
+  out.world_position = world_position;
+
+  return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+  let color = block_color(in.block, in.local_position, in.world_normal);
+  if color.a < 0.5 {
+    discard;
+  }
+
+  let diffuse = max(dot(in.world_normal, normalize(light_direction)), 0.0);
+  let lit = vec4<f32>(color.rgb * (0.6 + 0.4 * diffuse), 1.0);
+#ifdef DISTANCE_FOG
+  return view_fog(lit, in.world_position);
+#else
+  return lit;
+#endif
+}
@@ -1,21 +1,202 @@
-#import bevy_pbr::mesh_functions::{mesh_position_local_to_clip, get_world_from_local};
-#import "shaders/chunk_util.wgsl"::Vertex;
+#import bevy_pbr::view_transformations::position_world_to_clip;
+#ifndef PREPASS_PIPELINE
+#import bevy_pbr::mesh_view_bindings::{lights, view};
+#import bevy_pbr::mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT;
+#import bevy_pbr::shadows::fetch_directional_shadow;
+#ifdef DISTANCE_FOG
+#import "shaders/chunk_fog.wgsl"::view_fog;
+#endif
+#endif
 
+// Instanced props, one draw per prop kind and chunk. The mesh of the kind is the
+// vertex buffer and every instance is a `GpuInstanceData` from `material.rs`:
+//
+//   location 4: translation of the chunk entity
+//   location 3: packed prop as `.........vvvvrrrrzzzzzyyyyyxxxxx`, see `pack_prop`
+//               in `props.rs`
+//
+// x, y, z: cell the prop stands in, in the padded chunk space of the chunk meshes
+// r:       rotation around the vertical axis in steps of `ROTATION_STEPS`
+// v:       variant, picks a tint and a size
+//
+// With `PREPASS_PIPELINE` the props are drawn into shadow maps and the depth and
+// normal prepass by `instance_prepass.rs`, which only provides the normals for
+// the normal prepass.
+struct PropVertex {
+  @location(0) position: vec3<f32>,
+#ifdef PREPASS_PIPELINE
+#ifdef NORMAL_PREPASS
+  @location(1) normal: vec3<f32>,
+#endif
+#else
+  @location(1) normal: vec3<f32>,
+  // the alpha is how much the variant tints the colour
+  @location(5) color: vec4<f32>,
+#endif
+  @location(3) data: u32,
+  @location(4) chunk_translation: vec3<f32>,
+};
+
+struct UnpackedProp {
+  position: vec3<f32>,
+  rotation: f32,
+  variant: u32,
+}
+
+const ROTATION_STEPS: f32 = 16.0;
+const TAU: f32 = 6.28318530718;
+
+fn unpack_prop(data: u32) -> UnpackedProp {
+  let x = f32(data & 0x1F);
+  let y = f32((data >> 5) & 0x1F);
+  let z = f32((data >> 10) & 0x1F);
+  let rotation = f32((data >> 15) & 0xF) * TAU / ROTATION_STEPS;
+  let variant = (data >> 19) & 0xF;
+
+  return UnpackedProp(vec3<f32>(x, y, z), rotation, variant);
+}
+
+const variant_tints: array<vec3<f32>, 8> = array<vec3<f32>, 8>(
+  vec3<f32>(1.0, 1.0, 1.0),
+  vec3<f32>(0.95, 0.25, 0.2),
+  vec3<f32>(1.0, 0.85, 0.2),
+  vec3<f32>(0.35, 0.45, 1.0),
+  vec3<f32>(0.85, 0.4, 0.9),
+  vec3<f32>(1.1, 1.05, 0.8),
+  vec3<f32>(0.8, 0.9, 0.75),
+  vec3<f32>(1.0, 0.6, 0.3)
+);
+
+// Where a prop is placed, `rotate * (position * scale) + offset` moves a vertex
+// of its mesh into the world.
+struct PropPlacement {
+  rotate: mat3x3<f32>,
+  scale: f32,
+  offset: vec3<f32>,
+  variant: u32,
+}
+
+fn place_prop(data: u32, chunk_translation: vec3<f32>) -> PropPlacement {
+  let prop = unpack_prop(data);
+  // the upper half of the variants is a bit larger
+  let scale = select(1.0, 1.3, prop.variant >= 8u);
+
+  let c = cos(prop.rotation);
+  let s = sin(prop.rotation);
+  let rotate = mat3x3<f32>(
+    vec3<f32>(c, 0.0, -s),
+    vec3<f32>(0.0, 1.0, 0.0),
+    vec3<f32>(s, 0.0, c),
+  );
+
+  // stand on the center of the bottom of the cell
+  let offset = prop.position + vec3<f32>(0.5, 0.0, 0.5) + chunk_translation;
+  return PropPlacement(rotate, scale, offset, prop.variant);
+}
+
+#ifdef PREPASS_PIPELINE
 struct VertexOutput {
   @builtin(position) position: vec4<f32>,
+#ifdef NORMAL_PREPASS
+  @location(0) world_normal: vec3<f32>,
+#endif
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+  @location(1) unclipped_depth: f32,
+#endif
 }
 
 @vertex
-fn vertex(vertex: Vertex) -> VertexOutput {
+fn vertex(vertex: PropVertex) -> VertexOutput {
+  let placement = place_prop(vertex.data, vertex.chunk_translation);
+  let world_position = placement.rotate * (vertex.position * placement.scale) + placement.offset;
+
   var out: VertexOutput;
-  out.position = mesh_position_local_to_clip(
-    get_world_from_local(vertex.instance_index),
-    vec4<f32>(vertex.position, 1.0),
-  );
+  out.position = position_world_to_clip(world_position);
+#ifdef NORMAL_PREPASS
+  out.world_normal = placement.rotate * vertex.normal;
+#endif
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+  // clamp instead of clipping what's in front of the shadow map, the depth is
+  // written by the fragment shader
+  out.unclipped_depth = out.position.z;
+  out.position.z = min(out.position.z, 1.0);
+#endif
   return out;
 }
 
+#ifdef PREPASS_FRAGMENT
+struct FragmentOutput {
+#ifdef NORMAL_PREPASS
+  @location(0) normal: vec4<f32>,
+#endif
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+  @builtin(frag_depth) frag_depth: f32,
+#endif
+}
+
 @fragment
-fn fragment() -> @location(0) vec4<f32> {
-  return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+fn fragment(in: VertexOutput) -> FragmentOutput {
+  var out: FragmentOutput;
+#ifdef NORMAL_PREPASS
+  out.normal = vec4<f32>(normalize(in.world_normal) * 0.5 + vec3<f32>(0.5), 1.0);
+#endif
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+  out.frag_depth = in.unclipped_depth;
+#endif
+  return out;
+}
+#endif // PREPASS_FRAGMENT
+#else
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) world_normal: vec3<f32>,
+  @location(1) color: vec3<f32>,
+  @location(2) world_position: vec3<f32>,
+}
+
+// used without any directional light
+const light_direction: vec3<f32> = vec3<f32>(0.3, 1.0, 0.5);
+
+@vertex
+fn vertex(vertex: PropVertex) -> VertexOutput {
+  let placement = place_prop(vertex.data, vertex.chunk_translation);
+
+  var out: VertexOutput;
+  out.world_position = placement.rotate * (vertex.position * placement.scale) + placement.offset;
+  out.position = position_world_to_clip(out.world_position);
+  out.world_normal = placement.rotate * vertex.normal;
+  let tint = variant_tints[placement.variant & 7u];
+  out.color = mix(vertex.color.rgb, vertex.color.rgb * tint, vertex.color.a);
+  return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+  let normal = normalize(in.world_normal);
+
+  // lit by the first directional light, usually the sun, in its shadows
+  var direction = normalize(light_direction);
+  var shadow = 1.0;
+  if lights.n_directional_lights > 0u {
+    let light = lights.directional_lights[0];
+    direction = light.direction_to_light;
+    if (light.flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u {
+      let view_z = dot(vec4<f32>(
+        view.view_from_world[0].z,
+        view.view_from_world[1].z,
+        view.view_from_world[2].z,
+        view.view_from_world[3].z,
+      ), vec4<f32>(in.world_position, 1.0));
+      shadow = fetch_directional_shadow(0u, vec4<f32>(in.world_position, 1.0), normal, view_z);
+    }
+  }
+
+  let diffuse = max(dot(normal, direction), 0.0) * shadow;
+  let lit = vec4<f32>(in.color * (0.6 + 0.4 * diffuse), 1.0);
+#ifdef DISTANCE_FOG
+  return view_fog(lit, in.world_position);
+#else
+  return lit;
+#endif
 }
+#endif // PREPASS_PIPELINE
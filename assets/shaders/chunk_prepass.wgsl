@@ -1,7 +1,15 @@
-#import bevy_pbr::mesh_functions::{mesh_position_local_to_world, get_world_from_local, mesh_normal_local_to_world};
 #import bevy_pbr::prepass_io::FragmentOutput;
 #import bevy_pbr::view_transformations::position_world_to_clip;
-#import "shaders/chunk_util.wgsl"::{Vertex, unpack}
+#ifdef INDIRECT
+#import "shaders/chunk_util.wgsl"::{unpack, unpack_quad_corner, block_color}
+#else
+#import bevy_pbr::mesh_functions::{mesh_position_local_to_world, get_world_from_local, mesh_normal_local_to_world};
+#import bevy_pbr::mesh_bindings::mesh;
+#import "shaders/chunk_util.wgsl"::{Vertex, unpack, block_color}
+#endif
+#ifdef QUAD_STORAGE
+#import "shaders/chunk_util.wgsl"::{quad_attributes, unpack_quad_attributes, unpack_quad_position}
+#endif
 
 #ifdef DEFERRED_PREPASS
 #import bevy_pbr::rgb9e5
@@ -10,37 +18,103 @@
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) world_normal: vec3<f32>,
+    @location(1) local_position: vec3<f32>,
+    @location(2) @interpolate(flat) block: u32,
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+    @location(3) unclipped_depth: f32,
+#endif
 };
 
+#ifdef INDIRECT
+// Chunks of the indirect backend, with the same inputs as `chunk_indirect.wgsl`.
+#ifdef VERTEX_PULLING
+@group(2) @binding(0) var<storage, read> quads: array<u32>;
+
+struct ChunkVertex {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(1) chunk_translation: vec3<f32>,
+};
+#else
+struct ChunkVertex {
+    @location(0) data: u32,
+    @location(1) chunk_translation: vec3<f32>,
+};
+#endif
 
 @vertex
-fn vertex(vertex: Vertex) -> VertexOutput {
+fn vertex(vertex: ChunkVertex) -> VertexOutput {
+    var out: VertexOutput;
+#ifdef VERTEX_PULLING
+    let data = unpack_quad_corner(quads[vertex.vertex_index >> 2u], vertex.vertex_index & 3u);
+#else
     let data = unpack(vertex.data);
+#endif
+
+    out.world_normal = data.normal;
+    out.position = position_world_to_clip(data.position.xyz + vertex.chunk_translation);
+    out.local_position = data.position.xyz;
+    out.block = data.block;
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+    out.unclipped_depth = out.position.z;
+    out.position.z = min(out.position.z, 1.0);
+#endif
+
+    return out;
+}
+#else
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
     var out: VertexOutput;
+#ifdef QUAD_STORAGE
+    let quad_vertex = vertex.vertex_index - mesh[vertex.instance_index].first_vertex_index;
+    let quad = unpack_quad_attributes(quad_attributes[quad_vertex / 4u]);
+    let position = unpack_quad_position(vertex.data);
+    let normal = quad.normal;
+    out.block = quad.block;
+#else
+    let data = unpack(vertex.data);
+    let position = data.position;
+    let normal = data.normal;
+    out.block = data.block;
+#endif
 
     let world_to_local = get_world_from_local(vertex.instance_index);
     let world_position = mesh_position_local_to_world(
         world_to_local,
-        data.position
+        position
     );
-    out.world_normal = mesh_normal_local_to_world(data.normal, vertex.instance_index);
+    out.world_normal = mesh_normal_local_to_world(normal, vertex.instance_index);
     out.position = position_world_to_clip(world_position.xyz);
+    out.local_position = position.xyz;
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+    out.unclipped_depth = out.position.z;
+    out.position.z = min(out.position.z, 1.0);
+#endif
 
     return out;
 }
+#endif
 
 #ifdef PREPASS_FRAGMENT
 @fragment
 fn fragment(in: VertexOutput) -> FragmentOutput {
+#ifdef MAY_DISCARD
+    if block_color(in.block, in.local_position, in.world_normal).a < 0.5 {
+        discard;
+    }
+#endif
+
     var out: FragmentOutput;
 
-    out.frag_depth = in.position.z;
+#ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+    out.frag_depth = in.unclipped_depth;
+#endif
 #ifdef NORMAL_PREPASS
     out.normal = vec4(in.world_normal * 0.5 + vec3(0.5), 1.0);
 #endif
 
 #ifdef DEFERRED_PREPASS
-    // There isn't any material info available for this default prepass shader so we are just writing 
+    // There isn't any material info available for this default prepass shader so we are just writing
     // emissive magenta out to the deferred gbuffer to be rendered by the first deferred lighting pass layer.
     // This is here so if the default prepass fragment is used for deferred magenta will be rendered, and also
     // as an example to show that a user could write to the deferred gbuffer if they were to start from this shader.
@@ -50,4 +124,14 @@ fn fragment(in: VertexOutput) -> FragmentOutput {
 
     return out;
 }
-#endif // PREPASS_FRAGMENT
\ No newline at end of file
+#else
+// Depth only, the fragment shader just discards the holes of cutout blocks.
+@fragment
+fn fragment(in: VertexOutput) {
+#ifdef MAY_DISCARD
+    if block_color(in.block, in.local_position, in.world_normal).a < 0.5 {
+        discard;
+    }
+#endif
+}
+#endif // PREPASS_FRAGMENT
@@ -4,9 +4,10 @@ struct UnpackedData {
   height: f32,
   width: f32,
   direction: u32,
+  block: u32,
 }
 
-// format: xxxxxyyyyyzzzzzwwwwwhhhhh----ddd
+// format: xxxxxyyyyyzzzzzwwwwwhhhhhbbbbddd
 fn unpack(data: u32) -> UnpackedData {
   let x: f32 = f32((data >> 27) & 0x1F);
   let y: f32 = f32((data >> 22) & 0x1F);
@@ -14,13 +15,77 @@ fn unpack(data: u32) -> UnpackedData {
   let width: f32 = f32((data >> 12) & 0x1F);
   let height: f32 = f32((data >> 7) & 0x1F);
   let direction: u32 = data & 7;
+  let block: u32 = (data >> 3) & 0xF;
 
   let normal: vec3<f32> = normals[direction];
   let position = vec4<f32>(x, y, z, 1.0);
 
-  return UnpackedData(position, normal, height, width, direction);
+  return UnpackedData(position, normal, height, width, direction, block);
 }
 
+const BLOCK_LEAVES: u32 = 4u;
+
+// Base colour per block id, must match the ids in `block.rs`.
+const block_colors: array<vec4<f32>, 6> = array<vec4<f32>, 6>(
+  vec4<f32>(0.0, 0.0, 0.0, 0.0), // Air
+  vec4<f32>(0.0, 0.2, 0.0, 1.0), // Grass
+  vec4<f32>(0.05, 0.2, 0.5, 0.6), // Water
+  vec4<f32>(0.8, 0.9, 1.0, 0.25), // Glass
+  vec4<f32>(0.05, 0.35, 0.05, 1.0), // Leaves
+  vec4<f32>(1.0, 0.3, 0.0, 1.0) // Lava
+);
+
+// Colour of a block at a position in chunk space. Leaves get a coarse pattern
+// of fully transparent holes that cutout pipelines discard.
+fn block_color(block: u32, local_position: vec3<f32>, normal: vec3<f32>) -> vec4<f32> {
+  var color = block_colors[min(block, 5u)];
+
+  if block == BLOCK_LEAVES {
+    // step inside the block so cells on the face plane are stable
+    let cell = floor((local_position - normal * 0.01) * 4.0);
+    let noise = fract(sin(dot(cell, vec3<f32>(12.9898, 78.233, 37.719))) * 43758.5453);
+    if noise < 0.35 {
+      color.a = 0.0;
+    }
+  }
+
+  return color;
+}
+
+// Expands a packed quad into one of its corners. Corners follow the shared
+// quad index pattern 0 1 2 2 3 0, so faces keep the same winding as the
+// indices `ChunkMeshData::vertices` emits on the CPU.
+fn unpack_quad_corner(data: u32, corner: u32) -> UnpackedData {
+  var unpacked = unpack(data);
+
+  var c = corner;
+  if unpacked.direction == 0u || unpacked.direction == 2u || unpacked.direction == 5u {
+    c = (4u - corner) & 3u;
+  }
+
+  let axes = quad_axes[unpacked.direction];
+  var offset = vec3<f32>(0.0);
+  if c == 1u || c == 2u {
+    offset += axes[0] * unpacked.width;
+  }
+  if c == 2u || c == 3u {
+    offset += axes[1] * unpacked.height;
+  }
+  unpacked.position += vec4<f32>(offset, 0.0);
+
+  return unpacked;
+}
+
+// Axes spanned by the width and height of a quad, must match `QUAD_AXES` in `mesh.rs`.
+const quad_axes: array<mat2x3<f32>, 6> = array<mat2x3<f32>, 6>(
+  mat2x3<f32>(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0)),
+  mat2x3<f32>(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0)),
+  mat2x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0)),
+  mat2x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0)),
+  mat2x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0)),
+  mat2x3<f32>(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0))
+);
+
 const normals: array<vec3<f32>,6> = array<vec3<f32>,6> (
 	vec3<f32>(1.0, 0.0, 0.0), // Left
 	vec3<f32>(-1.0, 0.0, 0.0), // Right
@@ -32,6 +97,55 @@ const normals: array<vec3<f32>,6> = array<vec3<f32>,6> (
 
 struct Vertex {
     @builtin(instance_index) instance_index: u32,
-    @location(0) position: vec3<f32>,
-    @location(3) data: u32,
+    @builtin(vertex_index) vertex_index: u32,
+    // a packed quad, or only a packed position with `QUAD_STORAGE`
+    @location(0) data: u32,
 };
+
+#ifdef QUAD_STORAGE
+// One packed `u32` per quad of a greedy mesh, see `unpack_quad_attributes`.
+@group(#{MATERIAL_BIND_GROUP}) @binding(0) var<storage, read> quad_attributes: array<u32>;
+#endif
+
+struct QuadAttributes {
+  normal: vec3<f32>,
+  block: u32,
+  // occlusion of each corner from 0 (dark) to 1, in the order of the quad's vertices
+  ao: vec4<f32>,
+  // sky light from 0 to 1
+  light: f32,
+}
+
+// format: ............llllaaaaaaaabbbbddd, see `pack_quad_attributes` in `greedy.rs`
+fn unpack_quad_attributes(data: u32) -> QuadAttributes {
+  let direction = data & 7u;
+  let block = (data >> 3u) & 0xFu;
+  let ao = vec4<f32>(
+    f32((data >> 7u) & 3u),
+    f32((data >> 9u) & 3u),
+    f32((data >> 11u) & 3u),
+    f32((data >> 13u) & 3u),
+  ) / 3.0;
+  let light = f32((data >> 15u) & 0xFu) / 15.0;
+
+  return QuadAttributes(normals[direction], block, ao, light);
+}
+
+// format: xxxxxyyyyyzzzzz, see `QUAD_POSITION_ATTRIBUTE` in `greedy.rs`
+fn unpack_quad_position(data: u32) -> vec4<f32> {
+  return vec4<f32>(f32((data >> 10u) & 0x1Fu), f32((data >> 5u) & 0x1Fu), f32(data & 0x1Fu), 1.0);
+}
+
+// Position of each vertex of a quad within the quad, matching the corner order
+// of its attributes.
+const quad_corner_uvs: array<vec2<f32>, 4> = array<vec2<f32>, 4>(
+  vec2<f32>(0.0, 0.0),
+  vec2<f32>(1.0, 0.0),
+  vec2<f32>(1.0, 1.0),
+  vec2<f32>(0.0, 1.0)
+);
+
+// Occlusion at a point of a quad, bilinear between its corners.
+fn quad_ao(ao: vec4<f32>, uv: vec2<f32>) -> f32 {
+  return mix(mix(ao.x, ao.y, uv.x), mix(ao.w, ao.z, uv.x), uv.y);
+}
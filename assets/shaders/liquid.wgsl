@@ -0,0 +1,141 @@
+#import bevy_pbr::mesh_functions::{get_world_from_local, mesh_position_local_to_world, mesh_normal_local_to_world};
+#import bevy_pbr::view_transformations::{position_world_to_clip, depth_ndc_to_view_z};
+#import bevy_pbr::mesh_view_bindings::globals;
+#import bevy_pbr::mesh_bindings::mesh;
+#import bevy_pbr::pbr_functions::{calculate_view, apply_pbr_lighting, main_pass_post_lighting_processing};
+#import bevy_pbr::pbr_types::{pbr_input_new, STANDARD_MATERIAL_FLAGS_FOG_ENABLED_BIT};
+#import bevy_pbr::prepass_utils;
+
+struct LiquidMaterial {
+    shallow_color: vec4<f32>,
+    deep_color: vec4<f32>,
+    emissive: vec4<f32>,
+    absorption: f32,
+    wave_amplitude: f32,
+    wave_length: f32,
+    wave_speed: f32,
+    normal_scroll_speed: f32,
+    normal_strength: f32,
+};
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(0) var<uniform> material: LiquidMaterial;
+@group(#{MATERIAL_BIND_GROUP}) @binding(1) var normal_map: texture_2d<f32>;
+@group(#{MATERIAL_BIND_GROUP}) @binding(2) var normal_sampler: sampler;
+
+// Must match `LIQUID_SURFACE_BIT` in `mesh.rs`.
+const SURFACE_BIT: u32 = 16u;
+const TAU: f32 = 6.28318530718;
+// Blocks covered by one repeat of the normal map.
+const NORMAL_MAP_SCALE: f32 = 8.0;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) data: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) world_position: vec4<f32>,
+    @location(2) @interpolate(flat) instance_index: u32,
+};
+
+// Two crossing sine waves, returns the height and its gradient along x and z.
+fn wave(xz: vec2<f32>) -> vec3<f32> {
+    let k = TAU / material.wave_length;
+    let t = globals.time * material.wave_speed;
+    let d1 = vec2(0.8, 0.6) * k;
+    let d2 = vec2(-0.5, 0.9) * k * 1.3;
+    let p1 = dot(xz, d1) + t;
+    let p2 = dot(xz, d2) + t * 1.1;
+
+    let a = material.wave_amplitude * 0.5;
+    let height = a * (sin(p1) + sin(p2));
+    let gradient = a * (d1 * cos(p1) + d2 * cos(p2));
+    return vec3(height, gradient);
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_from_local = get_world_from_local(vertex.instance_index);
+    out.world_position = mesh_position_local_to_world(
+        world_from_local,
+        vec4(vertex.position, 1.0)
+    );
+    if (vertex.data & SURFACE_BIT) != 0u {
+        out.world_position.y += wave(out.world_position.xz).x;
+    }
+    out.position = position_world_to_clip(out.world_position.xyz);
+    out.world_normal = mesh_normal_local_to_world(
+        vertex.normal,
+        vertex.instance_index
+    );
+    out.instance_index = vertex.instance_index;
+
+    return out;
+}
+
+fn sample_normal(uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(normal_map, normal_sampler, uv).xyz * 2.0 - 1.0;
+}
+
+@fragment
+fn fragment(
+    input: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> @location(0) vec4<f32> {
+    var normal = normalize(input.world_normal);
+    if normal.y > 0.5 {
+        // two normal map layers scrolling in different directions on top of the waves
+        let uv = input.world_position.xz / NORMAL_MAP_SCALE;
+        let scroll = globals.time * material.normal_scroll_speed;
+        let n1 = sample_normal(uv + vec2(scroll, scroll * 0.5));
+        let n2 = sample_normal(uv * 1.7 + vec2(-scroll * 0.7, scroll * 0.3));
+        let detail = (n1.xy + n2.xy) * material.normal_strength;
+
+        let gradient = wave(input.world_position.xz).yz;
+        normal = normalize(vec3(detail.x - gradient.x, 1.0, detail.y - gradient.y));
+    }
+    if !is_front {
+        normal = -normal;
+    }
+
+    var pbr_input = pbr_input_new();
+    pbr_input.flags = mesh[input.instance_index].flags;
+    pbr_input.V = calculate_view(input.world_position, false);
+    pbr_input.frag_coord = input.position;
+    pbr_input.world_position = input.world_position;
+    pbr_input.world_normal = normal;
+    pbr_input.N = normal;
+
+    // distance the view ray travels through the liquid before hitting opaque geometry
+    var thickness = 1.0;
+#ifdef DEPTH_PREPASS
+    let scene_z = depth_ndc_to_view_z(prepass_utils::prepass_depth(input.position, 0u));
+    let surface_z = depth_ndc_to_view_z(input.position.z);
+    thickness = max(surface_z - scene_z, 0.0);
+#endif
+    let transmittance = exp(-thickness * material.absorption);
+    let color = mix(material.deep_color, material.shallow_color, transmittance);
+
+    // Schlick's approximation with the reflectance of water
+    let n_dot_v = saturate(dot(normal, pbr_input.V));
+    let fresnel = 0.02 + 0.98 * pow(1.0 - n_dot_v, 5.0);
+    let opacity = color.a + (1.0 - color.a) * (1.0 - transmittance);
+    let alpha = mix(opacity, 1.0, fresnel);
+
+    pbr_input.material.base_color = vec4(color.rgb, alpha);
+    pbr_input.material.perceptual_roughness = 0.08;
+    pbr_input.material.reflectance = vec3(0.02);
+    pbr_input.material.emissive = material.emissive;
+    // the view's distance fog, like the chunks around the liquid
+    pbr_input.material.flags |= STANDARD_MATERIAL_FLAGS_FOG_ENABLED_BIT;
+
+    var out = apply_pbr_lighting(pbr_input);
+    out = main_pass_post_lighting_processing(pbr_input, out);
+    return out;
+}
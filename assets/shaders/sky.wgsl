@@ -0,0 +1,59 @@
+#import bevy_pbr::mesh_view_bindings::view;
+#import bevy_pbr::view_transformations::position_world_to_clip;
+#ifdef TONEMAP_IN_SHADER
+#import bevy_core_pipeline::tonemapping::tone_mapping;
+#endif
+
+// `SkyUniform` in `sky.rs`, colours are linear
+struct Sky {
+  zenith: vec4<f32>,
+  horizon: vec4<f32>,
+  ground: vec4<f32>,
+  sun_color: vec4<f32>,
+  sun_direction: vec3<f32>,
+};
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(0) var<uniform> sky: Sky;
+
+struct Vertex {
+  @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) direction: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+  var out: VertexOutput;
+  // a unit sphere around the view, moved onto the far plane so everything else
+  // is drawn in front of it
+  out.position = position_world_to_clip(view.world_position + vertex.position);
+  out.position.z = 0.0;
+  out.direction = vertex.position;
+  return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+  let direction = normalize(in.direction);
+
+  var color: vec3<f32>;
+  if direction.y >= 0.0 {
+    color = mix(sky.horizon.rgb, sky.zenith.rgb, sqrt(direction.y));
+  } else {
+    // a narrow band fading into the ground, so the horizon matches the fog
+    color = mix(sky.horizon.rgb, sky.ground.rgb, smoothstep(0.0, 0.2, -direction.y));
+  }
+
+  // scattering around the sun and its disk
+  let sun = max(dot(direction, sky.sun_direction), 0.0);
+  color += sky.sun_color.rgb * (0.4 * pow(sun, 32.0) + 8.0 * smoothstep(0.9994, 0.9997, sun));
+
+  var out = vec4<f32>(color, 1.0);
+#ifdef TONEMAP_IN_SHADER
+  out = tone_mapping(out, view.color_grading);
+#endif
+  return out;
+}
@@ -0,0 +1,154 @@
+//! Flies a fixed camera path through the world and prints frame statistics.
+
+use std::f32::consts::TAU;
+
+use bevy::{diagnostic::DiagnosticsStore, prelude::*};
+use serde::Serialize;
+
+use crate::{
+  camera::{CameraPath, CameraPlayback, CameraSample},
+  voxel::{VoxelDiagnosticsPlugin, VoxelWorld},
+};
+
+/// Length of the benchmark path.
+const DURATION: f32 = 30.0;
+/// Time between two samples of the benchmark path.
+const SAMPLE_INTERVAL: f32 = 0.5;
+/// Speed along the path in blocks per second, fast enough to keep chunk streaming busy.
+const SPEED: f32 = 20.0;
+
+/// Plays [`benchmark_path`] on the first controlled camera, then prints frame time
+/// statistics as JSON and exits.
+pub struct BenchmarkPlugin;
+
+impl Plugin for BenchmarkPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<BenchmarkRun>()
+      .add_systems(Startup, start_benchmark)
+      .add_systems(Update, record_benchmark);
+  }
+}
+
+#[derive(Resource, Default)]
+struct BenchmarkRun {
+  /// Seconds between frames while the path plays.
+  frame_times: Vec<f32>,
+  started: bool,
+}
+
+/// Straight flight along negative Z that sways sideways and up and down, always
+/// looking ahead.
+pub fn benchmark_path() -> CameraPath {
+  let position = |time: f32| {
+    Vec3::new(
+      (time / DURATION * TAU * 2.0).sin() * 24.0,
+      24.0 + (time / DURATION * TAU * 3.0).sin() * 6.0,
+      -time * SPEED,
+    )
+  };
+
+  let count = (DURATION / SAMPLE_INTERVAL) as usize;
+  let samples = (0..=count)
+    .map(|i| {
+      let time = i as f32 * SAMPLE_INTERVAL;
+      let translation = position(time);
+      let ahead = position(time + SAMPLE_INTERVAL) - Vec3::Y * 4.0;
+      CameraSample {
+        time,
+        translation,
+        rotation: Transform::from_translation(translation)
+          .looking_at(ahead, Vec3::Y)
+          .rotation,
+      }
+    })
+    .collect();
+  CameraPath { samples }
+}
+
+fn start_benchmark(mut playback: ResMut<CameraPlayback>) {
+  playback.play(benchmark_path());
+}
+
+/// Frame time statistics in milliseconds.
+#[derive(Serialize)]
+struct FrameTimeReport {
+  mean: f64,
+  p50: f64,
+  p95: f64,
+  p99: f64,
+  max: f64,
+}
+
+#[derive(Serialize)]
+struct BenchmarkReport {
+  frames: usize,
+  duration_s: f64,
+  mean_fps: f64,
+  frame_time_ms: FrameTimeReport,
+  chunks_loaded: usize,
+  /// Average time to generate a chunk over the last samples, if diagnostics are enabled.
+  generation_ms: Option<f64>,
+  /// Average time to mesh a chunk over the last samples, if diagnostics are enabled.
+  meshing_ms: Option<f64>,
+}
+
+fn record_benchmark(
+  time: Res<Time>,
+  playback: Res<CameraPlayback>,
+  world: Res<VoxelWorld>,
+  diagnostics: Res<DiagnosticsStore>,
+  mut run: ResMut<BenchmarkRun>,
+  mut exit: MessageWriter<AppExit>,
+) {
+  if playback.is_playing() {
+    // the first frame includes startup
+    if run.started {
+      run.frame_times.push(time.delta_secs());
+    }
+    run.started = true;
+    return;
+  }
+  if !run.started {
+    return;
+  }
+
+  let mut frame_times: Vec<f64> = run
+    .frame_times
+    .iter()
+    .map(|time| *time as f64 * 1e3)
+    .collect();
+  frame_times.sort_unstable_by(f64::total_cmp);
+  let total: f64 = frame_times.iter().sum();
+  let percentile = |percent: f64| {
+    let index = ((frame_times.len().max(1) - 1) as f64 * percent / 100.0).round() as usize;
+    frame_times.get(index).copied().unwrap_or_default()
+  };
+  let average = |path| {
+    diagnostics
+      .get(&path)
+      .and_then(|diagnostic| diagnostic.average())
+  };
+
+  let report = BenchmarkReport {
+    frames: frame_times.len(),
+    duration_s: total / 1e3,
+    mean_fps: frame_times.len() as f64 / (total / 1e3).max(f64::EPSILON),
+    frame_time_ms: FrameTimeReport {
+      mean: total / frame_times.len().max(1) as f64,
+      p50: percentile(50.0),
+      p95: percentile(95.0),
+      p99: percentile(99.0),
+      max: frame_times.last().copied().unwrap_or_default(),
+    },
+    chunks_loaded: world.chunk_positions().count(),
+    generation_ms: average(VoxelDiagnosticsPlugin::GENERATION_TIME),
+    meshing_ms: average(VoxelDiagnosticsPlugin::MESHING_TIME),
+  };
+  match serde_json::to_string_pretty(&report) {
+    Ok(json) => println!("{json}"),
+    Err(error) => error!("Could not serialize benchmark report: {error}"),
+  }
+  run.started = false;
+  exit.write(AppExit::Success);
+}
@@ -0,0 +1,242 @@
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use bevy::{
+  asset::{AssetLoader, LoadContext, io::Reader},
+  prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Bindings file inside the `assets` folder.
+pub const BINDINGS_PATH: &str = "input.bindings.ron";
+
+/// Something the player can do, bound to inputs through [`Bindings`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+  MoveForward,
+  MoveBack,
+  MoveLeft,
+  MoveRight,
+  MoveUp,
+  MoveDown,
+  /// Use [`run_speed`](super::controller::CameraController::run_speed) instead of
+  /// [`walk_speed`](super::controller::CameraController::walk_speed).
+  Sprint,
+  /// Grab the cursor while held.
+  HoldGrab,
+  /// Grab or release the cursor.
+  ToggleGrab,
+  /// Switch to the next [`MovementMode`](super::controller::MovementMode).
+  CycleMovementMode,
+  /// Switch to the next [`CameraMode`](super::controller::CameraMode).
+  CycleCameraMode,
+  /// Drag the orbit focus with the mouse while held.
+  Pan,
+  /// Start or stop recording a [`CameraPath`](super::path::CameraPath).
+  ToggleRecording,
+  /// Start or stop playing back the last recorded [`CameraPath`](super::path::CameraPath).
+  TogglePlayback,
+}
+
+/// A single input an [`Action`] can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+  Key(KeyCode),
+  Mouse(MouseButton),
+  Gamepad(GamepadButton),
+}
+
+/// Current state of every input device [`Bindings`] can read from.
+pub struct InputState<'a> {
+  pub keys: &'a ButtonInput<KeyCode>,
+  pub mouse: &'a ButtonInput<MouseButton>,
+  pub gamepad: Option<&'a Gamepad>,
+}
+
+/// Maps [`Action`]s to one or more inputs.
+///
+/// Loaded from [`BINDINGS_PATH`] by the [`BindingsPlugin`] and reloaded whenever
+/// the file changes.
+#[derive(Resource, Asset, TypePath, Clone, Debug, Serialize, Deserialize)]
+pub struct Bindings(pub BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+  fn default() -> Self {
+    use Action::*;
+    use Binding::*;
+
+    Self(BTreeMap::from([
+      (MoveForward, vec![Key(KeyCode::KeyW)]),
+      (MoveBack, vec![Key(KeyCode::KeyS)]),
+      (MoveLeft, vec![Key(KeyCode::KeyA)]),
+      (MoveRight, vec![Key(KeyCode::KeyD)]),
+      (
+        MoveUp,
+        vec![Key(KeyCode::KeyE), Gamepad(GamepadButton::RightTrigger2)],
+      ),
+      (
+        MoveDown,
+        vec![Key(KeyCode::KeyQ), Gamepad(GamepadButton::LeftTrigger2)],
+      ),
+      (
+        Sprint,
+        vec![Key(KeyCode::ShiftLeft), Gamepad(GamepadButton::LeftThumb)],
+      ),
+      (HoldGrab, vec![Mouse(MouseButton::Left)]),
+      (ToggleGrab, vec![Key(KeyCode::KeyM)]),
+      (
+        CycleMovementMode,
+        vec![Key(KeyCode::KeyV), Gamepad(GamepadButton::North)],
+      ),
+      (
+        CycleCameraMode,
+        vec![Key(KeyCode::KeyC), Gamepad(GamepadButton::West)],
+      ),
+      (Pan, vec![Mouse(MouseButton::Middle)]),
+      (ToggleRecording, vec![Key(KeyCode::F9)]),
+      (TogglePlayback, vec![Key(KeyCode::F10)]),
+    ]))
+  }
+}
+
+impl Bindings {
+  fn bindings(&self, action: Action) -> &[Binding] {
+    self.0.get(&action).map(Vec::as_slice).unwrap_or_default()
+  }
+
+  pub fn pressed(&self, action: Action, input: &InputState) -> bool {
+    self.bindings(action).iter().any(|binding| match *binding {
+      Binding::Key(key) => input.keys.pressed(key),
+      Binding::Mouse(button) => input.mouse.pressed(button),
+      Binding::Gamepad(button) => input.gamepad.is_some_and(|gamepad| gamepad.pressed(button)),
+    })
+  }
+
+  pub fn just_pressed(&self, action: Action, input: &InputState) -> bool {
+    self.bindings(action).iter().any(|binding| match *binding {
+      Binding::Key(key) => input.keys.just_pressed(key),
+      Binding::Mouse(button) => input.mouse.just_pressed(button),
+      Binding::Gamepad(button) => input
+        .gamepad
+        .is_some_and(|gamepad| gamepad.just_pressed(button)),
+    })
+  }
+
+  pub fn just_released(&self, action: Action, input: &InputState) -> bool {
+    self.bindings(action).iter().any(|binding| match *binding {
+      Binding::Key(key) => input.keys.just_released(key),
+      Binding::Mouse(button) => input.mouse.just_released(button),
+      Binding::Gamepad(button) => input
+        .gamepad
+        .is_some_and(|gamepad| gamepad.just_released(button)),
+    })
+  }
+
+  /// Strongest input of an action in `0.0..=1.0`, analog for gamepad triggers.
+  pub fn value(&self, action: Action, input: &InputState) -> f32 {
+    self
+      .bindings(action)
+      .iter()
+      .map(|binding| match *binding {
+        Binding::Key(key) => input.keys.pressed(key) as u8 as f32,
+        Binding::Mouse(button) => input.mouse.pressed(button) as u8 as f32,
+        Binding::Gamepad(button) => input
+          .gamepad
+          .and_then(|gamepad| gamepad.get(button))
+          .unwrap_or(0.0),
+      })
+      .fold(0.0, f32::max)
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BindingsError> {
+    let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+    fs::write(path, ron)?;
+    Ok(())
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum BindingsError {
+  #[error("could not read or write bindings: {0}")]
+  Io(#[from] io::Error),
+  #[error("could not parse bindings: {0}")]
+  Parse(#[from] ron::de::SpannedError),
+  #[error("could not serialize bindings: {0}")]
+  Serialize(#[from] ron::Error),
+}
+
+#[derive(Default)]
+struct BindingsLoader;
+
+impl AssetLoader for BindingsLoader {
+  type Asset = Bindings;
+  type Settings = ();
+  type Error = BindingsError;
+
+  async fn load(
+    &self,
+    reader: &mut dyn Reader,
+    _settings: &(),
+    _load_context: &mut LoadContext<'_>,
+  ) -> Result<Bindings, BindingsError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).await?;
+    Ok(ron::de::from_bytes(&bytes)?)
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["bindings.ron"]
+  }
+}
+
+#[derive(Resource)]
+struct BindingsHandle(Handle<Bindings>);
+
+/// Keeps the [`Bindings`] resource in sync with the bindings file, writing the
+/// default bindings there if it doesn't exist yet.
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<Bindings>()
+      .init_asset::<Bindings>()
+      .init_asset_loader::<BindingsLoader>()
+      .add_systems(Startup, load_bindings)
+      .add_systems(PreUpdate, apply_bindings);
+  }
+}
+
+fn load_bindings(mut commands: Commands, asset_server: Res<AssetServer>, bindings: Res<Bindings>) {
+  let path = Path::new("assets").join(BINDINGS_PATH);
+  if !path.exists()
+    && let Err(error) = bindings.save(&path)
+  {
+    warn!(
+      "Could not write default bindings to {}: {error}",
+      path.display()
+    );
+  }
+
+  commands.insert_resource(BindingsHandle(asset_server.load(BINDINGS_PATH)));
+}
+
+fn apply_bindings(
+  mut events: MessageReader<AssetEvent<Bindings>>,
+  handle: Option<Res<BindingsHandle>>,
+  assets: Res<Assets<Bindings>>,
+  mut bindings: ResMut<Bindings>,
+) {
+  let Some(handle) = handle else {
+    return;
+  };
+
+  for event in events.read() {
+    if (event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0))
+      && let Some(loaded) = assets.get(&handle.0)
+    {
+      *bindings = loaded.clone();
+      info!("Loaded input bindings");
+    }
+  }
+}
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+
+/// Gap kept between a box and the blocks it collides with.
+const SKIN: f32 = 1e-3;
+/// Longest distance moved at once, so fast boxes can't tunnel through blocks.
+const MAX_SWEEP_STEP: f32 = 0.45;
+/// How far below a box is searched for ground.
+const GROUND_PROBE: f32 = 0.05;
+/// Distance between the positions tested by [`cast_box`].
+const CAST_STEP: f32 = 0.05;
+
+/// Whether an axis aligned box overlaps any solid block.
+pub fn overlaps_solid(center: Vec3, half_extents: Vec3, is_solid: &impl Fn(IVec3) -> bool) -> bool {
+  // shrink the box slightly so touching faces don't count as overlapping
+  let min = (center - half_extents + SKIN * 0.5).floor().as_ivec3();
+  let max = (center + half_extents - SKIN * 0.5).floor().as_ivec3();
+
+  for x in min.x..=max.x {
+    for y in min.y..=max.y {
+      for z in min.z..=max.z {
+        if is_solid(IVec3::new(x, y, z)) {
+          return true;
+        }
+      }
+    }
+  }
+  false
+}
+
+/// How far a box can move from `center` along `direction` before it overlaps a
+/// solid block, at most `max_distance`.
+pub fn cast_box(
+  center: Vec3,
+  half_extents: Vec3,
+  direction: Vec3,
+  max_distance: f32,
+  is_solid: &impl Fn(IVec3) -> bool,
+) -> f32 {
+  let direction = direction.normalize_or_zero();
+  let steps = (max_distance / CAST_STEP).ceil() as u32;
+  for step in 1..=steps {
+    let distance = (step as f32 * CAST_STEP).min(max_distance);
+    if overlaps_solid(center + direction * distance, half_extents, is_solid) {
+      return ((step - 1) as f32 * CAST_STEP).max(0.0);
+    }
+  }
+  max_distance.max(0.0)
+}
+
+/// Moves a box along one axis until it touches a solid block, returns the distance moved.
+fn sweep_axis(
+  center: Vec3,
+  half_extents: Vec3,
+  axis: usize,
+  delta: f32,
+  is_solid: &impl Fn(IVec3) -> bool,
+) -> f32 {
+  if delta == 0.0 {
+    return 0.0;
+  }
+
+  let steps = (delta.abs() / MAX_SWEEP_STEP).ceil();
+  let step = delta / steps;
+  let mut moved = 0.0;
+  for _ in 0..steps as u32 {
+    let mut target = center;
+    target[axis] += moved + step;
+    if overlaps_solid(target, half_extents, is_solid) {
+      // snap to the face of the block that was hit
+      let face = if step > 0.0 {
+        (target[axis] + half_extents[axis]).floor() - half_extents[axis] - SKIN
+      } else {
+        (target[axis] - half_extents[axis]).ceil() + half_extents[axis] + SKIN
+      };
+      let snapped = face - center[axis];
+      return if step > 0.0 {
+        snapped.clamp(moved, moved + step)
+      } else {
+        snapped.clamp(moved + step, moved)
+      };
+    }
+    moved += step;
+  }
+  moved
+}
+
+/// Moves a box by `delta`, one axis at a time, stopping at solid blocks.
+///
+/// Returns the new center and the axes along which the movement was blocked.
+pub fn sweep(
+  center: Vec3,
+  half_extents: Vec3,
+  delta: Vec3,
+  is_solid: &impl Fn(IVec3) -> bool,
+) -> (Vec3, BVec3) {
+  let mut position = center;
+  let mut blocked = BVec3::FALSE;
+  for axis in [1, 0, 2] {
+    let moved = sweep_axis(position, half_extents, axis, delta[axis], is_solid);
+    position[axis] += moved;
+    blocked.set(axis, moved.abs() < delta[axis].abs());
+  }
+  (position, blocked)
+}
+
+/// Like [`sweep`], but a box standing on the ground steps up onto blocks up to
+/// `step_height` high. With `avoid_edges` it doesn't move off the ground sideways.
+pub fn walk(
+  center: Vec3,
+  half_extents: Vec3,
+  delta: Vec3,
+  step_height: f32,
+  avoid_edges: bool,
+  is_solid: &impl Fn(IVec3) -> bool,
+) -> (Vec3, BVec3) {
+  let mut position = center;
+  let mut blocked = BVec3::FALSE;
+
+  let moved = sweep_axis(position, half_extents, 1, delta.y, is_solid);
+  position.y += moved;
+  blocked.y = moved.abs() < delta.y.abs();
+  let on_ground = blocked.y && delta.y <= 0.0;
+
+  for axis in [0, 2] {
+    let mut moved = sweep_axis(position, half_extents, axis, delta[axis], is_solid);
+    let mut next = position;
+    next[axis] += moved;
+
+    if on_ground && moved.abs() < delta[axis].abs() && step_height > 0.0 {
+      let up = sweep_axis(position, half_extents, 1, step_height, is_solid);
+      let mut raised = position;
+      raised.y += up;
+      let raised_moved = sweep_axis(raised, half_extents, axis, delta[axis], is_solid);
+      if raised_moved.abs() > moved.abs() {
+        raised[axis] += raised_moved;
+        raised.y += sweep_axis(raised, half_extents, 1, -up, is_solid);
+        next = raised;
+        moved = raised_moved;
+      }
+    }
+
+    if avoid_edges
+      && on_ground
+      && !overlaps_solid(next - Vec3::Y * GROUND_PROBE, half_extents, is_solid)
+    {
+      blocked.set(axis, true);
+      continue;
+    }
+
+    position = next;
+    blocked.set(axis, moved.abs() < delta[axis].abs());
+  }
+
+  (position, blocked)
+}
@@ -1,5 +1,62 @@
 use bevy::prelude::*;
 
+/// How a [`CameraController`] moves through the world.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MovementMode {
+  /// Free flight that collides with solid blocks.
+  Fly,
+  /// Free flight through everything.
+  #[default]
+  Noclip,
+  /// Walking with gravity, [`MoveUp`](super::bindings::Action::MoveUp) jumps and
+  /// [`MoveDown`](super::bindings::Action::MoveDown) crouches.
+  Walk,
+}
+
+impl MovementMode {
+  pub fn next(self) -> Self {
+    match self {
+      MovementMode::Fly => MovementMode::Noclip,
+      MovementMode::Noclip => MovementMode::Walk,
+      MovementMode::Walk => MovementMode::Fly,
+    }
+  }
+}
+
+/// Where a [`CameraController`] puts the camera relative to the moving body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+  /// The camera is the moving body.
+  #[default]
+  FirstPerson,
+  /// Follows the body on a boom arm that is pulled in when blocks are in the way.
+  ThirdPerson,
+  /// Rotates around [`focus`](CameraController::focus) without moving the body, scroll
+  /// zooms and [`Pan`](super::bindings::Action::Pan) drags the focus.
+  Orbit,
+}
+
+impl CameraMode {
+  pub fn next(self) -> Self {
+    match self {
+      CameraMode::FirstPerson => CameraMode::ThirdPerson,
+      CameraMode::ThirdPerson => CameraMode::Orbit,
+      CameraMode::Orbit => CameraMode::FirstPerson,
+    }
+  }
+}
+
+/// Input devices a [`CameraController`] reads from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputSource {
+  /// Keyboard, mouse and the gamepad picked by [`select_gamepad`](super::gamepad::select_gamepad).
+  #[default]
+  Any,
+  KeyboardMouse,
+  /// Only the connected gamepad with this index, ordered by entity.
+  Gamepad(usize),
+}
+
 /// Camera controller [`Component`].
 #[derive(Component)]
 pub struct CameraController {
@@ -9,40 +66,76 @@ pub struct CameraController {
   pub initialized: bool,
   /// Multiplier for pitch and yaw rotation speed.
   pub sensitivity: f32,
-  /// [`KeyCode`] for forward translation.
-  pub key_forward: KeyCode,
-  /// [`KeyCode`] for backward translation.
-  pub key_back: KeyCode,
-  /// [`KeyCode`] for left translation.
-  pub key_left: KeyCode,
-  /// [`KeyCode`] for right translation.
-  pub key_right: KeyCode,
-  /// [`KeyCode`] for up translation.
-  pub key_up: KeyCode,
-  /// [`KeyCode`] for down translation.
-  pub key_down: KeyCode,
-  /// [`KeyCode`] to use [`run_speed`](CameraController::run_speed) instead of
-  /// [`walk_speed`](CameraController::walk_speed) for translation.
-  pub key_run: KeyCode,
-  /// [`MouseButton`] for grabbing the mouse focus.
-  pub mouse_key_cursor_grab: MouseButton,
-  /// [`KeyCode`] for grabbing the keyboard focus.
-  pub keyboard_key_toggle_cursor_grab: KeyCode,
+  pub input: InputSource,
+  /// Whether [`ToggleGrab`](super::bindings::Action::ToggleGrab) grabbed the cursor.
+  pub cursor_grab_toggled: bool,
+  /// Whether [`HoldGrab`](super::bindings::Action::HoldGrab) is held.
+  pub cursor_grab_held: bool,
+  /// Gamepad this controller listens to, `None` until one is connected.
+  ///
+  /// Switches to any other gamepad as soon as a button on it is pressed.
+  pub gamepad: Option<Entity>,
+  /// Stick and trigger values below this are ignored.
+  pub gamepad_deadzone: f32,
+  /// Exponent of the response curve applied to stick and trigger values after the deadzone.
+  pub gamepad_response_exponent: f32,
+  /// Rotation speed in radians per second with the right stick fully deflected.
+  pub gamepad_look_speed: f32,
+  pub movement_mode: MovementMode,
+  pub camera_mode: CameraMode,
+  /// Transform moved by the controller, the camera follows it as set by
+  /// [`camera_mode`](CameraController::camera_mode).
+  pub body: Transform,
+  /// Point rotated around in [`CameraMode::Orbit`].
+  pub focus: Vec3,
+  /// Distance between the camera and [`focus`](CameraController::focus).
+  pub orbit_distance: f32,
+  /// Length of the boom arm in [`CameraMode::ThirdPerson`].
+  pub boom_length: f32,
+  /// Current length of the boom arm, shorter than [`boom_length`](CameraController::boom_length)
+  /// while blocks are in the way.
+  pub boom_distance: f32,
+  /// Rate per second at which the boom arm extends again once blocks are out of the way.
+  pub boom_extend_rate: f32,
+  /// Seconds it takes to blend between camera modes.
+  pub transition_time: f32,
+  /// Camera transform a transition started from and the seconds since.
+  pub transition: Option<(Transform, f32)>,
   /// Multiplier for unmodified translation speed.
   pub walk_speed: f32,
   /// Multiplier for running translation speed.
   pub run_speed: f32,
   /// Multiplier for how the mouse scroll wheel modifies [`walk_speed`](CameraController::walk_speed)
-  /// and [`run_speed`](CameraController::run_speed).
+  /// and [`run_speed`](CameraController::run_speed), or the zoom outside of
+  /// [`CameraMode::FirstPerson`].
   pub scroll_factor: f32,
-  /// Friction factor used to exponentially decay [`velocity`](CameraController::velocity) over time.
+  /// Rate per second at which [`velocity`](CameraController::velocity) approaches the
+  /// requested speed while there is input.
+  pub acceleration: f32,
+  /// Rate per second at which [`velocity`](CameraController::velocity) decays without input.
   pub friction: f32,
+  /// Integrates movement in steps of this many seconds instead of once per frame.
+  pub fixed_timestep: Option<f32>,
+  /// Time not yet integrated when using [`fixed_timestep`](CameraController::fixed_timestep).
+  pub accumulated_time: f32,
   /// This [`CameraController`]'s pitch rotation.
   pub pitch: f32,
   /// This [`CameraController`]'s yaw rotation.
   pub yaw: f32,
   /// This [`CameraController`]'s translation velocity.
   pub velocity: Vec3,
+  /// Half size of the collider used by [`MovementMode::Fly`] and [`MovementMode::Walk`].
+  pub collider_half_extents: Vec3,
+  /// Height of the camera above the bottom of the collider.
+  pub eye_height: f32,
+  /// Downward acceleration while walking.
+  pub gravity: f32,
+  /// Upward velocity of a jump.
+  pub jump_speed: f32,
+  /// Highest block edge that is climbed without jumping.
+  pub step_height: f32,
+  /// Whether the collider stood on solid ground after the last update.
+  pub on_ground: bool,
 }
 
 impl Default for CameraController {
@@ -51,22 +144,39 @@ impl Default for CameraController {
       enabled: true,
       initialized: false,
       sensitivity: 1.0,
-      key_forward: KeyCode::KeyW,
-      key_back: KeyCode::KeyS,
-      key_left: KeyCode::KeyA,
-      key_right: KeyCode::KeyD,
-      key_up: KeyCode::KeyE,
-      key_down: KeyCode::KeyQ,
-      key_run: KeyCode::ShiftLeft,
-      mouse_key_cursor_grab: MouseButton::Left,
-      keyboard_key_toggle_cursor_grab: KeyCode::KeyM,
+      input: InputSource::default(),
+      cursor_grab_toggled: false,
+      cursor_grab_held: false,
+      gamepad: None,
+      gamepad_deadzone: 0.15,
+      gamepad_response_exponent: 2.0,
+      gamepad_look_speed: 3.0,
+      movement_mode: MovementMode::default(),
+      camera_mode: CameraMode::default(),
+      body: Transform::default(),
+      focus: Vec3::ZERO,
+      orbit_distance: 10.0,
+      boom_length: 4.0,
+      boom_distance: 0.0,
+      boom_extend_rate: 4.0,
+      transition_time: 0.4,
+      transition: None,
       walk_speed: 5.0,
       run_speed: 15.0,
       scroll_factor: 0.1,
-      friction: 0.5,
+      acceleration: 12.0,
+      friction: 10.0,
+      fixed_timestep: None,
+      accumulated_time: 0.0,
       pitch: 0.0,
       yaw: 0.0,
       velocity: Vec3::ZERO,
+      collider_half_extents: Vec3::new(0.3, 0.9, 0.3),
+      eye_height: 1.6,
+      gravity: 25.0,
+      jump_speed: 8.0,
+      step_height: 1.0,
+      on_ground: false,
     }
   }
 }
@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+use crate::camera::controller::{CameraController, InputSource};
+
+/// Maps a stick position through a radial deadzone and a power response curve.
+///
+/// Positions inside the deadzone are zero, the rest is rescaled to start at zero
+/// so there is no jump at the edge of the deadzone.
+pub fn stick_response(stick: Vec2, deadzone: f32, exponent: f32) -> Vec2 {
+  let length = stick.length();
+  if length <= deadzone {
+    return Vec2::ZERO;
+  }
+  stick / length * trigger_response(length, deadzone, exponent)
+}
+
+/// Maps a value in `0.0..=1.0` through a deadzone and a power response curve.
+pub fn trigger_response(value: f32, deadzone: f32, exponent: f32) -> f32 {
+  if value <= deadzone {
+    return 0.0;
+  }
+  ((value - deadzone) / (1.0 - deadzone).max(f32::EPSILON))
+    .min(1.0)
+    .powf(exponent)
+}
+
+/// Picks the gamepad a controller listens to, depending on its [`InputSource`].
+///
+/// With [`InputSource::Any`] the current gamepad is kept while it is connected, but
+/// any other gamepad takes over as soon as one of its buttons is pressed. Without a
+/// current gamepad the first connected one is used.
+pub fn select_gamepad<'a>(
+  controller: &mut CameraController,
+  gamepads: impl Iterator<Item = (Entity, &'a Gamepad)> + Clone,
+) -> Option<&'a Gamepad> {
+  match controller.input {
+    InputSource::Any => {}
+    InputSource::KeyboardMouse => {
+      controller.gamepad = None;
+      return None;
+    }
+    InputSource::Gamepad(index) => {
+      let mut gamepads: Vec<_> = gamepads.collect();
+      gamepads.sort_unstable_by_key(|(entity, _)| *entity);
+      let selected = gamepads.get(index).copied();
+      controller.gamepad = selected.map(|(entity, _)| entity);
+      return selected.map(|(_, gamepad)| gamepad);
+    }
+  }
+
+  let active = gamepads
+    .clone()
+    .find(|(_, gamepad)| gamepad.get_just_pressed().next().is_some());
+  let current = controller
+    .gamepad
+    .and_then(|entity| gamepads.clone().find(|(candidate, _)| *candidate == entity));
+
+  let (entity, gamepad) = active
+    .or(current)
+    .or_else(|| gamepads.min_by_key(|(entity, _)| *entity))?;
+  controller.gamepad = Some(entity);
+  Some(gamepad)
+}
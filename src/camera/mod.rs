@@ -1,18 +1,40 @@
-use bevy::prelude::*;
+use bevy::{core_pipeline::prepass::DepthPrepass, prelude::*};
 
-use crate::camera::controller::CameraController;
+pub use controller::InputSource;
+pub use path::{CameraPath, CameraPathPlugin, CameraPlayback, CameraSample};
+pub use split_screen::SplitScreenPlugin;
 
+use crate::{
+  camera::{bindings::BindingsPlugin, controller::CameraController},
+  voxel::ChunkViewer,
+};
+
+mod bindings;
+mod collision;
 mod controller;
+mod gamepad;
+mod mode;
+mod movement;
+mod path;
+mod split_screen;
 mod system;
 
-pub fn camera_components() -> impl Bundle {
-  (CameraController::default(), Camera3d::default())
+pub fn camera_components(input: InputSource) -> impl Bundle {
+  // the depth prepass is read by the liquid shader
+  (
+    CameraController { input, ..default() },
+    Camera3d::default(),
+    DepthPrepass,
+    ChunkViewer,
+  )
 }
 
 pub struct CameraControllerPlugin;
 
 impl Plugin for CameraControllerPlugin {
   fn build(&self, app: &mut App) {
-    app.add_systems(Update, system::run_camera_controller);
+    app
+      .add_plugins(BindingsPlugin)
+      .add_systems(Update, system::run_camera_controller);
   }
 }
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use crate::camera::{
+  collision,
+  controller::{CameraController, CameraMode},
+};
+
+/// Half size of the box kept clear of blocks around the camera in [`CameraMode::ThirdPerson`].
+const CAMERA_HALF_EXTENTS: Vec3 = Vec3::splat(0.2);
+/// Closest the camera zooms in to the orbit focus or the body.
+const MIN_ZOOM_DISTANCE: f32 = 1.0;
+/// Focus movement per mouse dot and unit of orbit distance.
+const PAN_PER_DOT: f32 = 1.0 / 600.0;
+
+impl CameraController {
+  /// Rotation from [`yaw`](CameraController::yaw) and [`pitch`](CameraController::pitch).
+  pub fn look_rotation(&self) -> Quat {
+    Quat::from_euler(EulerRot::ZYX, 0.0, self.yaw, self.pitch)
+  }
+
+  /// Switches to `mode`, blending over from the camera's current `transform`.
+  pub fn set_camera_mode(&mut self, mode: CameraMode, transform: &Transform) {
+    if mode == CameraMode::Orbit {
+      // orbit around whatever is in front of the camera so it doesn't jump
+      self.focus = transform.translation + self.look_rotation() * Vec3::NEG_Z * self.orbit_distance;
+    }
+    self.camera_mode = mode;
+    self.transition = Some((*transform, 0.0));
+  }
+
+  /// Zooms outside of [`CameraMode::FirstPerson`], changes the movement speed otherwise.
+  pub fn scroll(&mut self, scroll: f32) {
+    let factor = 1.0 - scroll * self.scroll_factor;
+    match self.camera_mode {
+      CameraMode::FirstPerson => {
+        self.walk_speed += scroll * self.scroll_factor * self.walk_speed;
+        self.run_speed = self.walk_speed * 3.0;
+      }
+      CameraMode::ThirdPerson => {
+        self.boom_length = (self.boom_length * factor).max(MIN_ZOOM_DISTANCE);
+      }
+      CameraMode::Orbit => {
+        self.orbit_distance = (self.orbit_distance * factor).max(MIN_ZOOM_DISTANCE);
+      }
+    }
+  }
+
+  /// Moves the orbit focus along the camera plane by a mouse movement in dots.
+  pub fn pan(&mut self, delta: Vec2) {
+    let rotation = self.look_rotation();
+    let offset = rotation * Vec3::new(-delta.x, delta.y, 0.0);
+    self.focus += offset * PAN_PER_DOT * self.orbit_distance;
+  }
+
+  /// Camera transform for the current [`camera_mode`](CameraController::camera_mode),
+  /// blended with the transform before the last mode switch while transitioning.
+  pub fn camera_transform(&mut self, dt: f32, is_solid: &impl Fn(IVec3) -> bool) -> Transform {
+    let rotation = self.look_rotation();
+    let back = rotation * Vec3::Z;
+
+    let translation = match self.camera_mode {
+      CameraMode::FirstPerson => self.body.translation,
+      CameraMode::ThirdPerson => {
+        let pivot = self.body.translation;
+        let free =
+          collision::cast_box(pivot, CAMERA_HALF_EXTENTS, back, self.boom_length, is_solid);
+        // pull in right away so the camera never clips, ease back out afterwards
+        if free < self.boom_distance {
+          self.boom_distance = free;
+        } else {
+          self
+            .boom_distance
+            .smooth_nudge(&free, self.boom_extend_rate, dt);
+        }
+        pivot + back * self.boom_distance
+      }
+      CameraMode::Orbit => self.focus + back * self.orbit_distance,
+    };
+    let target = Transform::from_translation(translation).with_rotation(rotation);
+
+    let Some((start, elapsed)) = &mut self.transition else {
+      return target;
+    };
+    *elapsed += dt;
+    let t = (*elapsed / self.transition_time.max(f32::EPSILON)).min(1.0);
+    let blend = EaseFunction::SmoothStep.sample_clamped(t);
+    let transform = Transform::from_translation(start.translation.lerp(target.translation, blend))
+      .with_rotation(start.rotation.slerp(target.rotation, blend));
+    if t >= 1.0 {
+      self.transition = None;
+    }
+    transform
+  }
+}
@@ -0,0 +1,407 @@
+use bevy::prelude::*;
+
+use crate::camera::{
+  collision,
+  controller::{CameraController, MovementMode},
+};
+
+/// Speed multiplier while crouching in [`MovementMode::Walk`].
+const CROUCH_SPEED_FACTOR: f32 = 0.3;
+/// Most fixed steps run in a single update, the remaining time is dropped.
+const MAX_FIXED_STEPS: u32 = 8;
+
+/// Movement requested for one update of a [`CameraController`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MovementInput {
+  /// Requested direction in camera space, `x` right, `y` up and `z` forward.
+  /// Components are in `-1.0..=1.0`.
+  pub axis: Vec3,
+  pub run: bool,
+  /// Jump in [`MovementMode::Walk`], ignored otherwise.
+  pub jump: bool,
+  /// Crouch in [`MovementMode::Walk`], ignored otherwise.
+  pub crouch: bool,
+}
+
+/// Moves `velocity` towards `target` with exponential damping at `rate` per second.
+///
+/// Returns the new velocity and the distance travelled during `dt`. Both are the
+/// exact solution, so splitting `dt` into smaller steps gives the same result.
+pub fn damp(velocity: Vec3, target: Vec3, rate: f32, dt: f32) -> (Vec3, Vec3) {
+  if rate <= 0.0 {
+    return (velocity, velocity * dt);
+  }
+  let decay = (-rate * dt).exp();
+  let velocity_after = target + (velocity - target) * decay;
+  let distance = target * dt + (velocity - target) * (1.0 - decay) / rate;
+  (velocity_after, distance)
+}
+
+impl CameraController {
+  /// Advances the movement by `dt` seconds, in steps of
+  /// [`fixed_timestep`](CameraController::fixed_timestep) if set.
+  pub fn advance(
+    &mut self,
+    transform: &mut Transform,
+    input: &MovementInput,
+    dt: f32,
+    is_solid: &impl Fn(IVec3) -> bool,
+  ) {
+    let Some(step) = self.fixed_timestep.filter(|step| *step > 0.0) else {
+      self.integrate(transform, input, dt, is_solid);
+      return;
+    };
+
+    self.accumulated_time += dt;
+    let mut steps = 0;
+    while self.accumulated_time >= step {
+      self.accumulated_time -= step;
+      steps += 1;
+      if steps > MAX_FIXED_STEPS {
+        self.accumulated_time = 0.0;
+        break;
+      }
+      self.integrate(transform, input, step, is_solid);
+    }
+  }
+
+  fn integrate(
+    &mut self,
+    transform: &mut Transform,
+    input: &MovementInput,
+    dt: f32,
+    is_solid: &impl Fn(IVec3) -> bool,
+  ) {
+    let walking = self.movement_mode == MovementMode::Walk;
+    let crouching = walking && input.crouch;
+
+    let mut axis = input.axis;
+    if walking {
+      // up and down jump and crouch instead
+      axis.y = 0.0;
+    }
+
+    let mut max_speed = if input.run {
+      self.run_speed
+    } else {
+      self.walk_speed
+    };
+    if crouching {
+      max_speed *= CROUCH_SPEED_FACTOR;
+    }
+    let (target, rate) = if axis != Vec3::ZERO {
+      (axis.clamp_length_max(1.0) * max_speed, self.acceleration)
+    } else {
+      (Vec3::ZERO, self.friction)
+    };
+
+    let (mut velocity, mut distance) = damp(self.velocity, target, rate, dt);
+    if axis == Vec3::ZERO && velocity.length_squared() < 1e-6 {
+      velocity = Vec3::ZERO;
+    }
+
+    if walking {
+      let vertical = if self.on_ground && input.jump {
+        self.jump_speed
+      } else {
+        self.velocity.y
+      };
+      velocity.y = vertical - self.gravity * dt;
+      distance.y = vertical * dt - 0.5 * self.gravity * dt * dt;
+    }
+    self.velocity = velocity;
+
+    if distance == Vec3::ZERO {
+      return;
+    }
+
+    let (forward, right) = if walking {
+      (
+        transform.forward().with_y(0.0).normalize_or_zero(),
+        transform.right().with_y(0.0).normalize_or_zero(),
+      )
+    } else {
+      (*transform.forward(), *transform.right())
+    };
+    let delta = distance.x * right + distance.y * Vec3::Y + distance.z * forward;
+
+    let half_extents = self.collider_half_extents;
+    // the collider hangs below the camera
+    let offset = Vec3::Y * (self.eye_height - half_extents.y);
+    let center = transform.translation - offset;
+
+    match self.movement_mode {
+      MovementMode::Noclip => transform.translation += delta,
+      MovementMode::Fly => {
+        let (center, _) = collision::sweep(center, half_extents, delta, is_solid);
+        transform.translation = center + offset;
+      }
+      MovementMode::Walk => {
+        let (center, blocked) = collision::walk(
+          center,
+          half_extents,
+          delta,
+          self.step_height,
+          crouching,
+          is_solid,
+        );
+        transform.translation = center + offset;
+        self.on_ground = blocked.y && delta.y <= 0.0;
+        if blocked.y {
+          self.velocity.y = 0.0;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::voxel::{ExportMesh, Generator, VoxelWorld};
+
+  const DT: f32 = 1.0 / 60.0;
+
+  /// Camera height above the ground while standing with the default collider.
+  const STANDING_HEIGHT: f32 = 1.6;
+
+  fn walker(position: Vec3) -> (CameraController, Transform) {
+    let controller = CameraController {
+      movement_mode: MovementMode::Walk,
+      ..default()
+    };
+    // looking along -Z
+    (controller, Transform::from_translation(position))
+  }
+
+  fn forward(crouch: bool) -> MovementInput {
+    MovementInput {
+      axis: Vec3::Z,
+      crouch,
+      ..default()
+    }
+  }
+
+  fn simulate(
+    controller: &mut CameraController,
+    transform: &mut Transform,
+    input: &MovementInput,
+    seconds: f32,
+    dt: f32,
+    is_solid: &impl Fn(IVec3) -> bool,
+  ) {
+    for _ in 0..(seconds / dt).round() as u32 {
+      controller.advance(transform, input, dt, is_solid);
+    }
+  }
+
+  #[test]
+  fn walking_into_a_wall_stops_at_its_face() {
+    // ground and a wall three blocks high with its face at z = -2
+    let is_solid = |pos: IVec3| pos.y < 0 || (pos.z <= -3 && pos.y < 3);
+    let (mut controller, mut transform) = walker(Vec3::new(0.5, STANDING_HEIGHT, 0.5));
+
+    simulate(
+      &mut controller,
+      &mut transform,
+      &forward(false),
+      2.0,
+      DT,
+      &is_solid,
+    );
+
+    let half_depth = controller.collider_half_extents.z;
+    assert!((transform.translation.z - (-2.0 + half_depth)).abs() < 0.01);
+    assert!((transform.translation.y - STANDING_HEIGHT).abs() < 0.01);
+    assert!(controller.on_ground);
+  }
+
+  #[test]
+  fn falling_lands_on_the_ground() {
+    let is_solid = |pos: IVec3| pos.y < 0;
+    let (mut controller, mut transform) = walker(Vec3::new(0.5, 10.0, 0.5));
+
+    simulate(
+      &mut controller,
+      &mut transform,
+      &MovementInput::default(),
+      2.0,
+      DT,
+      &is_solid,
+    );
+
+    assert!((transform.translation.y - STANDING_HEIGHT).abs() < 0.01);
+    assert!(controller.on_ground);
+    assert_eq!(controller.velocity.y, 0.0);
+  }
+
+  #[test]
+  fn walking_steps_up_a_ledge() {
+    // a ledge one block high starting at z = -2
+    let is_solid = |pos: IVec3| pos.y < 0 || (pos.z <= -3 && pos.y < 1);
+    let (mut controller, mut transform) = walker(Vec3::new(0.5, STANDING_HEIGHT, 0.5));
+
+    simulate(
+      &mut controller,
+      &mut transform,
+      &forward(false),
+      2.0,
+      DT,
+      &is_solid,
+    );
+
+    assert!(transform.translation.z < -4.0);
+    assert!((transform.translation.y - (1.0 + STANDING_HEIGHT)).abs() < 0.01);
+    assert!(controller.on_ground);
+  }
+
+  #[test]
+  fn crouching_stops_at_edges() {
+    // a platform ending at z = -2 above nothing
+    let is_solid = |pos: IVec3| pos.y < 0 && pos.y > -2 && pos.z > -3;
+    let (mut controller, mut transform) = walker(Vec3::new(0.5, STANDING_HEIGHT, 0.5));
+
+    simulate(
+      &mut controller,
+      &mut transform,
+      &forward(true),
+      3.0,
+      DT,
+      &is_solid,
+    );
+
+    // still partly over the platform
+    let half_depth = controller.collider_half_extents.z;
+    assert!(transform.translation.z > -2.0 - half_depth);
+    assert!((transform.translation.y - STANDING_HEIGHT).abs() < 0.01);
+    assert!(controller.on_ground);
+
+    // without crouching the same walk goes over the edge
+    let (mut controller, mut transform) = walker(Vec3::new(0.5, STANDING_HEIGHT, 0.5));
+    simulate(
+      &mut controller,
+      &mut transform,
+      &forward(false),
+      3.0,
+      DT,
+      &is_solid,
+    );
+    assert!(transform.translation.y < 0.0);
+  }
+
+  /// Final position and velocity after running `inputs`, each for its number of
+  /// seconds, at `fps` updates per second.
+  fn run_at(
+    fps: f32,
+    mut controller: CameraController,
+    inputs: &[(MovementInput, f32)],
+    is_solid: &impl Fn(IVec3) -> bool,
+  ) -> (Vec3, Vec3) {
+    let mut transform = Transform::from_translation(Vec3::new(0.5, STANDING_HEIGHT, 0.5));
+    for (input, seconds) in inputs {
+      simulate(
+        &mut controller,
+        &mut transform,
+        input,
+        *seconds,
+        1.0 / fps,
+        is_solid,
+      );
+    }
+    (transform.translation, controller.velocity)
+  }
+
+  #[test]
+  fn movement_does_not_depend_on_frame_rate() {
+    let is_solid = |pos: IVec3| pos.y < 0;
+    // accelerate, run, then slow down without input
+    let inputs = [
+      (forward(false), 0.5),
+      (
+        MovementInput {
+          axis: Vec3::new(1.0, 0.5, 1.0),
+          run: true,
+          ..default()
+        },
+        1.0,
+      ),
+      (MovementInput::default(), 0.5),
+    ];
+
+    for movement_mode in [MovementMode::Noclip, MovementMode::Walk] {
+      let results: Vec<_> = [30.0, 60.0, 144.0]
+        .into_iter()
+        .map(|fps| {
+          let controller = CameraController {
+            movement_mode,
+            ..default()
+          };
+          run_at(fps, controller, &inputs, &is_solid)
+        })
+        .collect();
+
+      let (position, velocity) = results[0];
+      assert!(position.distance(Vec3::new(0.5, STANDING_HEIGHT, 0.5)) > 1.0);
+      for (other_position, other_velocity) in &results[1..] {
+        assert!(
+          position.distance(*other_position) < 1e-3,
+          "{movement_mode:?}: {position} != {other_position}"
+        );
+        assert!(
+          velocity.distance(*other_velocity) < 1e-3,
+          "{movement_mode:?}: {velocity} != {other_velocity}"
+        );
+      }
+    }
+  }
+
+  /// Height of the highest rendered vertex on the corners of the column of
+  /// blocks at `x`, `z`.
+  fn rendered_top(export: &ExportMesh, x: i32, z: i32) -> f32 {
+    export
+      .primitives
+      .iter()
+      .flat_map(|primitive| &primitive.positions)
+      .filter(|pos| {
+        (pos.x == x as f32 || pos.x == x as f32 + 1.0)
+          && (pos.z == z as f32 || pos.z == z as f32 + 1.0)
+      })
+      .map(|pos| pos.y)
+      .fold(f32::MIN, f32::max)
+  }
+
+  #[test]
+  fn walking_settles_on_the_rendered_top_face() {
+    let chunk_pos = IVec3::new(0, 0, -1);
+    let mut world = VoxelWorld::default();
+    world.insert(Generator::Flat { height: 3 }.generate(0, chunk_pos));
+    // a pillar two blocks above the ground
+    let grass = world.get_block(IVec3::new(4, 3, -8)).unwrap();
+    world.set_block(IVec3::new(4, 4, -8), grass);
+    world.set_block(IVec3::new(4, 5, -8), grass);
+
+    let mesh = world.chunk(chunk_pos).unwrap().create_mesh();
+    let export = ExportMesh::from_chunks([&mesh]);
+    let is_solid = |pos: IVec3| world.is_solid(pos);
+
+    for (x, z, top) in [(10, -4, 4.0), (4, -8, 6.0)] {
+      assert_eq!(rendered_top(&export, x, z), top);
+
+      let (mut controller, mut transform) = walker(Vec3::new(x as f32 + 0.5, 10.0, z as f32 + 0.5));
+      simulate(
+        &mut controller,
+        &mut transform,
+        &MovementInput::default(),
+        2.0,
+        DT,
+        &is_solid,
+      );
+      assert!(
+        (transform.translation.y - (top + STANDING_HEIGHT)).abs() < 0.01,
+        "standing at {} over {x}, {z}",
+        transform.translation.y
+      );
+      assert!(controller.on_ground);
+    }
+  }
+}
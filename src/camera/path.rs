@@ -0,0 +1,281 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use bevy::{math::VectorSpace, prelude::*};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::camera::{
+  bindings::{Action, Bindings, InputState},
+  controller::CameraController,
+};
+
+/// File recordings are written to and played back from by default.
+pub const DEFAULT_CAMERA_PATH: &str = "camera_path.ron";
+
+/// Minimum time between two recorded samples.
+const SAMPLE_INTERVAL: f32 = 1.0 / 20.0;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct CameraSample {
+  /// Seconds since the start of the recording.
+  pub time: f32,
+  pub translation: Vec3,
+  pub rotation: Quat,
+}
+
+/// Recorded camera motion, samples are sorted by time.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CameraPath {
+  pub samples: Vec<CameraSample>,
+}
+
+impl CameraPath {
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, CameraPathError> {
+    Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+  }
+
+  pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CameraPathError> {
+    let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+    fs::write(path, ron)?;
+    Ok(())
+  }
+
+  pub fn duration(&self) -> f32 {
+    self.samples.last().map_or(0.0, |sample| sample.time)
+  }
+
+  /// Camera transform at `time`, interpolated with a Catmull-Rom spline through the samples.
+  pub fn sample(&self, time: f32) -> Option<Transform> {
+    let samples = &self.samples;
+    let last = samples.len().checked_sub(1)?;
+
+    let next = samples.partition_point(|sample| sample.time <= time);
+    if next == 0 || next > last {
+      let sample = samples[next.min(last)];
+      return Some(Transform::from_translation(sample.translation).with_rotation(sample.rotation));
+    }
+
+    let i1 = next - 1;
+    let [s0, s1, s2, s3] =
+      [i1.saturating_sub(1), i1, next, (next + 1).min(last)].map(|i| samples[i]);
+    let t = ((time - s1.time) / (s2.time - s1.time).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+    let translation = catmull_rom(
+      [
+        s0.translation,
+        s1.translation,
+        s2.translation,
+        s3.translation,
+      ],
+      t,
+    );
+
+    // keep neighbouring quaternions in the same hemisphere before blending them
+    let mut rotations = [s0.rotation, s1.rotation, s2.rotation, s3.rotation].map(Vec4::from);
+    for i in 1..4 {
+      if rotations[i].dot(rotations[i - 1]) < 0.0 {
+        rotations[i] = -rotations[i];
+      }
+    }
+    let rotation = Quat::from_vec4(catmull_rom(rotations, t)).normalize();
+
+    Some(Transform::from_translation(translation).with_rotation(rotation))
+  }
+}
+
+/// Uniform Catmull-Rom spline between `p[1]` and `p[2]`.
+fn catmull_rom<P: VectorSpace<Scalar = f32>>(p: [P; 4], t: f32) -> P {
+  let t2 = t * t;
+  let t3 = t2 * t;
+  (p[1] * 2.0
+    + (p[2] - p[0]) * t
+    + (p[0] * 2.0 - p[1] * 5.0 + p[2] * 4.0 - p[3]) * t2
+    + (p[1] * 3.0 - p[0] - p[2] * 3.0 + p[3]) * t3)
+    * 0.5
+}
+
+#[derive(Debug, Error)]
+pub enum CameraPathError {
+  #[error("could not read or write camera path: {0}")]
+  Io(#[from] io::Error),
+  #[error("could not parse camera path: {0}")]
+  Parse(#[from] ron::de::SpannedError),
+  #[error("could not serialize camera path: {0}")]
+  Serialize(#[from] ron::Error),
+}
+
+/// Records the controlled camera while active.
+#[derive(Resource, Default)]
+pub struct CameraRecorder {
+  recording: Option<CameraPath>,
+  /// Real time since the recording started, like the [`CameraController`] moves
+  /// in, so pausing or scaling virtual time doesn't change the recording.
+  elapsed: f32,
+}
+
+/// Drives the controlled camera along a [`CameraPath`] while active, with its
+/// [`CameraController`] disabled.
+#[derive(Resource, Default)]
+pub struct CameraPlayback {
+  playing: Option<CameraPath>,
+  /// Real time since the playback started, matching the recording.
+  elapsed: f32,
+  /// Exits the app when the current playback finishes.
+  pub exit_when_done: bool,
+}
+
+impl CameraPlayback {
+  pub fn play(&mut self, path: CameraPath) {
+    self.playing = Some(path);
+    self.elapsed = 0.0;
+  }
+
+  pub fn is_playing(&self) -> bool {
+    self.playing.is_some()
+  }
+}
+
+/// Records and plays back camera paths of the first controlled camera by
+/// [`Camera::order`], toggled with [`Action::ToggleRecording`]
+/// and [`Action::TogglePlayback`].
+#[derive(Default)]
+pub struct CameraPathPlugin {
+  /// Path played back right after startup, the app exits once it finishes.
+  pub playback: Option<PathBuf>,
+}
+
+impl Plugin for CameraPathPlugin {
+  fn build(&self, app: &mut App) {
+    let mut playback = CameraPlayback::default();
+    if let Some(file) = &self.playback {
+      match CameraPath::load(file) {
+        Ok(path) => {
+          playback.play(path);
+          playback.exit_when_done = true;
+        }
+        Err(error) => error!("Could not play back {}: {error}", file.display()),
+      }
+    }
+
+    app
+      .init_resource::<CameraRecorder>()
+      .insert_resource(playback)
+      .add_systems(
+        Update,
+        (toggle_camera_path, record_camera_path, play_camera_path).chain(),
+      );
+  }
+}
+
+fn toggle_camera_path(
+  key_input: Res<ButtonInput<KeyCode>>,
+  mouse_button_input: Res<ButtonInput<MouseButton>>,
+  gamepads: Query<&Gamepad>,
+  bindings: Res<Bindings>,
+  controllers: Query<(&Camera, &CameraController)>,
+  mut recorder: ResMut<CameraRecorder>,
+  mut playback: ResMut<CameraPlayback>,
+) {
+  let input = InputState {
+    keys: &key_input,
+    mouse: &mouse_button_input,
+    gamepad: controllers
+      .iter()
+      .min_by_key(|(camera, _)| camera.order)
+      .and_then(|(_, controller)| controller.gamepad)
+      .and_then(|entity| gamepads.get(entity).ok()),
+  };
+
+  if bindings.just_pressed(Action::ToggleRecording, &input) {
+    match recorder.recording.take() {
+      Some(path) => match path.save(DEFAULT_CAMERA_PATH) {
+        Ok(()) => info!(
+          "Saved {} camera samples to {DEFAULT_CAMERA_PATH}",
+          path.samples.len()
+        ),
+        Err(error) => error!("Could not save camera path: {error}"),
+      },
+      None => {
+        info!("Recording camera path");
+        recorder.recording = Some(CameraPath::default());
+        recorder.elapsed = 0.0;
+      }
+    }
+  }
+
+  if bindings.just_pressed(Action::TogglePlayback, &input) {
+    if playback.is_playing() {
+      playback.elapsed = f32::INFINITY;
+    } else {
+      match CameraPath::load(DEFAULT_CAMERA_PATH) {
+        Ok(path) => playback.play(path),
+        Err(error) => error!("Could not play back {DEFAULT_CAMERA_PATH}: {error}"),
+      }
+    }
+  }
+}
+
+fn record_camera_path(
+  time: Res<Time<Real>>,
+  mut recorder: ResMut<CameraRecorder>,
+  cameras: Query<(&Camera, &Transform), With<CameraController>>,
+) {
+  let recorder = &mut *recorder;
+  let Some(path) = &mut recorder.recording else {
+    return;
+  };
+  let Some((_, transform)) = cameras.iter().min_by_key(|(camera, _)| camera.order) else {
+    return;
+  };
+
+  if path
+    .samples
+    .last()
+    .is_none_or(|last| recorder.elapsed - last.time >= SAMPLE_INTERVAL)
+  {
+    path.samples.push(CameraSample {
+      time: recorder.elapsed,
+      translation: transform.translation,
+      rotation: transform.rotation,
+    });
+  }
+  recorder.elapsed += time.delta_secs();
+}
+
+fn play_camera_path(
+  time: Res<Time<Real>>,
+  mut playback: ResMut<CameraPlayback>,
+  mut cameras: Query<(&Camera, &mut Transform, &mut CameraController)>,
+  mut exit: MessageWriter<AppExit>,
+) {
+  let playback = &mut *playback;
+  let Some(path) = &playback.playing else {
+    return;
+  };
+  let Some((_, mut transform, mut controller)) =
+    cameras.iter_mut().min_by_key(|(camera, ..)| camera.order)
+  else {
+    return;
+  };
+
+  if playback.elapsed > path.duration() {
+    info!("Camera path playback finished");
+    playback.playing = None;
+    // pick up the pose of the last sample
+    controller.enabled = true;
+    controller.initialized = false;
+    if playback.exit_when_done {
+      exit.write(AppExit::Success);
+    }
+    return;
+  }
+
+  controller.enabled = false;
+  if let Some(sample) = path.sample(playback.elapsed) {
+    *transform = sample;
+  }
+  playback.elapsed += time.delta_secs();
+}
@@ -0,0 +1,71 @@
+use bevy::{
+  camera::{NormalizedRenderTarget, Viewport},
+  ecs::entity::ContainsEntity,
+  platform::collections::HashMap,
+  prelude::*,
+  window::PrimaryWindow,
+};
+
+use crate::camera::controller::CameraController;
+
+/// Splits each window into a grid of viewports, one per camera with a
+/// [`CameraController`] rendering to it, ordered by [`Camera::order`].
+pub struct SplitScreenPlugin;
+
+impl Plugin for SplitScreenPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_systems(PostUpdate, update_split_screen_viewports);
+  }
+}
+
+fn update_split_screen_viewports(
+  windows: Query<&Window>,
+  primary_window: Query<Entity, With<PrimaryWindow>>,
+  mut cameras: Query<(Entity, &mut Camera), With<CameraController>>,
+) {
+  let primary_window = primary_window.single().ok();
+  let mut by_window: HashMap<Entity, Vec<(isize, Entity)>> = HashMap::default();
+  for (entity, camera) in &cameras {
+    if let Some(NormalizedRenderTarget::Window(window)) = camera.target.normalize(primary_window) {
+      by_window
+        .entry(window.entity())
+        .or_default()
+        .push((camera.order, entity));
+    }
+  }
+
+  for (window, mut views) in by_window {
+    let Ok(window) = windows.get(window) else {
+      continue;
+    };
+    views.sort_unstable();
+
+    let columns = (views.len() as f32).sqrt().ceil() as u32;
+    let rows = (views.len() as u32).div_ceil(columns);
+    let size = window.physical_size() / UVec2::new(columns, rows);
+
+    for (i, (_, entity)) in views.iter().enumerate() {
+      let Ok((_, mut camera)) = cameras.get_mut(*entity) else {
+        continue;
+      };
+      let cell = UVec2::new(i as u32 % columns, i as u32 / columns);
+      let viewport = (views.len() > 1).then(|| Viewport {
+        physical_position: cell * size,
+        physical_size: size.max(UVec2::ONE),
+        ..default()
+      });
+
+      // only touch the camera when the layout changed
+      let unchanged = match (&camera.viewport, &viewport) {
+        (None, None) => true,
+        (Some(old), Some(new)) => {
+          old.physical_position == new.physical_position && old.physical_size == new.physical_size
+        }
+        _ => false,
+      };
+      if !unchanged {
+        camera.viewport = viewport;
+      }
+    }
+  }
+}
@@ -1,12 +1,22 @@
 use std::f32::consts::PI;
 
 use bevy::{
+  camera::NormalizedRenderTarget,
+  ecs::entity::ContainsEntity,
   input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit},
   prelude::*,
-  window::{CursorGrabMode, CursorOptions},
+  window::{CursorGrabMode, CursorOptions, PrimaryWindow},
 };
 
-use crate::camera::controller::CameraController;
+use crate::{
+  camera::{
+    bindings::{Action, Bindings, InputState},
+    controller::{CameraController, CameraMode, InputSource},
+    gamepad::{select_gamepad, stick_response, trigger_response},
+    movement::MovementInput,
+  },
+  voxel::VoxelWorld,
+};
 
 pub const RADIANS_PER_DOT: f32 = 1.0 / 180.0;
 
@@ -18,124 +28,152 @@ pub fn run_camera_controller(
   accumulated_mouse_scroll: Res<AccumulatedMouseScroll>,
   mouse_button_input: Res<ButtonInput<MouseButton>>,
   key_input: Res<ButtonInput<KeyCode>>,
-  mut toggle_cursor_grab: Local<bool>,
-  mut mouse_cursor_grab: Local<bool>,
-  mut query: Query<(&mut Transform, &mut CameraController), With<Camera>>,
+  primary_window: Query<Entity, With<PrimaryWindow>>,
+  mut query: Query<(&Camera, &mut Transform, &mut CameraController)>,
+  gamepads: Query<(Entity, &Gamepad)>,
+  bindings: Res<Bindings>,
+  world: Option<Res<VoxelWorld>>,
 ) {
   let dt = time.delta_secs();
+  let no_keys = ButtonInput::default();
+  let no_mouse = ButtonInput::default();
+  let is_solid = |pos: IVec3| world.as_ref().is_some_and(|world| world.is_solid(pos));
 
-  let Ok((mut transform, mut controller)) = query.single_mut() else {
-    info!("No camera with CameraController found, skipping CameraController system.");
-    return;
-  };
-
-  if !controller.initialized {
-    let (yaw, pitch, _roll) = transform.rotation.to_euler(EulerRot::YXZ);
-    controller.yaw = yaw;
-    controller.pitch = pitch;
-    controller.initialized = true;
-  }
-  if !controller.enabled {
-    return;
-  }
+  for (camera, mut transform, mut controller) in &mut query {
+    if !controller.initialized {
+      let (yaw, pitch, _roll) = transform.rotation.to_euler(EulerRot::YXZ);
+      controller.yaw = yaw;
+      controller.pitch = pitch;
+      controller.body = *transform;
+      controller.focus = transform.translation + transform.forward() * controller.orbit_distance;
+      controller.transition = Some((*transform, 0.0));
+      controller.initialized = true;
+    }
+    if !controller.enabled {
+      continue;
+    }
 
-  let mut scroll = 0.0;
+    // gamepad-only controllers ignore the keyboard and mouse
+    let uses_mouse = !matches!(controller.input, InputSource::Gamepad(_));
+    let (keys, mouse, mouse_motion, scroll) = if uses_mouse {
+      let scroll = match accumulated_mouse_scroll.unit {
+        MouseScrollUnit::Line => accumulated_mouse_scroll.delta.y,
+        MouseScrollUnit::Pixel => accumulated_mouse_scroll.delta.y / 16.0,
+      };
+      (
+        &*key_input,
+        &*mouse_button_input,
+        accumulated_mouse_motion.delta,
+        scroll,
+      )
+    } else {
+      (&no_keys, &no_mouse, Vec2::ZERO, 0.0)
+    };
+    if scroll != 0.0 {
+      controller.scroll(scroll);
+    }
 
-  let amount = match accumulated_mouse_scroll.unit {
-    MouseScrollUnit::Line => accumulated_mouse_scroll.delta.y,
-    MouseScrollUnit::Pixel => accumulated_mouse_scroll.delta.y / 16.0,
-  };
-  scroll += amount;
-  controller.walk_speed += scroll * controller.scroll_factor * controller.walk_speed;
-  controller.run_speed = controller.walk_speed * 3.0;
+    let gamepad = select_gamepad(&mut controller, gamepads.iter());
+    let input_state = InputState {
+      keys,
+      mouse,
+      gamepad,
+    };
+    let deadzone = controller.gamepad_deadzone;
+    let exponent = controller.gamepad_response_exponent;
+    let value = |action| trigger_response(bindings.value(action, &input_state), deadzone, exponent);
 
-  // Handle key input
-  let mut axis_input = Vec3::ZERO;
-  if key_input.pressed(controller.key_forward) {
-    axis_input.z += 1.0;
-  }
-  if key_input.pressed(controller.key_back) {
-    axis_input.z -= 1.0;
-  }
-  if key_input.pressed(controller.key_right) {
-    axis_input.x += 1.0;
-  }
-  if key_input.pressed(controller.key_left) {
-    axis_input.x -= 1.0;
-  }
-  if key_input.pressed(controller.key_up) {
-    axis_input.y += 1.0;
-  }
-  if key_input.pressed(controller.key_down) {
-    axis_input.y -= 1.0;
-  }
+    // Handle bound input
+    let mut axis_input = Vec3::new(
+      value(Action::MoveRight) - value(Action::MoveLeft),
+      value(Action::MoveUp) - value(Action::MoveDown),
+      value(Action::MoveForward) - value(Action::MoveBack),
+    );
 
-  let mut cursor_grab_change = false;
-  if key_input.just_pressed(controller.keyboard_key_toggle_cursor_grab) {
-    *toggle_cursor_grab = !*toggle_cursor_grab;
-    cursor_grab_change = true;
-  }
-  if mouse_button_input.just_pressed(controller.mouse_key_cursor_grab) {
-    *mouse_cursor_grab = true;
-    cursor_grab_change = true;
-  }
-  if mouse_button_input.just_released(controller.mouse_key_cursor_grab) {
-    *mouse_cursor_grab = false;
-    cursor_grab_change = true;
-  }
-  let cursor_grab = *mouse_cursor_grab || *toggle_cursor_grab;
+    // Handle gamepad sticks
+    let mut gamepad_look = Vec2::ZERO;
+    if let Some(gamepad) = gamepad {
+      let movement = stick_response(gamepad.left_stick(), deadzone, exponent);
+      axis_input += Vec3::new(movement.x, 0.0, movement.y);
+      gamepad_look = stick_response(gamepad.right_stick(), deadzone, exponent);
+    }
 
-  // Update velocity
-  if axis_input != Vec3::ZERO {
-    let max_speed = if key_input.pressed(controller.key_run) {
-      controller.run_speed
-    } else {
-      controller.walk_speed
-    };
-    controller.velocity = axis_input.normalize() * max_speed;
-  } else {
-    let friction = controller.friction.clamp(0.0, 1.0);
-    controller.velocity *= 1.0 - friction;
-    if controller.velocity.length_squared() < 1e-6 {
+    if bindings.just_pressed(Action::CycleMovementMode, &input_state) {
+      controller.movement_mode = controller.movement_mode.next();
       controller.velocity = Vec3::ZERO;
+      info!("Camera movement mode: {:?}", controller.movement_mode);
+    }
+    if bindings.just_pressed(Action::CycleCameraMode, &input_state) {
+      let mode = controller.camera_mode.next();
+      controller.set_camera_mode(mode, &transform);
+      info!("Camera mode: {:?}", controller.camera_mode);
     }
-  }
 
-  // Apply movement update
-  if controller.velocity != Vec3::ZERO {
-    let forward = *transform.forward();
-    let right = *transform.right();
-    transform.translation += controller.velocity.x * dt * right
-      + controller.velocity.y * dt * Vec3::Y
-      + controller.velocity.z * dt * forward;
-  }
+    let mut cursor_grab_change = false;
+    if bindings.just_pressed(Action::ToggleGrab, &input_state) {
+      controller.cursor_grab_toggled = !controller.cursor_grab_toggled;
+      cursor_grab_change = true;
+    }
+    if bindings.just_pressed(Action::HoldGrab, &input_state) {
+      controller.cursor_grab_held = true;
+      cursor_grab_change = true;
+    }
+    if bindings.just_released(Action::HoldGrab, &input_state) {
+      controller.cursor_grab_held = false;
+      cursor_grab_change = true;
+    }
+    let cursor_grab = controller.cursor_grab_held || controller.cursor_grab_toggled;
 
-  // Handle cursor grab
-  if cursor_grab_change {
-    if cursor_grab {
-      for (window, mut cursor_options) in &mut windows {
-        if !window.focused {
-          continue;
-        }
+    let input = MovementInput {
+      axis: axis_input.clamp(Vec3::NEG_ONE, Vec3::ONE),
+      run: bindings.pressed(Action::Sprint, &input_state),
+      jump: value(Action::MoveUp) > 0.5,
+      crouch: value(Action::MoveDown) > 0.5,
+    };
 
+    // the body stays in place while orbiting
+    if controller.camera_mode != CameraMode::Orbit {
+      let mut body = controller.body;
+      controller.advance(&mut body, &input, dt, &is_solid);
+      controller.body = body;
+    }
+
+    // Handle cursor grab of the window this camera renders to
+    let window = match camera.target.normalize(primary_window.single().ok()) {
+      Some(NormalizedRenderTarget::Window(window)) => windows.get_mut(window.entity()).ok(),
+      _ => None,
+    };
+    if cursor_grab_change && let Some((window, mut cursor_options)) = window {
+      if cursor_grab && window.focused {
         cursor_options.grab_mode = CursorGrabMode::Locked;
         cursor_options.visible = false;
-      }
-    } else {
-      for (_, mut cursor_options) in &mut windows {
+      } else if !cursor_grab {
         cursor_options.grab_mode = CursorGrabMode::None;
         cursor_options.visible = true;
       }
     }
-  }
 
-  // Handle mouse input
-  if accumulated_mouse_motion.delta != Vec2::ZERO && cursor_grab {
-    // Apply look update
-    controller.pitch = (controller.pitch
-      - accumulated_mouse_motion.delta.y * RADIANS_PER_DOT * controller.sensitivity)
-      .clamp(-PI / 2., PI / 2.);
-    controller.yaw -= accumulated_mouse_motion.delta.x * RADIANS_PER_DOT * controller.sensitivity;
-    transform.rotation = Quat::from_euler(EulerRot::ZYX, 0.0, controller.yaw, controller.pitch);
+    // Handle mouse input, dragging the orbit focus instead of looking while panning
+    let panning =
+      controller.camera_mode == CameraMode::Orbit && bindings.pressed(Action::Pan, &input_state);
+    if mouse_motion != Vec2::ZERO && panning {
+      controller.pan(mouse_motion);
+    } else if mouse_motion != Vec2::ZERO && cursor_grab {
+      // Apply look update
+      controller.pitch = (controller.pitch
+        - mouse_motion.y * RADIANS_PER_DOT * controller.sensitivity)
+        .clamp(-PI / 2., PI / 2.);
+      controller.yaw -= mouse_motion.x * RADIANS_PER_DOT * controller.sensitivity;
+    }
+
+    // Handle gamepad look, scaled by time instead of per dot like the mouse
+    if gamepad_look != Vec2::ZERO {
+      let speed = controller.gamepad_look_speed * dt;
+      controller.pitch = (controller.pitch + gamepad_look.y * speed).clamp(-PI / 2., PI / 2.);
+      controller.yaw -= gamepad_look.x * speed;
+    }
+
+    controller.body.rotation = controller.look_rotation();
+    *transform = controller.camera_transform(dt, &is_solid);
   }
 }
@@ -0,0 +1,204 @@
+//! Commands that run without a window or GPU.
+
+use std::{
+  path::{Path, PathBuf},
+  str::FromStr,
+  time::{Duration, Instant},
+};
+
+use bevy::{prelude::*, tasks::ComputeTaskPool};
+use serde::Serialize;
+
+use crate::voxel::{
+  BlockMapping, BlockVolume, ChunkRegion, ExportMesh, Generator, VoxelWorld, generate_region,
+  mesh_region,
+};
+
+/// Runs `command` if it is a headless command, `None` otherwise.
+pub fn run(command: &str, args: &[String]) -> Option<AppExit> {
+  let result = match command {
+    "bench" => bench(args),
+    "export" => export(args),
+    _ => return None,
+  };
+
+  Some(match result {
+    Ok(()) => AppExit::Success,
+    Err(error) => {
+      eprintln!("{command}: {error}");
+      AppExit::error()
+    }
+  })
+}
+
+/// Value following `--name`, `None` if the flag isn't given.
+fn option<T: FromStr>(args: &[String], name: &str) -> Result<Option<T>, String> {
+  let Some(index) = args.iter().position(|arg| arg == name) else {
+    return Ok(None);
+  };
+  let value = args
+    .get(index + 1)
+    .ok_or_else(|| format!("missing value for {name}"))?;
+  value
+    .parse()
+    .map(Some)
+    .map_err(|_| format!("invalid value for {name}: {value}"))
+}
+
+/// Region selected with `--radius`, `--min-height` and `--max-height`.
+fn region(args: &[String]) -> Result<ChunkRegion, String> {
+  let radius = option(args, "--radius")?.unwrap_or(8);
+  let min_height = option(args, "--min-height")?.unwrap_or(-2);
+  let max_height = option(args, "--max-height")?.unwrap_or(1);
+  Ok(ChunkRegion::around_origin(radius, min_height..=max_height))
+}
+
+/// Timing of one pipeline stage.
+#[derive(Serialize)]
+struct StageReport {
+  /// Wall clock time for the whole region.
+  total_ms: f64,
+  mean_chunk_us: f64,
+  max_chunk_us: f64,
+}
+
+impl StageReport {
+  fn new(total: Duration, chunks: impl Iterator<Item = Duration> + Clone) -> Self {
+    let count = chunks.clone().count().max(1) as f64;
+    Self {
+      total_ms: total.as_secs_f64() * 1e3,
+      mean_chunk_us: chunks.clone().map(|time| time.as_secs_f64()).sum::<f64>() / count * 1e6,
+      max_chunk_us: chunks.max().unwrap_or_default().as_secs_f64() * 1e6,
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct BenchReport {
+  seed: u32,
+  chunks: usize,
+  threads: usize,
+  generation: StageReport,
+  meshing: StageReport,
+  quads: usize,
+  translucent_quads: usize,
+  liquid_faces: usize,
+  vertices: usize,
+  indices: usize,
+}
+
+/// `bench [--seed <n>] [--radius <chunks>] [--min-height <chunk>] [--max-height <chunk>]`
+///
+/// Generates and meshes a region of chunks in parallel and prints timing and mesh
+/// statistics as JSON.
+fn bench(args: &[String]) -> Result<(), String> {
+  let seed = option(args, "--seed")?.unwrap_or(0);
+  let region = region(args)?;
+
+  let start = Instant::now();
+  let (chunks, generation_times): (Vec<_>, Vec<_>) =
+    generate_region(seed, &region, &Generator::default())
+      .into_iter()
+      .unzip();
+  let generation = StageReport::new(start.elapsed(), generation_times.into_iter());
+
+  let start = Instant::now();
+  let meshes = mesh_region(&chunks);
+  let meshing = StageReport::new(start.elapsed(), meshes.iter().map(|(_, time)| *time));
+
+  let quads = meshes.iter().map(|(mesh, _)| mesh.quads.len()).sum();
+  let translucent_quads = meshes
+    .iter()
+    .map(|(mesh, _)| mesh.translucent_quads.len())
+    .sum();
+  let liquid_faces = meshes.iter().map(|(mesh, _)| mesh.liquid_faces.len()).sum();
+  // every quad and liquid face is drawn as two triangles
+  let faces = quads + translucent_quads + liquid_faces;
+
+  let report = BenchReport {
+    seed,
+    chunks: chunks.len(),
+    threads: ComputeTaskPool::get().thread_num(),
+    generation,
+    meshing,
+    quads,
+    translucent_quads,
+    liquid_faces,
+    vertices: faces * 4,
+    indices: faces * 6,
+  };
+  let json = serde_json::to_string_pretty(&report).map_err(|error| error.to_string())?;
+  println!("{json}");
+  Ok(())
+}
+
+/// `export <file.obj|file.glb|file.vox|file.schem> [--seed <n>] [--mapping <file.ron>] [--radius <chunks>] [--min-height <chunk>] [--max-height <chunk>]`
+///
+/// Generates a region of chunks and writes it depending on the extension of the
+/// output file: meshed as OBJ or binary glTF, or as blocks to a MagicaVoxel file or
+/// a Sponge schematic. Block ids are mapped with the [`BlockMapping`] read from
+/// `--mapping`, or the default one.
+fn export(args: &[String]) -> Result<(), String> {
+  let path = args
+    .first()
+    .filter(|arg| !arg.starts_with("--"))
+    .map(PathBuf::from)
+    .ok_or("missing output file")?;
+  let seed = option(args, "--seed")?.unwrap_or(0);
+  let region = region(args)?;
+  let mapping = match option::<PathBuf>(args, "--mapping")? {
+    Some(mapping) => BlockMapping::load(mapping).map_err(|error| error.to_string())?,
+    None => BlockMapping::default(),
+  };
+
+  let chunks: Vec<_> = generate_region(seed, &region, &Generator::default())
+    .into_iter()
+    .map(|(chunk, _)| chunk)
+    .collect();
+
+  let extension = Path::new(&path).extension().and_then(|ext| ext.to_str());
+  if let Some("vox" | "schem") = extension {
+    let (min, max) = region.block_bounds();
+    let mut world = VoxelWorld::default();
+    for chunk in chunks {
+      world.insert(chunk);
+    }
+    let volume = BlockVolume::from_world(&world, min, max);
+
+    let result = match extension {
+      Some("vox") => volume
+        .write_vox(&path, &mapping)
+        .map_err(|error| error.to_string()),
+      _ => volume
+        .write_schem(&path, &mapping)
+        .map_err(|error| error.to_string()),
+    };
+    result?;
+
+    eprintln!(
+      "Exported {} blocks within {min} to {max} to {}",
+      volume.blocks.iter().filter(|block| **block != 0).count(),
+      path.display()
+    );
+    return Ok(());
+  }
+
+  let meshes = mesh_region(&chunks);
+  let export = ExportMesh::from_chunks(meshes.iter().map(|(mesh, _)| mesh));
+
+  let result = match extension {
+    Some("obj") => export.write_obj(&path),
+    Some("glb") => export.write_glb(&path),
+    _ => return Err(format!("unsupported format: {}", path.display())),
+  };
+  result.map_err(|error| error.to_string())?;
+
+  let (min, max) = export.bounds().unwrap_or_default();
+  eprintln!(
+    "Exported {} vertices and {} triangles within {min} to {max} to {}",
+    export.vertex_count(),
+    export.index_count() / 3,
+    path.display()
+  );
+  Ok(())
+}
@@ -1,35 +1,159 @@
-use bevy::{
-  color::palettes::css::WHITE,
-  pbr::wireframe::{WireframeConfig, WireframePlugin},
-  prelude::*,
-};
+use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*, window::PresentMode};
 
-use crate::{camera::CameraControllerPlugin, voxel::VoxelPlugin};
+use crate::{
+  benchmark::BenchmarkPlugin,
+  camera::{CameraControllerPlugin, CameraPathPlugin, InputSource, SplitScreenPlugin},
+  options::Options,
+  voxel::{
+    ChunkBackend, DebugOverlayPlugin, Generator, InstancingExamplePlugin, TimeOfDay,
+    VoxelDiagnosticsPlugin, VoxelPlugin,
+  },
+};
 
+mod benchmark;
 mod camera;
+mod headless;
+mod options;
 mod voxel;
 
-fn main() {
-  App::new()
-    .add_plugins(DefaultPlugins.set(AssetPlugin {
-      watch_for_changes_override: Some(true),
-      ..Default::default()
-    }))
+/// Where the first camera starts without a `start_position` option.
+const DEFAULT_START_POSITION: Vec3 = Vec3::new(-2.5, 12.5, 9.0);
+
+fn main() -> AppExit {
+  // `bench` and the other headless commands run without opening a window
+  let args: Vec<String> = std::env::args().collect();
+  if let Some(exit) = args
+    .get(1)
+    .and_then(|command| headless::run(command, &args[2..]))
+  {
+    return exit;
+  }
+
+  // command line flags override the config file, see `options`
+  let options = match Options::load(args.get(1..).unwrap_or_default()) {
+    Ok(options) => options,
+    Err(error) => {
+      eprintln!("{error}");
+      return AppExit::error();
+    }
+  };
+
+  let mut window = Window::default();
+  if let Some(size) = options.window_size {
+    window.resolution = size.into();
+  }
+  if let Some(vsync) = options.vsync {
+    window.present_mode = if vsync {
+      PresentMode::AutoVsync
+    } else {
+      PresentMode::AutoNoVsync
+    };
+  }
+
+  let mut voxel_plugin = VoxelPlugin::default();
+  if let Some(seed) = options.seed {
+    voxel_plugin = voxel_plugin.with_seed(seed);
+  }
+  if let Some(render_distance) = options.render_distance {
+    voxel_plugin = voxel_plugin.with_render_distance(render_distance);
+  }
+  if let (Some(min), Some(max)) = (options.min_height, options.max_height) {
+    voxel_plugin = voxel_plugin.with_height_range(min..=max);
+  }
+  if let Some(height) = options.flat {
+    voxel_plugin = voxel_plugin.with_generator(Generator::Flat { height });
+  }
+  // the vertex layout only applies to the indirect backend, so it selects it
+  let backend = options
+    .backend
+    .or(options.vertex_layout.map(|_| ChunkBackend::Indirect));
+  if let Some(backend) = backend {
+    voxel_plugin = voxel_plugin.with_backend(backend);
+  }
+  if let Some(mesh_layout) = options.mesh_layout {
+    voxel_plugin = voxel_plugin.with_mesh_layout(mesh_layout);
+  }
+  if let Some(vertex_layout) = options.vertex_layout {
+    if backend != Some(ChunkBackend::Indirect) {
+      eprintln!("vertex_layout is ignored without the indirect backend");
+    }
+    voxel_plugin = voxel_plugin.with_vertex_layout(vertex_layout);
+  }
+  if let Some(sky) = options.sky {
+    voxel_plugin = voxel_plugin.with_sky(sky);
+  }
+  if options.day_night == Some(true) {
+    let mut time_of_day = TimeOfDay::default();
+    if let Some(day_length) = options.day_length {
+      time_of_day.day_length = day_length;
+    }
+    if let Some(time) = options.time_of_day {
+      time_of_day.time = time.rem_euclid(1.0);
+    }
+    time_of_day.paused = options.pause_time.unwrap_or_default();
+    voxel_plugin = voxel_plugin.with_time_of_day(time_of_day);
+  }
+
+  // several players split the window, the first one uses keyboard and mouse and
+  // the others a gamepad each
+  let players = options.players.unwrap_or(1).clamp(1, 4);
+  let start_position = options.start_position.unwrap_or(DEFAULT_START_POSITION);
+
+  let mut app = App::new();
+  app
+    .add_plugins(
+      DefaultPlugins
+        .set(AssetPlugin {
+          watch_for_changes_override: Some(true),
+          ..Default::default()
+        })
+        .set(WindowPlugin {
+          primary_window: Some(window),
+          ..default()
+        }),
+    )
     .add_plugins(CameraControllerPlugin)
-    .add_plugins(WireframePlugin::default())
-    .add_plugins(VoxelPlugin)
-    .add_systems(Startup, setup)
-    .insert_resource(WireframeConfig {
-      global: true,
-      default_color: WHITE.into(),
+    .add_plugins(CameraPathPlugin {
+      playback: options.camera_path,
     })
-    .run();
+    .add_plugins(SplitScreenPlugin)
+    .add_plugins(voxel_plugin)
+    .add_plugins(VoxelDiagnosticsPlugin)
+    .add_plugins(DebugOverlayPlugin)
+    .add_systems(Startup, move |commands: Commands| {
+      setup(commands, players, start_position)
+    });
+  if options.benchmark == Some(true) {
+    app.add_plugins(BenchmarkPlugin);
+  }
+  if options.instancing_example == Some(true) {
+    app.add_plugins(InstancingExamplePlugin);
+  }
+  if options.log_diagnostics == Some(true) {
+    app.add_plugins(LogDiagnosticsPlugin::filtered(
+      VoxelDiagnosticsPlugin::ALL.into_iter().collect(),
+    ));
+  }
+  app.run()
 }
 
-fn setup(mut commands: Commands) {
-  // camera
-  commands.spawn((
-    camera::camera_components(),
-    Transform::from_xyz(-2.5, 12.5, 9.0).looking_at(Vec3::new(8.0, 10.0, 8.0), Vec3::Y),
-  ));
+fn setup(mut commands: Commands, players: usize, start_position: Vec3) {
+  // cameras
+  for player in 0..players {
+    let input = match player {
+      0 if players == 1 => InputSource::Any,
+      0 => InputSource::KeyboardMouse,
+      _ => InputSource::Gamepad(player - 1),
+    };
+    let offset = Vec3::X * 2.0 * player as f32;
+    commands.spawn((
+      camera::camera_components(input),
+      Camera {
+        order: player as isize,
+        ..default()
+      },
+      Transform::from_translation(start_position + offset)
+        .looking_to(Vec3::new(10.5, -2.5, -1.0), Vec3::Y),
+    ));
+  }
 }
@@ -0,0 +1,356 @@
+//! Startup options read from the command line and a TOML config file.
+//!
+//! Every option is a `snake_case` key in the config file and a `--kebab-case` flag
+//! on the command line, which takes precedence. Boolean options are set with
+//! `--name` and cleared with `--no-name`.
+
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use thiserror::Error;
+use toml_edit::{Document, Value};
+
+use crate::voxel::{ChunkBackend, ChunkMeshLayout, ChunkVertexLayout};
+
+/// Config file read when `--config` isn't given, if it exists.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Options of the windowed app, `None` where neither source sets one.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+  pub seed: Option<u32>,
+  pub render_distance: Option<i32>,
+  /// Lowest chunk height that is loaded, used together with `max_height`.
+  pub min_height: Option<i32>,
+  pub max_height: Option<i32>,
+  /// Replaces the terrain with flat ground at this height.
+  pub flat: Option<i32>,
+  /// `meshes` or `indirect`, see [`ChunkBackend`].
+  pub backend: Option<ChunkBackend>,
+  /// `packed` or `greedy`, see [`ChunkMeshLayout`].
+  pub mesh_layout: Option<ChunkMeshLayout>,
+  /// `vertices` or `quads`, see [`ChunkVertexLayout`]. Selects the indirect
+  /// backend unless `backend` is given.
+  pub vertex_layout: Option<ChunkVertexLayout>,
+  /// Draws a procedural sky dome that colours the fog.
+  pub sky: Option<bool>,
+  /// Runs the day/night cycle with a shadow casting sun.
+  pub day_night: Option<bool>,
+  /// Seconds of a full day.
+  pub day_length: Option<f32>,
+  /// Fraction of the day the cycle starts at, 0.5 is noon.
+  pub time_of_day: Option<f32>,
+  /// Starts the day/night cycle paused.
+  pub pause_time: Option<bool>,
+  /// Physical size of the window, `1280x720` on the command line.
+  pub window_size: Option<UVec2>,
+  pub vsync: Option<bool>,
+  /// Where the first camera starts, `x,y,z` on the command line.
+  pub start_position: Option<Vec3>,
+  /// Number of split-screen players.
+  pub players: Option<usize>,
+  /// Recorded camera path that is played back, the app exits once it finishes.
+  pub camera_path: Option<PathBuf>,
+  /// Flies a fixed camera path, prints frame statistics and exits.
+  pub benchmark: Option<bool>,
+  /// Logs the timings of the voxel pipeline every second.
+  pub log_diagnostics: Option<bool>,
+  /// Adds a grid of flowers drawn through the instanced chunk pipeline.
+  pub instancing_example: Option<bool>,
+}
+
+impl Options {
+  /// Reads the options from `args`, without the program name, and the config file
+  /// given with `--config` or [`DEFAULT_CONFIG_PATH`].
+  pub fn load(args: &[String]) -> Result<Self, OptionsError> {
+    let mut sources = Sources {
+      args,
+      used_args: vec![false; args.len()],
+      file: None,
+      used_keys: Vec::new(),
+    };
+
+    let config = sources.arg::<PathBuf>("config")?;
+    let path = match &config {
+      Some(path) => Some(path.as_path()),
+      None => Some(Path::new(DEFAULT_CONFIG_PATH)).filter(|path| path.exists()),
+    };
+    if let Some(path) = path {
+      sources.file = Some(Document::parse(fs::read_to_string(path)?)?);
+    }
+
+    let options = Self {
+      seed: sources.get("seed")?,
+      render_distance: sources.get("render_distance")?,
+      min_height: sources.get("min_height")?,
+      max_height: sources.get("max_height")?,
+      flat: sources.get("flat")?,
+      backend: sources.get("backend")?,
+      mesh_layout: sources.get("mesh_layout")?,
+      vertex_layout: sources.get("vertex_layout")?,
+      sky: sources.get("sky")?,
+      day_night: sources.get("day_night")?,
+      day_length: sources.get("day_length")?,
+      time_of_day: sources.get("time_of_day")?,
+      pause_time: sources.get("pause_time")?,
+      window_size: sources.get("window_size")?,
+      vsync: sources.get("vsync")?,
+      start_position: sources.get("start_position")?,
+      players: sources.get("players")?,
+      camera_path: sources.get("camera_path")?,
+      benchmark: sources.get("benchmark")?,
+      log_diagnostics: sources.get("log_diagnostics")?,
+      instancing_example: sources.get("instancing_example")?,
+    };
+    sources.finish()?;
+    Ok(options)
+  }
+}
+
+/// Command line arguments and config file, keeping track of what was read so
+/// unknown options can be reported.
+struct Sources<'a> {
+  args: &'a [String],
+  used_args: Vec<bool>,
+  file: Option<Document<String>>,
+  used_keys: Vec<&'static str>,
+}
+
+impl Sources<'_> {
+  /// Value of `key`, from the command line if given there and the config file otherwise.
+  fn get<T: OptionValue>(&mut self, key: &'static str) -> Result<Option<T>, OptionsError> {
+    // the file value is checked even when overridden, so mistakes in it show up
+    let file_value = match self.file.as_ref().and_then(|file| file.as_table().get(key)) {
+      Some(item) => {
+        self.used_keys.push(key);
+        let value = item.as_value().and_then(T::from_toml);
+        Some(value.ok_or(OptionsError::InvalidKey(key))?)
+      }
+      None => None,
+    };
+    Ok(self.arg(key)?.or(file_value))
+  }
+
+  /// Value of the command line flag for `key`.
+  fn arg<T: OptionValue>(&mut self, key: &str) -> Result<Option<T>, OptionsError> {
+    let flag = format!("--{}", key.replace('_', "-"));
+    let negated = format!("--no-{}", key.replace('_', "-"));
+    let Some(index) = self
+      .args
+      .iter()
+      .position(|arg| *arg == flag || (T::FLAG && *arg == negated))
+    else {
+      return Ok(None);
+    };
+    self.used_args[index] = true;
+
+    if T::FLAG {
+      return Ok(T::from_arg(if self.args[index] == flag {
+        "true"
+      } else {
+        "false"
+      }));
+    }
+
+    let value = self
+      .args
+      .get(index + 1)
+      .ok_or_else(|| OptionsError::MissingArg(flag.clone()))?;
+    self.used_args[index + 1] = true;
+    T::from_arg(value)
+      .map(Some)
+      .ok_or_else(|| OptionsError::InvalidArg {
+        flag,
+        value: value.clone(),
+      })
+  }
+
+  /// Fails on the first argument or config key that wasn't read.
+  fn finish(self) -> Result<(), OptionsError> {
+    if let Some((arg, _)) = self
+      .args
+      .iter()
+      .zip(&self.used_args)
+      .find(|(_, used)| !**used)
+    {
+      return Err(OptionsError::UnknownArg(arg.clone()));
+    }
+    if let Some(file) = &self.file
+      && let Some((key, _)) = file
+        .as_table()
+        .iter()
+        .find(|(key, _)| !self.used_keys.contains(key))
+    {
+      return Err(OptionsError::UnknownKey(key.to_string()));
+    }
+    Ok(())
+  }
+}
+
+/// Type of an option, parsed from a command line argument or a config file value.
+trait OptionValue: Sized {
+  /// Whether the option is a flag without a value on the command line.
+  const FLAG: bool = false;
+
+  fn from_arg(arg: &str) -> Option<Self>;
+
+  fn from_toml(value: &Value) -> Option<Self>;
+}
+
+impl OptionValue for u32 {
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_integer()?.try_into().ok()
+  }
+}
+
+impl OptionValue for i32 {
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_integer()?.try_into().ok()
+  }
+}
+
+impl OptionValue for usize {
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_integer()?.try_into().ok()
+  }
+}
+
+impl OptionValue for f32 {
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value
+      .as_float()
+      .or_else(|| value.as_integer().map(|value| value as f64))
+      .map(|value| value as f32)
+  }
+}
+
+impl OptionValue for bool {
+  const FLAG: bool = true;
+
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_bool()
+  }
+}
+
+impl OptionValue for PathBuf {
+  fn from_arg(arg: &str) -> Option<Self> {
+    Some(PathBuf::from(arg))
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_str().map(PathBuf::from)
+  }
+}
+
+impl OptionValue for ChunkBackend {
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_str()?.parse().ok()
+  }
+}
+
+impl OptionValue for ChunkMeshLayout {
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_str()?.parse().ok()
+  }
+}
+
+impl OptionValue for ChunkVertexLayout {
+  fn from_arg(arg: &str) -> Option<Self> {
+    arg.parse().ok()
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    value.as_str()?.parse().ok()
+  }
+}
+
+/// `WIDTHxHEIGHT` or `[width, height]`.
+impl OptionValue for UVec2 {
+  fn from_arg(arg: &str) -> Option<Self> {
+    let (width, height) = arg.split_once('x')?;
+    Some(UVec2::new(width.parse().ok()?, height.parse().ok()?))
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    let values: Vec<u32> = value
+      .as_array()?
+      .iter()
+      .map(|value| value.as_integer()?.try_into().ok())
+      .collect::<Option<_>>()?;
+    Some(UVec2::from_array(values.try_into().ok()?))
+  }
+}
+
+/// `x,y,z` or `[x, y, z]`.
+impl OptionValue for Vec3 {
+  fn from_arg(arg: &str) -> Option<Self> {
+    let values: Vec<f32> = arg
+      .split(',')
+      .map(|part| part.trim().parse().ok())
+      .collect::<Option<_>>()?;
+    Some(Vec3::from_array(values.try_into().ok()?))
+  }
+
+  fn from_toml(value: &Value) -> Option<Self> {
+    let values: Vec<f32> = value
+      .as_array()?
+      .iter()
+      .map(|value| {
+        value
+          .as_float()
+          .or_else(|| value.as_integer().map(|value| value as f64))
+          .map(|value| value as f32)
+      })
+      .collect::<Option<_>>()?;
+    Some(Vec3::from_array(values.try_into().ok()?))
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum OptionsError {
+  #[error("could not read config file: {0}")]
+  Io(#[from] io::Error),
+  #[error("could not parse config file: {0}")]
+  Parse(#[from] toml_edit::TomlError),
+  #[error("missing value for {0}")]
+  MissingArg(String),
+  #[error("invalid value for {flag}: {value}")]
+  InvalidArg { flag: String, value: String },
+  #[error("invalid value for {0} in config file")]
+  InvalidKey(&'static str),
+  #[error("unknown argument: {0}")]
+  UnknownArg(String),
+  #[error("unknown key in config file: {0}")]
+  UnknownKey(String),
+}
@@ -0,0 +1,258 @@
+use std::{hash::Hash, ops::Range};
+
+use bevy::platform::collections::HashMap;
+
+/// A moved allocation, expressed in elements of the backing buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relocation {
+  pub from: u32,
+  pub to: u32,
+  pub len: u32,
+}
+
+/// First-fit sub-allocator for ranges of one large GPU buffer.
+///
+/// Only the bookkeeping lives here; the owner is responsible for resizing the
+/// actual buffer when [`capacity`](BufferAllocator::capacity) changes and for
+/// copying data according to the [`Relocation`]s returned by
+/// [`defragment`](BufferAllocator::defragment).
+pub struct BufferAllocator<K> {
+  capacity: u32,
+  /// Free ranges sorted by start, never adjacent to each other.
+  free: Vec<Range<u32>>,
+  allocations: HashMap<K, Range<u32>>,
+}
+
+impl<K: Copy + Eq + Hash> BufferAllocator<K> {
+  pub fn new(capacity: u32) -> Self {
+    let mut free = Vec::new();
+    if capacity > 0 {
+      free.push(0..capacity);
+    }
+
+    Self {
+      capacity,
+      free,
+      allocations: HashMap::default(),
+    }
+  }
+
+  #[inline]
+  pub fn capacity(&self) -> u32 {
+    self.capacity
+  }
+
+  #[inline]
+  pub fn get(&self, key: &K) -> Option<Range<u32>> {
+    self.allocations.get(key).cloned()
+  }
+
+  /// Fraction of the free space that is not part of the largest free range.
+  ///
+  /// `0.0` means all free space is contiguous, values close to `1.0` mean the
+  /// free space is scattered in many small holes.
+  pub fn fragmentation(&self) -> f32 {
+    let total: u32 = self.free.iter().map(|range| range.len() as u32).sum();
+    if total == 0 {
+      return 0.0;
+    }
+    let largest = self
+      .free
+      .iter()
+      .map(|range| range.len() as u32)
+      .max()
+      .unwrap_or(0);
+    1.0 - largest as f32 / total as f32
+  }
+
+  /// Allocates `len` elements for `key`, growing the capacity if no free range is
+  /// large enough. An existing allocation for `key` is released first.
+  pub fn allocate(&mut self, key: K, len: u32) -> Range<u32> {
+    self.deallocate(&key);
+
+    if len == 0 {
+      self.allocations.insert(key, 0..0);
+      return 0..0;
+    }
+
+    let index = match self.free.iter().position(|range| range.len() as u32 >= len) {
+      Some(index) => index,
+      None => {
+        self.grow((self.capacity * 2).max(self.capacity + len));
+        self
+          .free
+          .iter()
+          .position(|range| range.len() as u32 >= len)
+          .expect("growing always leaves a large enough free range at the end")
+      }
+    };
+
+    let start = self.free[index].start;
+    self.free[index].start += len;
+    if self.free[index].is_empty() {
+      self.free.remove(index);
+    }
+
+    let range = start..start + len;
+    self.allocations.insert(key, range.clone());
+    range
+  }
+
+  /// Releases the allocation of `key`, returning the range it occupied.
+  pub fn deallocate(&mut self, key: &K) -> Option<Range<u32>> {
+    let range = self.allocations.remove(key)?;
+    if range.is_empty() {
+      return Some(range);
+    }
+
+    let index = self.free.partition_point(|free| free.start < range.start);
+    self.free.insert(index, range.clone());
+
+    // merge with the following range
+    if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+      self.free[index].end = self.free[index + 1].end;
+      self.free.remove(index + 1);
+    }
+    // merge with the preceding range
+    if index > 0 && self.free[index - 1].end == self.free[index].start {
+      self.free[index - 1].end = self.free[index].end;
+      self.free.remove(index);
+    }
+
+    Some(range)
+  }
+
+  /// Extends the capacity to `new_capacity`. Existing allocations keep their offsets.
+  pub fn grow(&mut self, new_capacity: u32) {
+    if new_capacity <= self.capacity {
+      return;
+    }
+
+    match self.free.last_mut() {
+      Some(last) if last.end == self.capacity => last.end = new_capacity,
+      _ => self.free.push(self.capacity..new_capacity),
+    }
+    self.capacity = new_capacity;
+  }
+
+  /// Packs all allocations to the start of the buffer, preserving their order.
+  ///
+  /// Returns every allocation that changed its offset. Allocations that are not
+  /// part of the result keep their previous offset.
+  pub fn defragment(&mut self) -> Vec<Relocation> {
+    let mut allocations: Vec<_> = self
+      .allocations
+      .values_mut()
+      .filter(|range| range.start < range.end)
+      .collect();
+    allocations.sort_by_key(|range| range.start);
+
+    let mut relocations = Vec::new();
+    let mut offset = 0;
+    for range in allocations {
+      let len = range.len() as u32;
+      if range.start != offset {
+        relocations.push(Relocation {
+          from: range.start,
+          to: offset,
+          len,
+        });
+        *range = offset..offset + len;
+      }
+      offset += len;
+    }
+
+    self.free.clear();
+    if offset < self.capacity {
+      self.free.push(offset..self.capacity);
+    }
+
+    relocations
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn allocating_past_capacity_grows() {
+    let mut allocator = BufferAllocator::new(8);
+    assert_eq!(allocator.allocate(0, 6), 0..6);
+    // doubled, and the new space continues the free range at the end
+    assert_eq!(allocator.allocate(1, 4), 6..10);
+    assert_eq!(allocator.capacity(), 16);
+    // large requests grow by at least their length
+    assert_eq!(allocator.allocate(2, 40), 10..50);
+    assert_eq!(allocator.capacity(), 56);
+    assert_eq!(allocator.free, vec![50..56]);
+  }
+
+  #[test]
+  fn deallocate_merges_with_both_neighbors() {
+    let mut allocator = BufferAllocator::new(12);
+    allocator.allocate(0, 4);
+    allocator.allocate(1, 4);
+    allocator.allocate(2, 4);
+
+    assert_eq!(allocator.deallocate(&0), Some(0..4));
+    assert_eq!(allocator.deallocate(&2), Some(8..12));
+    assert_eq!(allocator.free, vec![0..4, 8..12]);
+
+    assert_eq!(allocator.deallocate(&1), Some(4..8));
+    assert_eq!(allocator.free, vec![0..12]);
+    assert_eq!(allocator.deallocate(&1), None);
+  }
+
+  #[test]
+  fn reallocating_a_key_releases_its_range() {
+    let mut allocator = BufferAllocator::new(16);
+    allocator.allocate(0, 4);
+    allocator.allocate(1, 4);
+
+    // the old range is freed first, so the smaller allocation reuses it
+    assert_eq!(allocator.allocate(0, 2), 0..2);
+    assert_eq!(allocator.get(&0), Some(0..2));
+    assert_eq!(allocator.free, vec![2..4, 8..16]);
+
+    assert_eq!(allocator.allocate(0, 0), 0..0);
+    assert_eq!(allocator.free, vec![0..4, 8..16]);
+  }
+
+  #[test]
+  fn defragment_packs_allocations() {
+    let mut allocator = BufferAllocator::new(16);
+    for key in 0..4 {
+      allocator.allocate(key, 4);
+    }
+    allocator.deallocate(&0);
+    allocator.deallocate(&2);
+    // 4 of the 8 free elements are outside the largest free range
+    assert_eq!(allocator.fragmentation(), 0.5);
+
+    let mut relocations = allocator.defragment();
+    relocations.sort_by_key(|relocation| relocation.from);
+    assert_eq!(
+      relocations,
+      vec![
+        Relocation {
+          from: 4,
+          to: 0,
+          len: 4
+        },
+        Relocation {
+          from: 12,
+          to: 4,
+          len: 4
+        },
+      ]
+    );
+    assert_eq!(allocator.get(&1), Some(0..4));
+    assert_eq!(allocator.get(&3), Some(4..8));
+    assert_eq!(allocator.free, vec![8..16]);
+    assert_eq!(allocator.fragmentation(), 0.0);
+
+    // packed allocations don't move again
+    assert!(allocator.defragment().is_empty());
+  }
+}
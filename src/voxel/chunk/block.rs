@@ -0,0 +1,109 @@
+use bevy::color::LinearRgba;
+
+/// Block ids stored in the low bits of [`ChunkBlockData`](super::generation::ChunkBlockData).
+/// Must match `block_colors` in `chunk_util.wgsl`.
+pub const AIR: u8 = 0;
+pub const GRASS: u8 = 1;
+pub const WATER: u8 = 2;
+pub const GLASS: u8 = 3;
+pub const LEAVES: u8 = 4;
+pub const LAVA: u8 = 5;
+
+/// Bits of a block holding its id, the remaining bits store the liquid state as
+/// `flll` with the falling flag `f` and the level `l`.
+const ID_MASK: u8 = 0x0F;
+const LEVEL_SHIFT: u32 = 4;
+const FALLING_BIT: u8 = 0x80;
+
+/// Level of the thinnest flowing liquid, sources have level 0.
+pub const MAX_LIQUID_LEVEL: u8 = 7;
+
+/// How a block is rendered and which neighboring faces it hides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+  Air,
+  /// Fully opaque, hides every face behind it.
+  Opaque,
+  /// Alpha tested, rendered with the opaque geometry but doesn't hide faces behind it.
+  Cutout,
+  /// Alpha blended, rendered in a separate mesh sorted back to front.
+  Translucent,
+  /// Rendered with its own [`LiquidMaterial`](super::liquid::LiquidMaterial).
+  Liquid,
+}
+
+#[inline]
+pub fn id(block: u8) -> u8 {
+  block & ID_MASK
+}
+
+/// A liquid block with the given id, level and falling flag.
+#[inline]
+pub fn liquid(id: u8, level: u8, falling: bool) -> u8 {
+  (id & ID_MASK)
+    | (level.min(MAX_LIQUID_LEVEL) << LEVEL_SHIFT)
+    | if falling { FALLING_BIT } else { 0 }
+}
+
+#[inline]
+pub fn liquid_level(block: u8) -> u8 {
+  (block >> LEVEL_SHIFT) & MAX_LIQUID_LEVEL
+}
+
+/// Whether a liquid block is fed from above instead of from its sides.
+#[inline]
+pub fn liquid_falling(block: u8) -> bool {
+  block & FALLING_BIT != 0
+}
+
+#[inline]
+pub fn block_kind(block: u8) -> BlockKind {
+  match id(block) {
+    AIR => BlockKind::Air,
+    WATER | LAVA => BlockKind::Liquid,
+    GLASS => BlockKind::Translucent,
+    LEAVES => BlockKind::Cutout,
+    _ => BlockKind::Opaque,
+  }
+}
+
+/// Whether a block stops movement.
+#[inline]
+pub fn is_solid(block: u8) -> bool {
+  !matches!(block_kind(block), BlockKind::Air | BlockKind::Liquid)
+}
+
+/// Whether the face of `block` pointing towards `neighbor` has to be meshed.
+#[inline]
+pub fn face_visible(block: u8, neighbor: u8) -> bool {
+  match block_kind(neighbor) {
+    BlockKind::Opaque => false,
+    BlockKind::Translucent | BlockKind::Liquid => id(block) != id(neighbor),
+    BlockKind::Air | BlockKind::Cutout => true,
+  }
+}
+
+/// Lowercase name of a block id, used in exported files.
+pub fn name(block: u8) -> &'static str {
+  match id(block) {
+    AIR => "air",
+    GRASS => "grass",
+    WATER => "water",
+    GLASS => "glass",
+    LEAVES => "leaves",
+    LAVA => "lava",
+    _ => "unknown",
+  }
+}
+
+/// Base colour of a block, must match `block_colors` in `chunk_util.wgsl`.
+pub fn base_color(block: u8) -> LinearRgba {
+  match id(block) {
+    GRASS => LinearRgba::new(0.0, 0.2, 0.0, 1.0),
+    WATER => LinearRgba::new(0.05, 0.2, 0.5, 0.6),
+    GLASS => LinearRgba::new(0.8, 0.9, 1.0, 0.25),
+    LEAVES => LinearRgba::new(0.05, 0.35, 0.05, 1.0),
+    LAVA => LinearRgba::new(1.0, 0.3, 0.0, 1.0),
+    _ => LinearRgba::NONE,
+  }
+}
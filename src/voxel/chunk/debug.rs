@@ -0,0 +1,225 @@
+use std::fmt::Write;
+
+use bevy::{
+  color::palettes::css::{WHITE, YELLOW},
+  diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+  pbr::wireframe::{WireframeConfig, WireframePlugin},
+  prelude::*,
+};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE,
+  diagnostics::{VoxelDiagnosticsPlugin, percentile},
+  indirect::IndirectChunkMesh,
+  streaming::{ChunkStreamingQueue, ChunkViewer, stream_chunks},
+  world::{ChunkQuadCount, VoxelWorld},
+};
+
+/// Shows or hides the stats text.
+const TOGGLE_STATS: KeyCode = KeyCode::F3;
+/// Shows or hides the chunk borders.
+const TOGGLE_CHUNK_BORDERS: KeyCode = KeyCode::F4;
+/// Turns the wireframe of all meshes on or off.
+const TOGGLE_WIREFRAME: KeyCode = KeyCode::F5;
+
+/// Horizontal distance in chunks around each viewer whose borders are drawn.
+const CHUNK_BORDER_RADIUS: i32 = 2;
+
+/// Which parts of the debug overlay are shown, the wireframe is toggled through
+/// [`WireframeConfig::global`].
+#[derive(Resource, Clone, Debug)]
+pub struct DebugOverlay {
+  pub stats: bool,
+  pub chunk_borders: bool,
+}
+
+impl Default for DebugOverlay {
+  fn default() -> Self {
+    Self {
+      stats: true,
+      chunk_borders: false,
+    }
+  }
+}
+
+#[derive(Component)]
+struct DebugStatsText;
+
+/// Stats text, chunk border gizmos and a wireframe toggle, each switched with an
+/// F-key: F3 for the stats, F4 for chunk borders and F5 for the wireframe.
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+  fn build(&self, app: &mut App) {
+    if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+      app.add_plugins(FrameTimeDiagnosticsPlugin::default());
+    }
+    app
+      .add_plugins(WireframePlugin::default())
+      .insert_resource(WireframeConfig {
+        global: false,
+        default_color: WHITE.into(),
+      })
+      .init_resource::<DebugOverlay>()
+      .add_systems(Startup, spawn_stats_text)
+      .add_systems(
+        Update,
+        (
+          toggle_debug_overlay,
+          update_stats_text.after(stream_chunks),
+          draw_chunk_borders,
+        )
+          .chain(),
+      );
+  }
+}
+
+fn spawn_stats_text(mut commands: Commands) {
+  commands.spawn((
+    DebugStatsText,
+    Text::default(),
+    TextFont::from_font_size(14.0),
+    TextShadow::default(),
+    Node {
+      position_type: PositionType::Absolute,
+      top: px(8),
+      left: px(8),
+      ..default()
+    },
+  ));
+}
+
+fn toggle_debug_overlay(
+  keys: Res<ButtonInput<KeyCode>>,
+  mut overlay: ResMut<DebugOverlay>,
+  mut wireframe: ResMut<WireframeConfig>,
+) {
+  if keys.just_pressed(TOGGLE_STATS) {
+    overlay.stats = !overlay.stats;
+  }
+  if keys.just_pressed(TOGGLE_CHUNK_BORDERS) {
+    overlay.chunk_borders = !overlay.chunk_borders;
+  }
+  if keys.just_pressed(TOGGLE_WIREFRAME) {
+    wireframe.global = !wireframe.global;
+  }
+}
+
+/// Chunk a world position lies in.
+fn chunk_position(translation: Vec3) -> IVec3 {
+  (translation / CHUNK_SIZE as f32).floor().as_ivec3()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_stats_text(
+  overlay: Res<DebugOverlay>,
+  world: Res<VoxelWorld>,
+  queue: Res<ChunkStreamingQueue>,
+  diagnostics: Res<DiagnosticsStore>,
+  viewers: Query<(&GlobalTransform, Option<&Camera>), With<ChunkViewer>>,
+  chunks: Query<&ChunkQuadCount>,
+  indirect_chunks: Query<&IndirectChunkMesh>,
+  mut text: Query<(&mut Text, &mut Visibility), With<DebugStatsText>>,
+) {
+  let Ok((mut text, mut visibility)) = text.single_mut() else {
+    return;
+  };
+  visibility.set_if_neq(if overlay.stats {
+    Visibility::Inherited
+  } else {
+    Visibility::Hidden
+  });
+  if !overlay.stats {
+    return;
+  }
+
+  let frame_time = diagnostics
+    .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+    .and_then(|frame_time| frame_time.smoothed())
+    .unwrap_or_default();
+  let quads = chunks.iter().map(|count| count.0).sum::<usize>()
+    + indirect_chunks
+      .iter()
+      .map(|mesh| mesh.quads.len())
+      .sum::<usize>();
+
+  let mut stats = format!("{frame_time:.2} ms/frame\n");
+  let mut viewers: Vec<_> = viewers.iter().collect();
+  viewers.sort_by_key(|(_, camera)| camera.map(|camera| camera.order));
+  for (transform, _) in viewers {
+    let translation = transform.translation();
+    let _ = writeln!(
+      stats,
+      "position {:.1} {:.1} {:.1}, chunk {}",
+      translation.x,
+      translation.y,
+      translation.z,
+      chunk_position(translation)
+    );
+  }
+  let _ = write!(
+    stats,
+    "chunks: {} loaded, {} meshing, {} queued\nquads: {quads}",
+    world.chunk_positions().count(),
+    world.changed_count(),
+    queue.queued
+  );
+
+  // rolling percentiles of the voxel pipeline, if its diagnostics are registered
+  for (label, path) in [
+    ("generation ms", VoxelDiagnosticsPlugin::GENERATION_TIME),
+    ("meshing ms", VoxelDiagnosticsPlugin::MESHING_TIME),
+    ("quads/chunk", VoxelDiagnosticsPlugin::QUADS_PER_CHUNK),
+    ("uploaded bytes", VoxelDiagnosticsPlugin::UPLOADED_BYTES),
+  ] {
+    let Some(diagnostic) = diagnostics.get(&path) else {
+      continue;
+    };
+    let [p50, p95, p99] = [50.0, 95.0, 99.0].map(|percent| percentile(diagnostic, percent));
+    if let (Some(p50), Some(p95), Some(p99)) = (p50, p95, p99) {
+      let _ = write!(stats, "\n{label}: p50 {p50:.2}, p95 {p95:.2}, p99 {p99:.2}");
+    }
+  }
+  text.0 = stats;
+}
+
+/// Draws the borders of loaded chunks close to a viewer, the chunk each viewer is
+/// in is highlighted.
+fn draw_chunk_borders(
+  overlay: Res<DebugOverlay>,
+  world: Res<VoxelWorld>,
+  viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+  mut gizmos: Gizmos,
+) {
+  if !overlay.chunk_borders {
+    return;
+  }
+
+  let centers: Vec<IVec3> = viewers
+    .iter()
+    .map(|transform| chunk_position(transform.translation()))
+    .collect();
+  for chunk_pos in world.chunk_positions() {
+    let Some(distance) = centers
+      .iter()
+      .map(|center| (chunk_pos - center).xz().abs().max_element())
+      .min()
+    else {
+      continue;
+    };
+    if distance > CHUNK_BORDER_RADIUS {
+      continue;
+    }
+
+    let color = if centers.contains(&chunk_pos) {
+      YELLOW
+    } else {
+      WHITE.with_alpha(0.4)
+    };
+    let size = CHUNK_SIZE as f32;
+    gizmos.cuboid(
+      Transform::from_translation((chunk_pos.as_vec3() + 0.5) * size).with_scale(Vec3::splat(size)),
+      color,
+    );
+  }
+}
@@ -0,0 +1,114 @@
+use std::sync::{
+  Arc,
+  atomic::{AtomicU64, Ordering},
+};
+
+use bevy::{
+  diagnostic::{
+    Diagnostic, DiagnosticMeasurement, DiagnosticPath, Diagnostics, DiagnosticsStore,
+    RegisterDiagnostic,
+  },
+  platform::time::Instant,
+  prelude::*,
+  render::RenderApp,
+};
+
+/// Samples kept per diagnostic for averages and percentiles.
+const HISTORY_LENGTH: usize = 240;
+
+/// Counts bytes written to instance and chunk buffers by the render world, shared
+/// with the main world through an [`Arc`].
+#[derive(Resource, Clone, Default)]
+pub struct InstanceUploads(Arc<AtomicU64>);
+
+impl InstanceUploads {
+  pub fn add(&self, bytes: u64) {
+    self.0.fetch_add(bytes, Ordering::Relaxed);
+  }
+}
+
+/// Registers [`Diagnostic`]s for each stage of the voxel pipeline, see the
+/// associated constants. They can be logged with
+/// [`LogDiagnosticsPlugin`](bevy::diagnostic::LogDiagnosticsPlugin) and are shown
+/// in the [`DebugOverlayPlugin`](super::DebugOverlayPlugin).
+pub struct VoxelDiagnosticsPlugin;
+
+impl VoxelDiagnosticsPlugin {
+  /// Time to generate the blocks of a chunk, in milliseconds, one sample per chunk.
+  pub const GENERATION_TIME: DiagnosticPath = DiagnosticPath::const_new("voxel/generation_time");
+  /// Time to mesh a chunk, in milliseconds, one sample per chunk.
+  pub const MESHING_TIME: DiagnosticPath = DiagnosticPath::const_new("voxel/meshing_time");
+  /// Opaque and translucent quads of a meshed chunk, one sample per chunk.
+  pub const QUADS_PER_CHUNK: DiagnosticPath = DiagnosticPath::const_new("voxel/quads_per_chunk");
+  /// Bytes written to instance and chunk buffers, one sample per frame.
+  pub const UPLOADED_BYTES: DiagnosticPath = DiagnosticPath::const_new("voxel/uploaded_bytes");
+  /// Chunks in range of a viewer that aren't generated yet, one sample per frame.
+  pub const CHUNKS_QUEUED: DiagnosticPath = DiagnosticPath::const_new("voxel/chunks_queued");
+
+  /// Every diagnostic of this plugin, to filter logged diagnostics by.
+  pub const ALL: [DiagnosticPath; 5] = [
+    Self::GENERATION_TIME,
+    Self::MESHING_TIME,
+    Self::QUADS_PER_CHUNK,
+    Self::UPLOADED_BYTES,
+    Self::CHUNKS_QUEUED,
+  ];
+}
+
+impl Plugin for VoxelDiagnosticsPlugin {
+  fn build(&self, app: &mut App) {
+    let diagnostic = |path, suffix| {
+      Diagnostic::new(path)
+        .with_suffix(suffix)
+        .with_max_history_length(HISTORY_LENGTH)
+    };
+
+    let uploads = InstanceUploads::default();
+    app
+      .register_diagnostic(diagnostic(Self::GENERATION_TIME, "ms"))
+      .register_diagnostic(diagnostic(Self::MESHING_TIME, "ms"))
+      .register_diagnostic(diagnostic(Self::QUADS_PER_CHUNK, ""))
+      .register_diagnostic(diagnostic(Self::UPLOADED_BYTES, "B"))
+      .register_diagnostic(diagnostic(Self::CHUNKS_QUEUED, ""))
+      .insert_resource(uploads.clone())
+      .add_systems(Last, record_instance_uploads);
+    app.sub_app_mut(RenderApp).insert_resource(uploads);
+  }
+}
+
+fn record_instance_uploads(uploads: Res<InstanceUploads>, mut diagnostics: Diagnostics) {
+  let bytes = uploads.0.swap(0, Ordering::Relaxed);
+  diagnostics.add_measurement(&VoxelDiagnosticsPlugin::UPLOADED_BYTES, || bytes as f64);
+}
+
+/// Adds a measurement if the diagnostic is registered and enabled. Unlike
+/// [`Diagnostics`] this keeps every measurement taken in a frame.
+pub(super) fn add_measurement(
+  store: &mut Option<ResMut<DiagnosticsStore>>,
+  path: &DiagnosticPath,
+  value: f64,
+) {
+  if let Some(diagnostic) = store.as_mut().and_then(|store| store.get_mut(path))
+    && diagnostic.is_enabled
+  {
+    diagnostic.add_measurement(DiagnosticMeasurement {
+      time: Instant::now(),
+      value,
+    });
+  }
+}
+
+/// Value below which `percent` of the recorded history falls, `None` without history.
+pub fn percentile(diagnostic: &Diagnostic, percent: f64) -> Option<f64> {
+  let mut values: Vec<f64> = diagnostic
+    .values()
+    .copied()
+    .filter(|value| value.is_finite())
+    .collect();
+  if values.is_empty() {
+    return None;
+  }
+  values.sort_unstable_by(f64::total_cmp);
+  let index = ((values.len() - 1) as f64 * percent / 100.0).round() as usize;
+  Some(values[index])
+}
@@ -1,18 +1,142 @@
-use crate::voxel::chunk::{material::ChunkMaterial, mesh::ChunkMeshData};
-use bevy::prelude::*;
+use crate::voxel::chunk::{
+  CHUNK_SIZE,
+  fog::ChunkFog,
+  greedy::greedy_mesh,
+  indirect::IndirectChunkMesh,
+  liquid::LiquidMaterials,
+  material::{ChunkMaterial, ChunkMeshLayout, InstanceData, InstanceMaterialData},
+  mesh::{ChunkMeshData, chunk_translation, liquid_mesh, quads_mesh},
+  props::{PropKind, PropMeshes},
+  translucent::TranslucentChunk,
+  world::{ChunkCoord, ChunkQuadCount},
+};
+use bevy::{
+  camera::primitives::Aabb,
+  ecs::spawn::SpawnIter,
+  prelude::*,
+  render::{batching::NoAutomaticBatching, storage::ShaderStorageBuffer},
+};
 
 impl ChunkMeshData {
+  /// Creates the chunk entity with its opaque mesh, built from the
+  /// [`GreedyQuads`](super::greedy::GreedyQuads) if there are any, and, if the
+  /// chunk contains any translucent faces, a child entity with a separate alpha
+  /// blended mesh. Faces of each liquid are spawned as another child using that
+  /// liquid's material, and every kind of prop as an instanced child. Chunk
+  /// materials get `fog`.
   pub fn create_entity(
     self,
     materials: &mut Assets<ChunkMaterial>,
     meshes: &mut Assets<Mesh>,
-  ) -> (Mesh3d, MeshMaterial3d<ChunkMaterial>, Transform) {
-    let material = materials.add(ChunkMaterial {});
+    buffers: &mut Assets<ShaderStorageBuffer>,
+    liquid_materials: &LiquidMaterials,
+    prop_meshes: &PropMeshes,
+    fog: ChunkFog,
+  ) -> impl Bundle {
+    let alpha_mode = if self.has_cutout() {
+      AlphaMode::Mask(0.5)
+    } else {
+      AlphaMode::Opaque
+    };
+    let quad_count = ChunkQuadCount(self.quad_count());
+    let (mesh, material) = match self.greedy_quads {
+      Some(greedy) => (
+        greedy_mesh(&greedy.quads),
+        ChunkMaterial {
+          alpha_mode,
+          layout: ChunkMeshLayout::GreedyQuads,
+          quad_attributes: buffers.add(ShaderStorageBuffer::from(greedy.attributes)),
+          fog,
+          ..default()
+        },
+      ),
+      None => (
+        quads_mesh(&self.quads),
+        ChunkMaterial {
+          alpha_mode,
+          fog,
+          ..default()
+        },
+      ),
+    };
 
+    let translucent = (!self.translucent_quads.is_empty()).then(|| {
+      (
+        Mesh3d(meshes.add(quads_mesh(&self.translucent_quads))),
+        MeshMaterial3d(materials.add(ChunkMaterial {
+          alpha_mode: AlphaMode::Blend,
+          fog,
+          ..default()
+        })),
+        TranslucentChunk::new(self.translucent_quads),
+      )
+    });
+
+    let mut liquid_blocks: Vec<_> = self.liquid_faces.iter().map(|face| face.block).collect();
+    liquid_blocks.sort_unstable();
+    liquid_blocks.dedup();
+    let liquids: Vec<_> = liquid_blocks
+      .into_iter()
+      .filter_map(|block| {
+        let material = liquid_materials.get(block)?;
+        let faces: Vec<_> = self
+          .liquid_faces
+          .iter()
+          .filter(|face| face.block == block)
+          .copied()
+          .collect();
+        Some((
+          Mesh3d(meshes.add(liquid_mesh(&faces))),
+          MeshMaterial3d(material),
+        ))
+      })
+      .collect();
+
+    // the prop mesh only covers a single block, so the bounds span the whole chunk
+    let chunk_bounds = Aabb::from_min_max(Vec3::ZERO, Vec3::splat((CHUNK_SIZE + 2) as f32));
+    let props: Vec<_> = PropKind::ALL
+      .into_iter()
+      .filter_map(|kind| {
+        let instances: Vec<_> = self
+          .props
+          .iter()
+          .filter(|prop| prop.kind == kind)
+          .map(|prop| InstanceData { data: prop.data })
+          .collect();
+        (!instances.is_empty()).then(|| {
+          (
+            Mesh3d(prop_meshes.get(kind)),
+            InstanceMaterialData(instances),
+            chunk_bounds,
+            NoAutomaticBatching,
+          )
+        })
+      })
+      .collect();
+
+    (
+      Mesh3d(meshes.add(mesh)),
+      MeshMaterial3d(materials.add(material)),
+      ChunkCoord(self.chunk_pos),
+      quad_count,
+      Transform::from_translation(chunk_translation(self.chunk_pos)),
+      Children::spawn((SpawnIter(translucent.into_iter()), liquids, props)),
+    )
+  }
+
+  /// Creates the chunk entity for drawing from the shared buffers of the
+  /// [`ChunkIndirectPlugin`](crate::voxel::chunk::ChunkIndirectPlugin).
+  ///
+  /// Only the opaque and cutout quads are drawn this way, translucent faces,
+  /// liquids and props are dropped.
+  pub fn create_indirect_entity(self) -> impl Bundle {
     (
-      Mesh3d(meshes.add(self.mesh)),
-      MeshMaterial3d(material),
-      Transform::from_translation(self.chunk_pos.as_vec3() * super::CHUNK_SIZE as f32),
+      ChunkCoord(self.chunk_pos),
+      ChunkQuadCount(self.quads.len()),
+      IndirectChunkMesh {
+        quads: self.quads.into(),
+        chunk_pos: self.chunk_pos,
+      },
     )
   }
 }
@@ -0,0 +1,381 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::{self, BufWriter, Write},
+  path::Path,
+};
+
+use bevy::prelude::*;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::voxel::chunk::{
+  block,
+  mesh::{ChunkMeshData, chunk_translation, expand_quads, face_normal, unpack_block, unpack_quad},
+};
+
+/// Triangles of a single block type in world space.
+#[derive(Clone, Debug, Default)]
+pub struct ExportPrimitive {
+  pub block: u8,
+  pub positions: Vec<Vec3>,
+  pub normals: Vec<Vec3>,
+  /// Texture coordinates in blocks, so a texture repeats once per block.
+  pub uvs: Vec<Vec2>,
+  pub indices: Vec<u32>,
+}
+
+/// Chunk meshes decoded into plain triangles, one primitive per block type.
+///
+/// Liquid surfaces are animated in their shader and are not exported.
+#[derive(Clone, Debug, Default)]
+pub struct ExportMesh {
+  pub primitives: Vec<ExportPrimitive>,
+}
+
+impl ExportMesh {
+  /// Decodes the packed opaque and translucent quads of every chunk the same way
+  /// `unpack` in `chunk_util.wgsl` does.
+  pub fn from_chunks<'a>(meshes: impl IntoIterator<Item = &'a ChunkMeshData>) -> Self {
+    let mut primitives: BTreeMap<u8, ExportPrimitive> = BTreeMap::new();
+
+    for mesh in meshes {
+      let offset = chunk_translation(mesh.chunk_pos);
+      for quads in [&mesh.quads, &mesh.translucent_quads] {
+        let (vertices, indices) = expand_quads(quads);
+
+        // every quad has four vertices and six indices
+        for (quad_vertices, quad_indices) in vertices.chunks_exact(4).zip(indices.chunks_exact(6)) {
+          let block = unpack_block(quad_vertices[0]);
+          let primitive = primitives
+            .entry(block)
+            .or_insert_with(|| ExportPrimitive { block, ..default() });
+
+          let start = primitive.positions.len() as u32;
+          let first = quad_indices.iter().min().copied().unwrap_or_default();
+          primitive
+            .indices
+            .extend(quad_indices.iter().map(|index| index - first + start));
+
+          for (corner, &vertex) in quad_vertices.iter().enumerate() {
+            let (position, width, height, dir) = unpack_quad(vertex);
+            let uv = match corner {
+              0 => Vec2::ZERO,
+              1 => Vec2::new(width as f32, 0.0),
+              2 => Vec2::new(width as f32, height as f32),
+              _ => Vec2::new(0.0, height as f32),
+            };
+            primitive.positions.push(position.as_vec3() + offset);
+            primitive.normals.push(face_normal(dir));
+            primitive.uvs.push(uv);
+          }
+        }
+      }
+    }
+
+    Self {
+      primitives: primitives.into_values().collect(),
+    }
+  }
+
+  pub fn vertex_count(&self) -> usize {
+    self.primitives.iter().map(|p| p.positions.len()).sum()
+  }
+
+  pub fn index_count(&self) -> usize {
+    self.primitives.iter().map(|p| p.indices.len()).sum()
+  }
+
+  /// Smallest box containing every vertex, `None` without vertices.
+  pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+    bounds(self.primitives.iter().flat_map(|p| p.positions.iter()))
+  }
+
+  /// Writes a Wavefront OBJ file and a material library next to it with the same name.
+  pub fn write_obj(&self, path: impl AsRef<Path>) -> Result<(), ExportError> {
+    let path = path.as_ref();
+    let mtl_path = path.with_extension("mtl");
+
+    let mut mtl = BufWriter::new(fs::File::create(&mtl_path)?);
+    for primitive in &self.primitives {
+      let color = block::base_color(primitive.block);
+      writeln!(mtl, "newmtl {}", block::name(primitive.block))?;
+      writeln!(mtl, "Kd {} {} {}", color.red, color.green, color.blue)?;
+      writeln!(mtl, "d {}", color.alpha)?;
+    }
+    mtl.flush()?;
+
+    let mut obj = BufWriter::new(fs::File::create(path)?);
+    if let Some(name) = mtl_path.file_name() {
+      writeln!(obj, "mtllib {}", name.to_string_lossy())?;
+    }
+
+    // OBJ indices are one-based and shared between all groups
+    let mut start = 1;
+    for primitive in &self.primitives {
+      writeln!(obj, "o {}", block::name(primitive.block))?;
+      writeln!(obj, "usemtl {}", block::name(primitive.block))?;
+      for position in &primitive.positions {
+        writeln!(obj, "v {} {} {}", position.x, position.y, position.z)?;
+      }
+      for uv in &primitive.uvs {
+        writeln!(obj, "vt {} {}", uv.x, uv.y)?;
+      }
+      for normal in &primitive.normals {
+        writeln!(obj, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+      }
+      for triangle in primitive.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + start);
+        writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+      }
+      start += primitive.positions.len() as u32;
+    }
+    obj.flush()?;
+    Ok(())
+  }
+
+  /// Writes a binary glTF file with one material per block type. Fails without
+  /// any triangles, as a glTF mesh needs at least one primitive.
+  pub fn write_glb(&self, path: impl AsRef<Path>) -> Result<(), ExportError> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    if self.primitives.is_empty() {
+      return Err(ExportError::Empty);
+    }
+
+    let mut bin: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut materials = Vec::new();
+    let mut primitives = Vec::new();
+
+    // appends a buffer view with its accessor and returns the accessor index
+    let mut push = |bytes: &[u8], target: u32, accessor: serde_json::Value| {
+      buffer_views.push(json!({
+        "buffer": 0,
+        "byteOffset": bin.len(),
+        "byteLength": bytes.len(),
+        "target": target,
+      }));
+      bin.extend_from_slice(bytes);
+      let mut accessor = accessor;
+      accessor["bufferView"] = json!(buffer_views.len() - 1);
+      accessors.push(accessor);
+      accessors.len() - 1
+    };
+
+    for primitive in &self.primitives {
+      let count = primitive.positions.len();
+      let (min, max) = bounds(primitive.positions.iter()).unwrap_or_default();
+
+      let position = push(
+        bytemuck::cast_slice(&primitive.positions),
+        ARRAY_BUFFER,
+        json!({
+          "componentType": FLOAT,
+          "count": count,
+          "type": "VEC3",
+          "min": min.to_array(),
+          "max": max.to_array(),
+        }),
+      );
+      let normal = push(
+        bytemuck::cast_slice(&primitive.normals),
+        ARRAY_BUFFER,
+        json!({ "componentType": FLOAT, "count": count, "type": "VEC3" }),
+      );
+      let uv = push(
+        bytemuck::cast_slice(&primitive.uvs),
+        ARRAY_BUFFER,
+        json!({ "componentType": FLOAT, "count": count, "type": "VEC2" }),
+      );
+      let indices = push(
+        bytemuck::cast_slice(&primitive.indices),
+        ELEMENT_ARRAY_BUFFER,
+        json!({
+          "componentType": UNSIGNED_INT,
+          "count": primitive.indices.len(),
+          "type": "SCALAR",
+        }),
+      );
+
+      let color = block::base_color(primitive.block);
+      materials.push(json!({
+        "name": block::name(primitive.block),
+        "pbrMetallicRoughness": {
+          "baseColorFactor": color.to_f32_array(),
+          "metallicFactor": 0.0,
+          "roughnessFactor": 1.0,
+        },
+        "alphaMode": if color.alpha < 1.0 { "BLEND" } else { "OPAQUE" },
+      }));
+      primitives.push(json!({
+        "attributes": { "POSITION": position, "NORMAL": normal, "TEXCOORD_0": uv },
+        "indices": indices,
+        "material": materials.len() - 1,
+      }));
+    }
+
+    let document = json!({
+      "asset": { "version": "2.0", "generator": env!("CARGO_PKG_NAME") },
+      "scene": 0,
+      "scenes": [{ "nodes": [0] }],
+      "nodes": [{ "name": "voxels", "mesh": 0 }],
+      "meshes": [{ "name": "voxels", "primitives": primitives }],
+      "materials": materials,
+      "accessors": accessors,
+      "bufferViews": buffer_views,
+      "buffers": [{ "byteLength": bin.len() }],
+    });
+
+    // both chunks are padded to four bytes, JSON with spaces and binary data with zeros
+    let mut json = serde_json::to_vec(&document)?;
+    json.resize(json.len().next_multiple_of(4), b' ');
+    bin.resize(bin.len().next_multiple_of(4), 0);
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    file.write_all(b"glTF")?;
+    file.write_all(&2u32.to_le_bytes())?;
+    file.write_all(&(length as u32).to_le_bytes())?;
+    file.write_all(&(json.len() as u32).to_le_bytes())?;
+    file.write_all(b"JSON")?;
+    file.write_all(&json)?;
+    file.write_all(&(bin.len() as u32).to_le_bytes())?;
+    file.write_all(b"BIN\0")?;
+    file.write_all(&bin)?;
+    file.flush()?;
+    Ok(())
+  }
+}
+
+fn bounds<'a>(positions: impl Iterator<Item = &'a Vec3>) -> Option<(Vec3, Vec3)> {
+  positions.fold(None, |bounds, &position| match bounds {
+    None => Some((position, position)),
+    Some((min, max)) => Some((min.min(position), max.max(position))),
+  })
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+  #[error("could not write export: {0}")]
+  Io(#[from] io::Error),
+  #[error("could not serialize glTF document: {0}")]
+  Json(#[from] serde_json::Error),
+  #[error("nothing to export, the region has no visible faces")]
+  Empty,
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::Value;
+
+  use super::*;
+  use crate::voxel::chunk::{CHUNK_SIZE, generation::ChunkBlockData, world::local_position};
+
+  /// Two grass blocks next to each other with glass on top of the first, in the
+  /// chunk at `chunk_pos`.
+  fn small_chunk(chunk_pos: IVec3) -> ChunkMeshData {
+    let origin = chunk_pos * CHUNK_SIZE as i32;
+    let mut chunk = ChunkBlockData::flat(chunk_pos, i32::MIN);
+    for (pos, block) in [
+      (IVec3::ZERO, block::GRASS),
+      (IVec3::X, block::GRASS),
+      (IVec3::Y, block::GLASS),
+    ] {
+      chunk.set(local_position(origin + pos, chunk_pos).unwrap(), block);
+    }
+    chunk.create_mesh()
+  }
+
+  /// The JSON document and the binary chunk of a `.glb` file.
+  fn read_glb(path: &Path) -> (Value, Vec<u8>) {
+    let bytes = fs::read(path).unwrap();
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    assert_eq!(&bytes[0..4], b"glTF");
+    assert_eq!(u32_at(8) as usize, bytes.len());
+
+    let json_len = u32_at(12) as usize;
+    assert_eq!(&bytes[16..20], b"JSON");
+    let document = serde_json::from_slice(&bytes[20..20 + json_len]).unwrap();
+    let bin_start = 20 + json_len;
+    let bin_len = u32_at(bin_start) as usize;
+    assert_eq!(&bytes[bin_start + 4..bin_start + 8], b"BIN\0");
+    (
+      document,
+      bytes[bin_start + 8..bin_start + 8 + bin_len].to_vec(),
+    )
+  }
+
+  fn vec3(value: &Value) -> Vec3 {
+    let values: Vec<f32> = value
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|value| value.as_f64().unwrap() as f32)
+      .collect();
+    Vec3::from_slice(&values)
+  }
+
+  #[test]
+  fn glb_round_trip_matches_the_blocks() {
+    let chunk_pos = IVec3::new(1, 0, -1);
+    // vertices line up with the blocks of the world
+    let origin = (chunk_pos * CHUNK_SIZE as i32).as_vec3();
+    let export = ExportMesh::from_chunks([&small_chunk(chunk_pos)]);
+    // the face between the grass blocks and the bottom of the glass are hidden
+    assert_eq!(export.vertex_count(), (10 + 5) * 4);
+    assert_eq!(export.index_count(), (10 + 5) * 6);
+    assert_eq!(
+      export.bounds(),
+      Some((origin, origin + Vec3::new(2.0, 2.0, 1.0)))
+    );
+
+    let path = std::env::temp_dir().join(format!("voxel_export_{}.glb", std::process::id()));
+    export.write_glb(&path).unwrap();
+    let (document, bin) = read_glb(&path);
+    fs::remove_file(&path).unwrap();
+
+    // primitives are ordered by block id
+    let expected = [
+      (block::GRASS, 10, Vec3::ZERO, Vec3::new(2.0, 1.0, 1.0)),
+      (block::GLASS, 5, Vec3::Y, Vec3::new(1.0, 2.0, 1.0)),
+    ];
+    let primitives = document["meshes"][0]["primitives"].as_array().unwrap();
+    assert_eq!(primitives.len(), expected.len());
+    for (primitive, (block, quads, min, max)) in primitives.iter().zip(expected) {
+      let material = &document["materials"][primitive["material"].as_u64().unwrap() as usize];
+      assert_eq!(material["name"], block::name(block));
+
+      let position =
+        &document["accessors"][primitive["attributes"]["POSITION"].as_u64().unwrap() as usize];
+      assert_eq!(position["count"], quads * 4);
+      assert_eq!(vec3(&position["min"]), origin + min);
+      assert_eq!(vec3(&position["max"]), origin + max);
+
+      // the accessor bounds match the positions in the binary chunk
+      let view = &document["bufferViews"][position["bufferView"].as_u64().unwrap() as usize];
+      let offset = view["byteOffset"].as_u64().unwrap() as usize;
+      let length = view["byteLength"].as_u64().unwrap() as usize;
+      let positions: &[Vec3] = bytemuck::cast_slice(&bin[offset..offset + length]);
+      assert_eq!(bounds(positions.iter()), Some((origin + min, origin + max)));
+
+      let indices = &document["accessors"][primitive["indices"].as_u64().unwrap() as usize];
+      assert_eq!(indices["count"], quads * 6);
+    }
+  }
+
+  #[test]
+  fn glb_export_of_an_empty_region_fails() {
+    let empty = ChunkBlockData::flat(IVec3::ZERO, i32::MIN).create_mesh();
+    let export = ExportMesh::from_chunks([&empty]);
+    assert_eq!(export.vertex_count(), 0);
+
+    let path = std::env::temp_dir().join(format!("voxel_empty_{}.glb", std::process::id()));
+    assert!(matches!(export.write_glb(&path), Err(ExportError::Empty)));
+    assert!(!path.exists());
+  }
+}
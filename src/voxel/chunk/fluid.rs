@@ -0,0 +1,345 @@
+use std::time::Duration;
+
+use bevy::{platform::collections::HashSet, prelude::*, time::common_conditions::on_timer};
+
+use crate::voxel::chunk::{
+  block::{self, BlockKind},
+  world::VoxelWorld,
+};
+
+/// Time between two steps of the fluid simulation.
+const TICK_INTERVAL: Duration = Duration::from_millis(250);
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// Number of ticks between two updates of a liquid.
+#[inline]
+fn flow_interval(liquid: u8) -> u64 {
+  match liquid {
+    block::LAVA => 4,
+    _ => 1,
+  }
+}
+
+/// Level increase per block a liquid flows sideways.
+#[inline]
+fn flow_step(liquid: u8) -> u8 {
+  match liquid {
+    block::LAVA => 2,
+    _ => 1,
+  }
+}
+
+/// Cellular automaton moving liquid blocks of the [`VoxelWorld`].
+///
+/// Sources never change. Liquid above air falls as a column of falling blocks,
+/// liquid resting on something else spreads sideways, one level per block, until
+/// [`MAX_LIQUID_LEVEL`](block::MAX_LIQUID_LEVEL) is reached. Flowing blocks that
+/// lose their supply drain away.
+///
+/// Only blocks next to a change are evaluated. Each step computes all new blocks
+/// from the previous state before applying them, so the result doesn't depend on
+/// iteration order.
+///
+/// Blocks edited through the [`VoxelWorld`] and flowing liquid in newly loaded
+/// chunks are picked up at the start of the next step.
+#[derive(Resource, Default)]
+pub struct FluidSimulation {
+  active: HashSet<IVec3>,
+  tick: u64,
+}
+
+impl FluidSimulation {
+  /// Schedules a block and its neighbors for the next step.
+  fn wake(&mut self, pos: IVec3) {
+    self.active.insert(pos);
+    self.active.insert(pos + IVec3::Y);
+    self.active.insert(pos - IVec3::Y);
+    for dir in HORIZONTAL {
+      self.active.insert(pos + dir);
+    }
+  }
+
+  /// Advances the simulation by one tick.
+  pub fn step(&mut self, world: &mut VoxelWorld) {
+    for pos in world.take_fluid_wakes() {
+      self.wake(pos);
+    }
+    let active = std::mem::take(&mut self.active);
+
+    let mut updates = Vec::new();
+    for pos in active {
+      let Some(current) = world.get_block(pos) else {
+        continue;
+      };
+      let next = next_block(world, pos, current);
+      if next == current {
+        continue;
+      }
+
+      let liquid = if block::block_kind(next) == BlockKind::Liquid {
+        block::id(next)
+      } else {
+        block::id(current)
+      };
+      if self.tick.is_multiple_of(flow_interval(liquid)) {
+        updates.push((pos, next));
+      } else {
+        // not this liquid's turn, try again next tick
+        self.active.insert(pos);
+      }
+    }
+
+    // the changed blocks wake their neighbors in the next step
+    for (pos, block) in updates {
+      world.set_block(pos, block);
+    }
+    self.tick += 1;
+  }
+}
+
+/// New state of the block at `pos` derived from its neighbors.
+fn next_block(world: &VoxelWorld, pos: IVec3, current: u8) -> u8 {
+  let kind = block::block_kind(current);
+  match kind {
+    BlockKind::Air => {}
+    BlockKind::Liquid if is_flowing(current) => {}
+    // solid blocks and sources
+    _ => return current,
+  }
+  // flowing liquid is only fed by the same liquid
+  let accepts = |block: u8| {
+    block::block_kind(block) == BlockKind::Liquid
+      && (kind == BlockKind::Air || block::id(block) == block::id(current))
+  };
+
+  if let Some(above) = world.get_block(pos + IVec3::Y)
+    && accepts(above)
+  {
+    return block::liquid(block::id(above), 0, true);
+  }
+
+  let mut best: Option<(u8, u8)> = None;
+  for dir in HORIZONTAL {
+    let Some(neighbor) = world.get_block(pos + dir) else {
+      continue;
+    };
+    if !accepts(neighbor) {
+      continue;
+    }
+    // liquid that can still flow down doesn't spread sideways
+    if world
+      .get_block(pos + dir - IVec3::Y)
+      .is_some_and(|below| !supports(neighbor, below))
+    {
+      continue;
+    }
+
+    let source_level = if block::liquid_falling(neighbor) {
+      0
+    } else {
+      block::liquid_level(neighbor)
+    };
+    let liquid = block::id(neighbor);
+    let level = source_level + flow_step(liquid);
+    if level <= block::MAX_LIQUID_LEVEL && best.is_none_or(|(best_level, _)| level < best_level) {
+      best = Some((level, liquid));
+    }
+  }
+
+  match best {
+    Some((level, liquid)) => block::liquid(liquid, level, false),
+    None => block::AIR,
+  }
+}
+
+/// Whether `liquid` rests on `below` and spreads sideways.
+#[inline]
+fn supports(liquid: u8, below: u8) -> bool {
+  match block::block_kind(below) {
+    BlockKind::Air => false,
+    BlockKind::Liquid if block::id(below) == block::id(liquid) => !is_flowing(below),
+    _ => true,
+  }
+}
+
+/// Whether a block is liquid that isn't a source.
+#[inline]
+pub(super) fn is_flowing(block: u8) -> bool {
+  block::block_kind(block) == BlockKind::Liquid
+    && (block::liquid_level(block) != 0 || block::liquid_falling(block))
+}
+
+/// Steps the [`FluidSimulation`] once every [`TICK_INTERVAL`].
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<FluidSimulation>()
+      .add_systems(Update, tick_fluids.run_if(on_timer(TICK_INTERVAL)));
+  }
+}
+
+fn tick_fluids(mut simulation: ResMut<FluidSimulation>, mut world: ResMut<VoxelWorld>) {
+  simulation.step(&mut world);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::voxel::chunk::{CHUNK_SIZE, generation::ChunkBlockData, world::local_position};
+
+  /// Two chunks next to each other along +X, with ground at height 0 and air above.
+  fn two_chunks() -> VoxelWorld {
+    let mut world = VoxelWorld::default();
+    world.insert(ChunkBlockData::flat(IVec3::ZERO, 0));
+    world.insert(ChunkBlockData::flat(IVec3::X, 0));
+    world
+  }
+
+  fn run(simulation: &mut FluidSimulation, world: &mut VoxelWorld, ticks: usize) {
+    for _ in 0..ticks {
+      simulation.step(world);
+    }
+  }
+
+  #[test]
+  fn water_spreads_one_level_per_block() {
+    let mut world = two_chunks();
+    let mut simulation = FluidSimulation::default();
+    let source = IVec3::new(8, 1, 8);
+    world.set_block(source, block::WATER);
+
+    run(&mut simulation, &mut world, 10);
+
+    assert_eq!(world.get_block(source), Some(block::WATER));
+    for distance in 1..=block::MAX_LIQUID_LEVEL as i32 {
+      let flowing = block::liquid(block::WATER, distance as u8, false);
+      assert_eq!(world.get_block(source + IVec3::X * distance), Some(flowing));
+      assert_eq!(
+        world.get_block(source + IVec3::NEG_Z * distance),
+        Some(flowing)
+      );
+    }
+    assert_eq!(
+      world.get_block(source + IVec3::new(3, 0, 2)),
+      Some(block::liquid(block::WATER, 5, false))
+    );
+    // no further than the thinnest level
+    assert_eq!(world.get_block(source + IVec3::X * 8), Some(block::AIR));
+    assert_eq!(world.get_block(source + IVec3::Y), Some(block::AIR));
+  }
+
+  #[test]
+  fn water_falls_before_spreading() {
+    let mut world = two_chunks();
+    let mut simulation = FluidSimulation::default();
+    let source = IVec3::new(8, 4, 8);
+    world.set_block(source, block::WATER);
+
+    // one block per tick down to the ground
+    run(&mut simulation, &mut world, 3);
+    let falling = block::liquid(block::WATER, 0, true);
+    for y in 1..4 {
+      assert_eq!(world.get_block(IVec3::new(8, y, 8)), Some(falling));
+    }
+    assert_eq!(world.get_block(source + IVec3::X), Some(block::AIR));
+    assert_eq!(world.get_block(IVec3::new(9, 1, 8)), Some(block::AIR));
+
+    // and only sideways on the ground, fed like a source
+    run(&mut simulation, &mut world, 1);
+    assert_eq!(
+      world.get_block(IVec3::new(9, 1, 8)),
+      Some(block::liquid(block::WATER, 1, false))
+    );
+    assert_eq!(world.get_block(IVec3::new(9, 2, 8)), Some(block::AIR));
+    assert!(block::liquid_falling(
+      world.get_block(IVec3::new(8, 1, 8)).unwrap()
+    ));
+  }
+
+  #[test]
+  fn water_flows_across_chunk_borders() {
+    let mut world = two_chunks();
+    let mut simulation = FluidSimulation::default();
+    let source = IVec3::new(CHUNK_SIZE as i32 - 2, 1, 8);
+    world.set_block(source, block::WATER);
+
+    run(&mut simulation, &mut world, 4);
+
+    // the first blocks of the next chunk
+    for distance in 2..=4 {
+      let pos = source + IVec3::X * distance;
+      let flowing = block::liquid(block::WATER, distance as u8, false);
+      assert_eq!(world.get_block(pos), Some(flowing));
+    }
+    assert_eq!(world.get_block(source + IVec3::X * 5), Some(block::AIR));
+
+    // both chunks see the same blocks along their border
+    for x in [CHUNK_SIZE as i32 - 1, CHUNK_SIZE as i32] {
+      let pos = IVec3::new(x, 1, 8);
+      for chunk_pos in [IVec3::ZERO, IVec3::X] {
+        let chunk = world.chunk(chunk_pos).unwrap();
+        let local = local_position(pos, chunk_pos).unwrap();
+        assert_eq!(Some(chunk.get(local)), world.get_block(pos));
+      }
+    }
+  }
+
+  #[test]
+  fn flowing_water_drains_without_a_source() {
+    let mut world = two_chunks();
+    let mut simulation = FluidSimulation::default();
+    let source = IVec3::new(8, 1, 8);
+    world.set_block(source, block::WATER);
+    run(&mut simulation, &mut world, 10);
+
+    world.set_block(source, block::AIR);
+    run(&mut simulation, &mut world, 20);
+
+    for x in 8..=15 {
+      assert_eq!(world.get_block(IVec3::new(x, 1, 8)), Some(block::AIR));
+    }
+  }
+
+  #[test]
+  fn lava_flows_slower_and_shorter() {
+    let mut world = two_chunks();
+    let mut simulation = FluidSimulation::default();
+    let source = IVec3::new(8, 1, 8);
+    world.set_block(source, block::LAVA);
+
+    // lava moves every fourth tick
+    run(&mut simulation, &mut world, 4);
+    assert_eq!(
+      world.get_block(source + IVec3::X),
+      Some(block::liquid(block::LAVA, 2, false))
+    );
+    assert_eq!(world.get_block(source + IVec3::X * 2), Some(block::AIR));
+
+    run(&mut simulation, &mut world, 40);
+    assert_eq!(
+      world.get_block(source + IVec3::X * 3),
+      Some(block::liquid(block::LAVA, 6, false))
+    );
+    assert_eq!(world.get_block(source + IVec3::X * 4), Some(block::AIR));
+  }
+
+  #[test]
+  fn inserted_chunks_wake_flowing_liquid() {
+    let mut world = VoxelWorld::default();
+    let mut simulation = FluidSimulation::default();
+    let mut chunk = ChunkBlockData::flat(IVec3::ZERO, 0);
+    let pos = IVec3::new(4, 1, 4);
+    chunk.set(
+      local_position(pos, IVec3::ZERO).unwrap(),
+      block::liquid(block::WATER, 3, false),
+    );
+    world.insert(chunk);
+
+    // nothing feeds it
+    run(&mut simulation, &mut world, 1);
+    assert_eq!(world.get_block(pos), Some(block::AIR));
+  }
+}
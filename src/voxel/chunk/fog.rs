@@ -0,0 +1,139 @@
+use bevy::{pbr::FogFalloff, prelude::*, render::render_resource::ShaderType};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE,
+  material::ChunkMaterial,
+  streaming::{ChunkViewer, VoxelWorldConfig},
+};
+
+/// Fog of a single [`ChunkMaterial`], applied on top of its lighting in the colour
+/// of the view's [`DistanceFog`]. Views without one aren't fogged at all.
+#[derive(Clone, Copy, Debug, PartialEq, ShaderType)]
+pub struct ChunkFog {
+  /// How much of the view's [`DistanceFog`] is applied, from 0 to 1.
+  pub distance_fog: f32,
+  /// Density of the height fog per block at `base_height`, zero turns it off.
+  pub height_density: f32,
+  /// How quickly the height fog thins out per block above `base_height`.
+  pub height_falloff: f32,
+  /// World height where the height fog has `height_density`.
+  pub base_height: f32,
+}
+
+impl Default for ChunkFog {
+  fn default() -> Self {
+    Self {
+      distance_fog: 1.0,
+      height_density: 0.015,
+      height_falloff: 0.12,
+      base_height: 0.0,
+    }
+  }
+}
+
+/// Fog of the voxel world. Every camera with a [`ChunkViewer`] gets a
+/// [`DistanceFog`] that fully covers the terrain at the edge of the loaded chunks.
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelFog {
+  /// Colour the terrain fades into, also used as the clear colour. The
+  /// [`SkyPlugin`](super::SkyPlugin) sets it to the horizon of the sky.
+  pub color: Color,
+  /// Fraction of the fog distance where the distance fog starts.
+  pub start: f32,
+  /// Tint of the light of directional lights scattered towards the view, see
+  /// [`DistanceFog::directional_light_color`].
+  pub sun_color: Color,
+  pub sun_exponent: f32,
+  /// Fog of chunk materials, materials that were changed since keep their own.
+  pub chunk: ChunkFog,
+}
+
+impl Default for VoxelFog {
+  fn default() -> Self {
+    Self {
+      color: Color::srgb(0.62, 0.75, 0.9),
+      start: 0.6,
+      sun_color: Color::srgba(1.0, 0.9, 0.7, 0.5),
+      sun_exponent: 24.0,
+      chunk: ChunkFog::default(),
+    }
+  }
+}
+
+impl VoxelFog {
+  /// Fog for viewers loading chunks up to `render_distance`. It ends a chunk
+  /// short of it, as the loaded area has a ragged edge of whole chunks.
+  pub fn distance_fog(&self, render_distance: i32) -> DistanceFog {
+    let end = (render_distance - 1).max(1) as f32 * CHUNK_SIZE as f32;
+    DistanceFog {
+      color: self.color,
+      directional_light_color: self.sun_color,
+      directional_light_exponent: self.sun_exponent,
+      falloff: FogFalloff::Linear {
+        start: end * self.start,
+        end,
+      },
+    }
+  }
+}
+
+/// Keeps the [`DistanceFog`] of chunk viewers, the clear colour and the fog of
+/// chunk materials in sync with the [`VoxelFog`].
+pub struct VoxelFogPlugin;
+
+impl Plugin for VoxelFogPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<VoxelFog>()
+      .add_systems(Update, (update_view_fog, update_material_fog));
+  }
+}
+
+fn update_view_fog(
+  mut commands: Commands,
+  fog: Res<VoxelFog>,
+  config: Res<VoxelWorldConfig>,
+  viewers: Query<(Entity, Ref<ChunkViewer>), With<Camera>>,
+  mut clear_color: ResMut<ClearColor>,
+) {
+  let changed = fog.is_changed() || config.is_changed();
+  for (entity, viewer) in &viewers {
+    if changed || viewer.is_added() {
+      commands
+        .entity(entity)
+        .insert(fog.distance_fog(config.render_distance));
+    }
+  }
+  if fog.is_changed() {
+    clear_color.0 = fog.color;
+  }
+}
+
+/// Applies changes of [`VoxelFog::chunk`] to the materials that still use the
+/// previous value.
+fn update_material_fog(
+  fog: Res<VoxelFog>,
+  mut materials: ResMut<Assets<ChunkMaterial>>,
+  mut previous: Local<Option<ChunkFog>>,
+) {
+  if !fog.is_changed() {
+    return;
+  }
+  let Some(previous) = previous.replace(fog.chunk) else {
+    return;
+  };
+  if previous == fog.chunk {
+    return;
+  }
+
+  let outdated: Vec<_> = materials
+    .iter()
+    .filter(|(_, material)| material.fog == previous)
+    .map(|(id, _)| id)
+    .collect();
+  for id in outdated {
+    if let Some(material) = materials.get_mut(id) {
+      material.fog = fog.chunk;
+    }
+  }
+}
@@ -1,23 +1,73 @@
-use crate::voxel::chunk::CHUNK_SIZE;
+use crate::voxel::chunk::{CHUNK_SIZE, block, props::Prop};
 use bevy::{math::USizeVec3, prelude::*};
 use noise::{NoiseFn, Perlin};
 
+/// Parameters of the terrain generator.
+#[derive(Resource, Clone, Debug)]
+pub struct GenerationSettings {
+  /// Horizontal size of terrain features in blocks.
+  pub noise_scale: f64,
+  /// Maximum distance of the terrain surface from height zero.
+  pub height_scale: f64,
+  /// World height up to which air above the terrain is filled with water.
+  pub sea_level: i32,
+}
+
+impl Default for GenerationSettings {
+  fn default() -> Self {
+    Self {
+      noise_scale: 50.0,
+      height_scale: 20.0,
+      sea_level: -4,
+    }
+  }
+}
+
+/// Terrain generator used for new chunks.
+#[derive(Clone, Debug)]
+pub enum Generator {
+  /// Perlin noise hills, with water filling the valleys below sea level.
+  Terrain(GenerationSettings),
+  /// Grass up to and including world height `height`, air above.
+  Flat { height: i32 },
+}
+
+impl Default for Generator {
+  fn default() -> Self {
+    Self::Terrain(GenerationSettings::default())
+  }
+}
+
+impl Generator {
+  /// Generates the blocks of a chunk and scatters props on its surface.
+  pub fn generate(&self, seed: u32, chunk_pos: IVec3) -> ChunkBlockData {
+    let mut chunk = match self {
+      Self::Terrain(settings) => ChunkBlockData::create(seed, chunk_pos, settings),
+      Self::Flat { height } => ChunkBlockData::flat(chunk_pos, *height),
+    };
+    chunk.scatter_props(seed);
+    chunk
+  }
+}
+
 pub struct ChunkBlockData {
   pub(super) data: [u8; (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2)],
   pub(super) chunk_pos: IVec3,
+  /// Props placed during generation, see [`ChunkBlockData::standing_props`].
+  pub(super) props: Vec<Prop>,
 }
 
 impl ChunkBlockData {
-  pub fn create(seed: u32, chunk_pos: IVec3) -> Self {
+  pub fn create(seed: u32, chunk_pos: IVec3, settings: &GenerationSettings) -> Self {
     let noise = Perlin::new(seed);
     let mut height_map = [[0f64; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
 
     for (x, row) in height_map.iter_mut().enumerate() {
       for (z, cell) in row.iter_mut().enumerate() {
         let height = noise.get([
-          (chunk_pos.x as f64 * CHUNK_SIZE as f64 + x as f64) / 50.0,
-          (chunk_pos.z as f64 * CHUNK_SIZE as f64 + z as f64) / 50.0,
-        ]) * 20.0;
+          (chunk_pos.x as f64 * CHUNK_SIZE as f64 + x as f64 - 1.0) / settings.noise_scale,
+          (chunk_pos.z as f64 * CHUNK_SIZE as f64 + z as f64 - 1.0) / settings.noise_scale,
+        ]) * settings.height_scale;
         *cell = height;
       }
     }
@@ -27,15 +77,42 @@ impl ChunkBlockData {
     for (x, row) in height_map.iter().enumerate() {
       for (z, cell) in row.iter().enumerate() {
         for y in 0..CHUNK_SIZE + 2 {
-          if (y as i32 + (chunk_pos.y * CHUNK_SIZE as i32)) <= cell.round() as i32 {
-            let index = get_index(x, y, z);
-            data[index] = 1;
+          let world_y = y as i32 - 1 + (chunk_pos.y * CHUNK_SIZE as i32);
+          let index = get_index(x, y, z);
+          if world_y <= cell.round() as i32 {
+            data[index] = block::GRASS;
+          } else if world_y <= settings.sea_level {
+            data[index] = block::WATER;
           }
         }
       }
     }
 
-    Self { data, chunk_pos }
+    Self {
+      data,
+      chunk_pos,
+      props: Vec::new(),
+    }
+  }
+
+  pub fn flat(chunk_pos: IVec3, height: i32) -> Self {
+    let mut data = [0u8; (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2)];
+    for y in 0..CHUNK_SIZE + 2 {
+      let world_y = y as i32 - 1 + (chunk_pos.y * CHUNK_SIZE as i32);
+      if world_y > height {
+        continue;
+      }
+      for x in 0..CHUNK_SIZE + 2 {
+        for z in 0..CHUNK_SIZE + 2 {
+          data[get_index(x, y, z)] = block::GRASS;
+        }
+      }
+    }
+    Self {
+      data,
+      chunk_pos,
+      props: Vec::new(),
+    }
   }
 
   #[inline]
@@ -44,8 +121,8 @@ impl ChunkBlockData {
   }
 
   #[inline]
-  pub fn empty(&self, pos: USizeVec3) -> bool {
-    self.get(pos) == 0
+  pub fn set(&mut self, pos: USizeVec3, block: u8) {
+    self.data[get_index(pos.x, pos.y, pos.z)] = block;
   }
 }
 
@@ -53,3 +130,27 @@ impl ChunkBlockData {
 fn get_index(x: usize, y: usize, z: usize) -> usize {
   x * (CHUNK_SIZE + 2) * (CHUNK_SIZE + 2) + y * (CHUNK_SIZE + 2) + z
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn generators_agree_on_world_heights() {
+    // terrain without hills is flat at height zero
+    let settings = GenerationSettings {
+      height_scale: 0.0,
+      ..default()
+    };
+    for chunk_pos in [IVec3::ZERO, IVec3::new(2, -1, -3)] {
+      let terrain = ChunkBlockData::create(7, chunk_pos, &settings);
+      let flat = ChunkBlockData::flat(chunk_pos, 0);
+      assert!(terrain.data == flat.data, "chunk {chunk_pos}");
+    }
+
+    // the padded cell below the chunk at height zero is the last grass block
+    let flat = ChunkBlockData::flat(IVec3::ZERO, 0);
+    assert_eq!(flat.get(USizeVec3::new(1, 1, 1)), block::GRASS);
+    assert_eq!(flat.get(USizeVec3::new(1, 2, 1)), block::AIR);
+  }
+}
@@ -0,0 +1,392 @@
+use bevy::{
+  asset::RenderAssetUsages,
+  math::USizeVec3,
+  mesh::{Indices, MeshVertexAttribute, PrimitiveTopology, VertexFormat},
+  prelude::*,
+};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE, CHUNK_SIZE_POW,
+  block::{self, BlockKind},
+  generation::ChunkBlockData,
+  mesh::{QUAD_AXES, face_normal, pack_quad, quad_corners, quad_indices, unpack_quad},
+};
+
+/// Position of a vertex of a greedy quad packed as `xxxxxyyyyyzzzzz`, the rest of
+/// the quad is in the attributes storage buffer of its [`ChunkMaterial`](super::material::ChunkMaterial).
+pub const QUAD_POSITION_ATTRIBUTE: MeshVertexAttribute =
+  MeshVertexAttribute::new("Quad position", 658854091323, VertexFormat::Uint32);
+
+/// Sky light of faces with an opaque block somewhere above them in the chunk.
+const COVERED_SKY_LIGHT: u32 = 5;
+/// Sky light of faces open to the sky.
+const MAX_SKY_LIGHT: u32 = 15;
+
+/// Opaque and cutout faces of a chunk merged into larger quads where neighboring
+/// faces share all their attributes.
+#[derive(Clone, Debug, Default)]
+pub struct GreedyQuads {
+  /// Merged quads, see [`pack_quad`].
+  pub quads: Vec<u32>,
+  /// Attributes of each quad, see [`pack_quad_attributes`].
+  pub attributes: Vec<u32>,
+}
+
+/// Packs the attributes of a quad as `............llllaaaaaaaabbbbddd`, matching
+/// `unpack_quad_attributes` in `chunk_util.wgsl`. `ao` is the occlusion of each
+/// corner from 0 (dark) to 3, in the order of the corners of the quad's vertices.
+#[inline]
+pub fn pack_quad_attributes(dir: u32, block: u8, ao: [u32; 4], light: u32) -> u32 {
+  (dir & 7)
+    | ((block::id(block) as u32) << 3)
+    | ao.iter().enumerate().fold(0, |bits, (corner, ao)| {
+      bits | ((ao & 3) << (7 + 2 * corner))
+    })
+    | ((light & 15) << 15)
+}
+
+/// Packs a vertex position for [`QUAD_POSITION_ATTRIBUTE`].
+#[inline]
+fn pack_quad_position(pos: USizeVec3) -> u32 {
+  let chunk_size_mask: u32 = (1 << CHUNK_SIZE_POW) - 1;
+
+  ((pos.x as u32 & chunk_size_mask) << (2 * CHUNK_SIZE_POW))
+    | ((pos.y as u32 & chunk_size_mask) << CHUNK_SIZE_POW)
+    | (pos.z as u32 & chunk_size_mask)
+}
+
+impl ChunkBlockData {
+  /// Whether the block at a padded position darkens the corners next to it,
+  /// positions outside the padding don't.
+  fn occludes(&self, pos: IVec3) -> bool {
+    let size = (CHUNK_SIZE + 2) as i32;
+    pos.cmpge(IVec3::ZERO).all()
+      && pos.cmplt(IVec3::splat(size)).all()
+      && block::block_kind(self.get(pos.as_usizevec3())) == BlockKind::Opaque
+  }
+
+  /// Ambient occlusion of each corner of a face, with the cell in front of the
+  /// face at `front`.
+  fn face_ao(&self, front: IVec3, dir: u32) -> [u32; 4] {
+    let (dir1, dir2) = QUAD_AXES[dir as usize];
+    let (dir1, dir2) = (dir1.as_ivec3(), dir2.as_ivec3());
+    quad_corners(USizeVec3::X, USizeVec3::Y).map(|corner| {
+      // the neighbors of the front cell towards the corner
+      let side1 = dir1 * (corner.x as i32 * 2 - 1);
+      let side2 = dir2 * (corner.y as i32 * 2 - 1);
+      let occluded1 = self.occludes(front + side1);
+      let occluded2 = self.occludes(front + side2);
+      if occluded1 && occluded2 {
+        return 0;
+      }
+      3 - occluded1 as u32 - occluded2 as u32 - self.occludes(front + side1 + side2) as u32
+    })
+  }
+
+  /// Sky light of the cell in front of a face, from opaque blocks above it in the
+  /// padded chunk. Chunks above aren't considered.
+  fn sky_light(&self, front: IVec3) -> u32 {
+    let covered = (front.y + 1..(CHUNK_SIZE + 2) as i32)
+      .any(|y| self.occludes(IVec3::new(front.x, y, front.z)));
+    if covered {
+      COVERED_SKY_LIGHT
+    } else {
+      MAX_SKY_LIGHT
+    }
+  }
+
+  /// Merges the single block faces of `quads`, as built by
+  /// [`create_mesh`](ChunkBlockData::create_mesh), into [`GreedyQuads`].
+  pub fn greedy_quads(&self, quads: &[u32]) -> GreedyQuads {
+    // faces with their attributes, grouped by direction and the layer of the face
+    let mut layers = vec![[[0u32; CHUNK_SIZE]; CHUNK_SIZE]; 6 * (CHUNK_SIZE + 2)];
+    for &quad in quads {
+      let (base, _, _, dir) = unpack_quad(quad);
+      let normal = face_normal(dir).as_ivec3();
+      // the block of the face, `base` lies on the face plane
+      let block_pos = base.as_ivec3() - normal.max(IVec3::ZERO);
+      let front = block_pos + normal;
+      let (dir1, dir2) = QUAD_AXES[dir as usize];
+      let block = self.get(block_pos.as_usizevec3());
+
+      let attributes =
+        pack_quad_attributes(dir, block, self.face_ao(front, dir), self.sky_light(front));
+      let layer = base.dot(USizeVec3::ONE - dir1 - dir2);
+      // zero marks an empty cell, so the attributes of every face are offset by one
+      layers[dir as usize * (CHUNK_SIZE + 2) + layer][base.dot(dir1) - 1][base.dot(dir2) - 1] =
+        attributes + 1;
+    }
+
+    let mut greedy = GreedyQuads::default();
+    for (index, cells) in layers.iter_mut().enumerate() {
+      let dir = (index / (CHUNK_SIZE + 2)) as u32;
+      let layer = index % (CHUNK_SIZE + 2);
+      let (dir1, dir2) = QUAD_AXES[dir as usize];
+      let normal_axis = USizeVec3::ONE - dir1 - dir2;
+
+      for u in 0..CHUNK_SIZE {
+        for v in 0..CHUNK_SIZE {
+          let cell = cells[u][v];
+          if cell == 0 {
+            continue;
+          }
+
+          let mut height = 1;
+          while v + height < CHUNK_SIZE && cells[u][v + height] == cell {
+            height += 1;
+          }
+          let mut width = 1;
+          while u + width < CHUNK_SIZE && cells[u + width][v..v + height].iter().all(|&c| c == cell)
+          {
+            width += 1;
+          }
+          for column in &mut cells[u..u + width] {
+            column[v..v + height].fill(0);
+          }
+
+          let attributes = cell - 1;
+          let base = normal_axis * layer + dir1 * (u + 1) + dir2 * (v + 1);
+          greedy.quads.push(pack_quad(
+            base,
+            width as u32,
+            height as u32,
+            dir,
+            ((attributes >> 3) & 15) as u8,
+          ));
+          greedy.attributes.push(attributes);
+        }
+      }
+    }
+    greedy
+  }
+}
+
+/// Builds a mesh with one [`QUAD_POSITION_ATTRIBUTE`] per vertex from greedy quads.
+/// Vertices of quad `i` are `4 * i..4 * i + 4`, so shaders find its attributes
+/// from the vertex index.
+pub fn greedy_mesh(quads: &[u32]) -> Mesh {
+  let mut vertices = Vec::with_capacity(quads.len() * 4);
+  let mut indices = Vec::with_capacity(quads.len() * 6);
+
+  for &quad in quads {
+    let (base, width, height, dir) = unpack_quad(quad);
+    let (dir1, dir2) = QUAD_AXES[dir as usize];
+
+    indices.extend(quad_indices(vertices.len() as u32, dir));
+    for corner in quad_corners(dir1 * width as usize, dir2 * height as usize) {
+      vertices.push(pack_quad_position(base + corner));
+    }
+  }
+
+  let mut mesh = Mesh::new(
+    PrimitiveTopology::TriangleList,
+    RenderAssetUsages::RENDER_WORLD,
+  );
+  mesh.insert_attribute(QUAD_POSITION_ATTRIBUTE, vertices);
+  mesh.insert_indices(Indices::U32(indices));
+  mesh
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A chunk of air with `blocks` set at their padded positions.
+  fn chunk_with(blocks: impl IntoIterator<Item = (USizeVec3, u8)>) -> ChunkBlockData {
+    let mut chunk = ChunkBlockData::flat(IVec3::ZERO, i32::MIN);
+    for (pos, block) in blocks {
+      chunk.set(pos, block);
+    }
+    chunk
+  }
+
+  /// Grass at every position of the layer at padded height `y`.
+  fn layer(y: usize) -> impl Iterator<Item = (USizeVec3, u8)> {
+    (1..CHUNK_SIZE + 1)
+      .flat_map(move |x| (1..CHUNK_SIZE + 1).map(move |z| (USizeVec3::new(x, y, z), block::GRASS)))
+  }
+
+  fn greedy(chunk: &ChunkBlockData) -> GreedyQuads {
+    chunk.greedy_quads(&chunk.create_mesh().quads)
+  }
+
+  /// Checks that every merged quad only covers faces with its own attributes.
+  fn assert_merged_faces_match(chunk: &ChunkBlockData, greedy: &GreedyQuads) {
+    for (&quad, &attributes) in greedy.quads.iter().zip(&greedy.attributes) {
+      let (base, width, height, dir) = unpack_quad(quad);
+      let (dir1, dir2) = QUAD_AXES[dir as usize];
+      let normal = face_normal(dir).as_ivec3();
+      for u in 0..width as usize {
+        for v in 0..height as usize {
+          let face = (base + dir1 * u + dir2 * v).as_ivec3();
+          let block_pos = face - normal.max(IVec3::ZERO);
+          let front = block_pos + normal;
+          let face_attributes = pack_quad_attributes(
+            dir,
+            chunk.get(block_pos.as_usizevec3()),
+            chunk.face_ao(front, dir),
+            chunk.sky_light(front),
+          );
+          assert_eq!(face_attributes, attributes, "face at {face} of {quad:#x}");
+        }
+      }
+    }
+  }
+
+  /// Top quads of the layer whose top faces are at padded height `y`.
+  fn top_quads(greedy: &GreedyQuads, y: usize) -> Vec<(u32, u32)> {
+    greedy
+      .quads
+      .iter()
+      .zip(&greedy.attributes)
+      .filter(|&(&quad, _)| {
+        let (base, _, _, dir) = unpack_quad(quad);
+        dir == 2 && base.y == y
+      })
+      .map(|(&quad, &attributes)| (quad, attributes))
+      .collect()
+  }
+
+  fn light(attributes: u32) -> u32 {
+    (attributes >> 15) & 15
+  }
+
+  #[test]
+  fn flat_layer_merges_into_one_quad_per_direction() {
+    let chunk = chunk_with(layer(8));
+    let greedy = greedy(&chunk);
+    assert_merged_faces_match(&chunk, &greedy);
+
+    let mut dirs: Vec<_> = greedy
+      .quads
+      .iter()
+      .map(|&quad| unpack_quad(quad).3)
+      .collect();
+    dirs.sort();
+    assert_eq!(dirs, [0, 1, 2, 3, 4, 5]);
+    for &quad in &greedy.quads {
+      let (_, width, height, dir) = unpack_quad(quad);
+      let (dir1, dir2) = QUAD_AXES[dir as usize];
+      let extent = |axis: USizeVec3| if axis == USizeVec3::Y { 1 } else { 16 };
+      assert_eq!(
+        (width, height),
+        (extent(dir1), extent(dir2)),
+        "direction {dir}"
+      );
+    }
+  }
+
+  #[test]
+  fn faces_with_different_ao_are_not_merged() {
+    // a block on the layer darkens the corners of the top faces around it
+    let chunk = chunk_with(layer(2).chain([(USizeVec3::new(8, 3, 8), block::GRASS)]));
+    let greedy = greedy(&chunk);
+    assert_merged_faces_match(&chunk, &greedy);
+
+    let top = top_quads(&greedy, 3);
+    assert!(top.len() > 1);
+    let occluded = top
+      .iter()
+      .filter(|&&(_, attributes)| (attributes >> 7) & 0xFF != 0xFF)
+      .count();
+    // the eight faces around the block, each on its own
+    assert_eq!(occluded, 8);
+    for (quad, attributes) in top {
+      let (_, width, height, _) = unpack_quad(quad);
+      if (attributes >> 7) & 0xFF != 0xFF {
+        assert_eq!((width, height), (1, 1));
+      }
+    }
+  }
+
+  #[test]
+  fn faces_with_different_light_are_not_merged() {
+    // a block high above the layer only covers the face below it from the sky
+    let chunk = chunk_with(layer(2).chain([(USizeVec3::new(5, 10, 5), block::GRASS)]));
+    let greedy = greedy(&chunk);
+    assert_merged_faces_match(&chunk, &greedy);
+
+    let top = top_quads(&greedy, 3);
+    assert!(top.len() > 1);
+    let covered: Vec<_> = top
+      .iter()
+      .filter(|&&(_, attributes)| light(attributes) == COVERED_SKY_LIGHT)
+      .collect();
+    assert_eq!(covered.len(), 1);
+    assert_eq!(
+      unpack_quad(covered[0].0),
+      (USizeVec3::new(5, 3, 5), 1, 1, 2)
+    );
+    assert!(
+      top
+        .iter()
+        .all(|&(quad, attributes)| quad == covered[0].0 || light(attributes) == MAX_SKY_LIGHT)
+    );
+  }
+
+  /// Shift and mask of every field `unpack_quad_attributes` in `chunk_util.wgsl`
+  /// reads, in the order it reads them.
+  fn shader_attribute_fields() -> Vec<(u32, u32)> {
+    let shader = include_str!("../../../assets/shaders/chunk_util.wgsl");
+    let start = shader
+      .find("fn unpack_quad_attributes")
+      .expect("unpack_quad_attributes is missing");
+    let body = &shader[start..];
+    let body = &body[body.find('{').unwrap()..body.find("\n}").unwrap()];
+
+    let number = |text: &str| {
+      let digits: String = text
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+      let digits = digits.trim_end_matches('u');
+      match digits.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).unwrap(),
+        None => digits.parse().unwrap(),
+      }
+    };
+    body
+      .split("data")
+      .skip(1)
+      .map(|field| {
+        let field = field.trim_start();
+        let shift = field
+          .strip_prefix(">>")
+          .map_or(0, |s| number(s.trim_start()));
+        let mask = field.split_once('&').unwrap().1.trim_start();
+        (shift, number(mask))
+      })
+      .collect()
+  }
+
+  #[test]
+  fn attribute_layout_matches_shader() {
+    let fields = shader_attribute_fields();
+    // direction, block, the occlusion of each corner and the light
+    assert_eq!(fields.len(), 7);
+
+    for (dir, block, ao, light) in [
+      (5, block::LEAVES, [0, 1, 2, 3], 9),
+      (2, block::LAVA, [3, 2, 1, 0], 15),
+      (0, block::GLASS, [3, 3, 3, 3], 0),
+    ] {
+      let attributes = pack_quad_attributes(dir, block, ao, light);
+      let unpacked: Vec<_> = fields
+        .iter()
+        .map(|&(shift, mask)| (attributes >> shift) & mask)
+        .collect();
+      let expected = [dir, block as u32, ao[0], ao[1], ao[2], ao[3], light];
+      assert_eq!(unpacked, expected);
+    }
+
+    // every field has its own bits
+    let all_bits = fields.iter().fold(0u32, |bits, &(shift, mask)| {
+      assert_eq!(bits & (mask << shift), 0, "field at {shift} overlaps");
+      bits | (mask << shift)
+    });
+    assert_eq!(
+      pack_quad_attributes(7, block::id(u8::MAX), [3; 4], 15),
+      all_bits
+    );
+  }
+}
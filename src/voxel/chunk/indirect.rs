@@ -0,0 +1,741 @@
+use std::{ops::Range, str::FromStr, sync::Arc};
+
+use bevy::{
+  camera::primitives::{Aabb, Frustum},
+  core_pipeline::core_3d::{CORE_3D_DEPTH_FORMAT, Transparent3d},
+  ecs::system::{
+    SystemParamItem,
+    lifetimeless::{Read, SRes},
+  },
+  image::BevyDefault,
+  math::Affine3A,
+  mesh::{VertexBufferLayout, VertexFormat},
+  pbr::{MeshPipeline, MeshPipelineKey, SetMeshViewBindGroup, SetMeshViewBindingArrayBindGroup},
+  platform::collections::HashMap,
+  prelude::*,
+  render::{
+    Extract, ExtractSchedule, Render, RenderApp, RenderStartup, RenderSystems,
+    render_phase::{
+      AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
+      RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+    },
+    render_resource::{
+      BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, Buffer,
+      BufferDescriptor, BufferInitDescriptor, BufferUsages, ColorTargetState, ColorWrites,
+      CommandEncoderDescriptor, CompareFunction, DepthStencilState, DrawIndexedIndirectArgs, Face,
+      FragmentState, IndexFormat, MultisampleState, PipelineCache, PrimitiveState,
+      RenderPipelineDescriptor, ShaderStages, SpecializedRenderPipeline,
+      SpecializedRenderPipelines, TextureFormat, VertexAttribute, VertexState, VertexStepMode,
+      binding_types::storage_buffer_read_only_sized,
+    },
+    renderer::{RenderDevice, RenderQueue},
+    settings::WgpuFeatures,
+    sync_world::MainEntity,
+    view::{ExtractedView, ViewTarget},
+  },
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE,
+  allocator::{BufferAllocator, Relocation},
+  diagnostics::InstanceUploads,
+  indirect_prepass::IndirectPrepassPlugin,
+  mesh::{MAX_CHUNK_QUADS, chunk_translation, expand_quads},
+};
+
+const SHADER_PATH: &str = "shaders/chunk_indirect.wgsl";
+
+/// Initial size of the shared vertex buffer, in vertices.
+const INITIAL_VERTEX_CAPACITY: u32 = 1 << 20;
+/// Initial size of the shared index buffer, in indices.
+const INITIAL_INDEX_CAPACITY: u32 = 3 << 19;
+/// Fragmentation above which a shared buffer gets compacted.
+const MAX_FRAGMENTATION: f32 = 0.5;
+
+/// Packed chunk geometry that is drawn from the shared buffers of the
+/// [`ChunkIndirectPlugin`] instead of through its own [`Mesh`].
+#[derive(Component, Clone)]
+pub struct IndirectChunkMesh {
+  /// Packed quads, see [`pack_quad`](crate::voxel::chunk::mesh::pack_quad).
+  pub quads: Arc<[u32]>,
+  pub chunk_pos: IVec3,
+}
+
+/// How chunk entities are rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkBackend {
+  /// A [`Mesh`] per chunk with the [`ChunkMaterial`](super::material::ChunkMaterial),
+  /// plus its translucent faces, liquids and props.
+  #[default]
+  Meshes,
+  /// Opaque and cutout quads of all chunks in the shared buffers of the
+  /// [`ChunkIndirectPlugin`], drawn with one indirect draw per view.
+  Indirect,
+}
+
+impl FromStr for ChunkBackend {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "meshes" => Ok(Self::Meshes),
+      "indirect" => Ok(Self::Indirect),
+      _ => Err(format!("unknown chunk backend: {s}")),
+    }
+  }
+}
+
+/// How chunk geometry is stored in the shared buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkVertexLayout {
+  /// Four packed vertices and six indices per quad in a vertex and an index buffer.
+  Vertices,
+  /// One packed `u32` per quad in a storage buffer, expanded to its corners in the
+  /// vertex shader and drawn with a shared static index buffer.
+  #[default]
+  Quads,
+}
+
+impl FromStr for ChunkVertexLayout {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "vertices" => Ok(Self::Vertices),
+      "quads" => Ok(Self::Quads),
+      _ => Err(format!("unknown vertex layout: {s}")),
+    }
+  }
+}
+
+/// Renders all [`IndirectChunkMesh`]es with a single `multi_draw_indexed_indirect`
+/// call per view, falling back to one draw per chunk when the device lacks support.
+/// Views include the shadow cascades of directional lights and the depth and
+/// normal prepass.
+#[derive(Default)]
+pub struct ChunkIndirectPlugin {
+  pub layout: ChunkVertexLayout,
+}
+
+impl Plugin for ChunkIndirectPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_plugins(IndirectPrepassPlugin);
+    app
+      .sub_app_mut(RenderApp)
+      .insert_resource(ChunkLayout(self.layout))
+      .add_render_command::<Transparent3d, DrawChunksIndirect>()
+      .init_resource::<SpecializedRenderPipelines<ChunkIndirectPipeline>>()
+      .init_resource::<ExtractedIndirectChunks>()
+      .add_systems(RenderStartup, init_chunk_indirect)
+      .add_systems(ExtractSchedule, extract_indirect_chunks)
+      .add_systems(
+        Render,
+        (
+          queue_indirect_chunks.in_set(RenderSystems::QueueMeshes),
+          (prepare_chunk_buffers, prepare_indirect_draws)
+            .chain()
+            .in_set(RenderSystems::PrepareResources),
+        ),
+      );
+  }
+}
+
+#[derive(Resource, Default)]
+struct ExtractedIndirectChunks {
+  changed: Vec<(MainEntity, IndirectChunkMesh)>,
+  removed: Vec<MainEntity>,
+}
+
+fn extract_indirect_chunks(
+  mut extracted: ResMut<ExtractedIndirectChunks>,
+  changed: Extract<Query<(Entity, &IndirectChunkMesh), Changed<IndirectChunkMesh>>>,
+  mut removed: Extract<RemovedComponents<IndirectChunkMesh>>,
+) {
+  for (entity, mesh) in &changed {
+    extracted.changed.push((entity.into(), mesh.clone()));
+  }
+
+  for entity in removed.read() {
+    if !changed.contains(entity) {
+      extracted.removed.push(entity.into());
+    }
+  }
+}
+
+#[derive(Resource, Deref)]
+pub(super) struct ChunkLayout(ChunkVertexLayout);
+
+/// One of the large buffers all chunks are sub-allocated from.
+struct ChunkSlab {
+  label: &'static str,
+  usage: BufferUsages,
+  allocator: BufferAllocator<MainEntity>,
+  buffer: Option<Buffer>,
+}
+
+impl ChunkSlab {
+  fn new(label: &'static str, usage: BufferUsages, capacity: u32) -> Self {
+    Self {
+      label,
+      usage: usage | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+      allocator: BufferAllocator::new(capacity),
+      buffer: None,
+    }
+  }
+
+  /// Compacts the allocator if too much of the free space is scattered.
+  fn defragment_if_needed(&mut self) -> Vec<Relocation> {
+    if self.allocator.fragmentation() > MAX_FRAGMENTATION {
+      self.allocator.defragment()
+    } else {
+      Vec::new()
+    }
+  }
+
+  /// Recreates the GPU buffer if the allocator grew or moved allocations and copies
+  /// the previous contents over. Returns `true` if the buffer was recreated.
+  fn sync(
+    &mut self,
+    relocations: &[Relocation],
+    device: &RenderDevice,
+    queue: &RenderQueue,
+  ) -> bool {
+    let size = self.allocator.capacity() as u64 * size_of::<u32>() as u64;
+    if let Some(buffer) = &self.buffer
+      && buffer.size() == size
+      && relocations.is_empty()
+    {
+      return false;
+    }
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+      label: Some(self.label),
+      size,
+      usage: self.usage,
+      mapped_at_creation: false,
+    });
+
+    if let Some(old) = self.buffer.take() {
+      let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("chunk_slab_copy"),
+      });
+      encoder.copy_buffer_to_buffer(&old, 0, &buffer, 0, old.size().min(size));
+      for relocation in relocations {
+        encoder.copy_buffer_to_buffer(
+          &old,
+          relocation.from as u64 * size_of::<u32>() as u64,
+          &buffer,
+          relocation.to as u64 * size_of::<u32>() as u64,
+          relocation.len as u64 * size_of::<u32>() as u64,
+        );
+      }
+      // submit right away so the copies land before the writes queued for this frame
+      queue.submit([encoder.finish()]);
+    }
+
+    self.buffer = Some(buffer);
+    true
+  }
+
+  fn write(&self, entity: &MainEntity, data: &[u32], queue: &RenderQueue) {
+    let (Some(buffer), Some(range)) = (&self.buffer, self.allocator.get(entity)) else {
+      return;
+    };
+    if range.is_empty() {
+      return;
+    }
+
+    queue.write_buffer(
+      buffer,
+      range.start as u64 * size_of::<u32>() as u64,
+      bytemuck::cast_slice(data),
+    );
+  }
+}
+
+#[derive(Resource)]
+pub(super) struct ChunkBuffers {
+  /// Packed vertices or quads, depending on the [`ChunkVertexLayout`].
+  data: ChunkSlab,
+  /// Per-chunk indices, only used by [`ChunkVertexLayout::Vertices`].
+  indices: Option<ChunkSlab>,
+  /// Static `0 1 2 2 3 0` index pattern for [`MAX_CHUNK_QUADS`] quads, only used by
+  /// [`ChunkVertexLayout::Quads`].
+  quad_indices: Option<Buffer>,
+  /// Binds `data` as storage buffer for [`ChunkVertexLayout::Quads`].
+  quad_bind_group: Option<BindGroup>,
+  translations: HashMap<MainEntity, Vec3>,
+  /// Whether `multi_draw_indexed_indirect` with a non-zero first instance is supported.
+  multi_draw: bool,
+}
+
+impl ChunkBuffers {
+  /// Whether no chunk is in the buffers.
+  pub(super) fn is_empty(&self) -> bool {
+    self.translations.is_empty()
+  }
+
+  fn remove(&mut self, entity: &MainEntity) {
+    self.data.allocator.deallocate(entity);
+    if let Some(indices) = &mut self.indices {
+      indices.allocator.deallocate(entity);
+    }
+    self.translations.remove(entity);
+  }
+
+  /// Returns the index range and base vertex to draw `entity` with.
+  fn draw_range(&self, entity: &MainEntity) -> Option<(Range<u32>, i32)> {
+    let data = self.data.allocator.get(entity)?;
+    match &self.indices {
+      Some(indices) => Some((indices.allocator.get(entity)?, data.start as i32)),
+      None => Some((0..data.len() as u32 * 6, data.start as i32 * 4)),
+    }
+  }
+}
+
+fn prepare_chunk_buffers(
+  mut extracted: ResMut<ExtractedIndirectChunks>,
+  mut buffers: ResMut<ChunkBuffers>,
+  chunk_pipeline: Res<ChunkIndirectPipeline>,
+  render_device: Res<RenderDevice>,
+  render_queue: Res<RenderQueue>,
+  instance_uploads: Option<Res<InstanceUploads>>,
+) {
+  let ExtractedIndirectChunks { changed, removed } = std::mem::take(&mut *extracted);
+  let buffers = &mut *buffers;
+
+  for entity in removed
+    .iter()
+    .chain(changed.iter().map(|(entity, _)| entity))
+  {
+    buffers.remove(entity);
+  }
+
+  let data_relocations = buffers.data.defragment_if_needed();
+  let index_relocations = buffers
+    .indices
+    .as_mut()
+    .map(ChunkSlab::defragment_if_needed)
+    .unwrap_or_default();
+
+  let uploads: Vec<_> = changed
+    .into_iter()
+    .map(|(entity, mesh)| {
+      let (data, indices) = match &buffers.indices {
+        Some(_) => {
+          let (vertices, indices) = expand_quads(&mesh.quads);
+          (vertices, Some(indices))
+        }
+        None => (mesh.quads.to_vec(), None),
+      };
+
+      buffers.data.allocator.allocate(entity, data.len() as u32);
+      if let (Some(slab), Some(indices)) = (&mut buffers.indices, &indices) {
+        slab.allocator.allocate(entity, indices.len() as u32);
+      }
+      buffers
+        .translations
+        .insert(entity, chunk_translation(mesh.chunk_pos));
+
+      (entity, data, indices)
+    })
+    .collect();
+
+  let data_recreated = buffers
+    .data
+    .sync(&data_relocations, &render_device, &render_queue);
+  if let Some(indices) = &mut buffers.indices {
+    indices.sync(&index_relocations, &render_device, &render_queue);
+  }
+
+  if buffers.indices.is_none()
+    && (data_recreated || buffers.quad_bind_group.is_none())
+    && let (Some(layout), Some(buffer)) = (&chunk_pipeline.quad_layout, &buffers.data.buffer)
+  {
+    buffers.quad_bind_group = Some(render_device.create_bind_group(
+      "chunk_quad_bind_group",
+      layout,
+      &BindGroupEntries::single(buffer.as_entire_buffer_binding()),
+    ));
+  }
+
+  for (entity, data, indices) in &uploads {
+    buffers.data.write(entity, data, &render_queue);
+    if let (Some(slab), Some(indices)) = (&buffers.indices, indices) {
+      slab.write(entity, indices, &render_queue);
+    }
+    if let Some(instance_uploads) = &instance_uploads {
+      let index_bytes = indices.as_deref().map_or(0, size_of_val);
+      instance_uploads.add((size_of_val(data.as_slice()) + index_bytes) as u64);
+    }
+  }
+}
+
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct ChunkInstance {
+  translation: [f32; 3],
+}
+
+/// Indirect draw arguments of all chunks visible from a view.
+#[derive(Component)]
+pub(super) struct ChunkIndirectDraws {
+  args: Vec<DrawIndexedIndirectArgs>,
+  args_buffer: Buffer,
+  instance_buffer: Buffer,
+}
+
+fn prepare_indirect_draws(
+  mut commands: Commands,
+  buffers: Res<ChunkBuffers>,
+  views: Query<(Entity, &Frustum), With<ExtractedView>>,
+  render_device: Res<RenderDevice>,
+  instance_uploads: Option<Res<InstanceUploads>>,
+) {
+  let chunk_aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::splat((CHUNK_SIZE + 2) as f32));
+
+  for (view, frustum) in &views {
+    let mut args = Vec::new();
+    let mut instances = Vec::new();
+
+    for (entity, translation) in &buffers.translations {
+      let Some((indices, base_vertex)) = buffers.draw_range(entity) else {
+        continue;
+      };
+      if indices.is_empty()
+        || !frustum.intersects_obb(
+          &chunk_aabb,
+          &Affine3A::from_translation(*translation),
+          true,
+          false,
+        )
+      {
+        continue;
+      }
+
+      args.push(DrawIndexedIndirectArgs {
+        index_count: indices.len() as u32,
+        instance_count: 1,
+        first_index: indices.start,
+        base_vertex,
+        first_instance: instances.len() as u32,
+      });
+      instances.push(ChunkInstance {
+        translation: translation.to_array(),
+      });
+    }
+
+    if args.is_empty() {
+      commands.entity(view).remove::<ChunkIndirectDraws>();
+      continue;
+    }
+
+    if let Some(instance_uploads) = &instance_uploads {
+      instance_uploads
+        .add((size_of_val(args.as_slice()) + size_of_val(instances.as_slice())) as u64);
+    }
+    let args_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+      label: Some("chunk_indirect_args_buffer"),
+      usage: BufferUsages::INDIRECT,
+      contents: bytemuck::cast_slice(&args),
+    });
+    let instance_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+      label: Some("chunk_indirect_instance_buffer"),
+      usage: BufferUsages::VERTEX,
+      contents: bytemuck::cast_slice(&instances),
+    });
+
+    commands.entity(view).insert(ChunkIndirectDraws {
+      args,
+      args_buffer,
+      instance_buffer,
+    });
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_indirect_chunks(
+  transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+  chunk_pipeline: Res<ChunkIndirectPipeline>,
+  mut pipelines: ResMut<SpecializedRenderPipelines<ChunkIndirectPipeline>>,
+  pipeline_cache: Res<PipelineCache>,
+  buffers: Res<ChunkBuffers>,
+  mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
+  views: Query<(Entity, &ExtractedView, &Msaa, Has<DistanceFog>)>,
+) {
+  if buffers.is_empty() {
+    return;
+  }
+
+  let draw_chunks = transparent_3d_draw_functions
+    .read()
+    .id::<DrawChunksIndirect>();
+
+  for (view_entity, view, msaa, distance_fog) in &views {
+    let Some(transparent_phase) = transparent_render_phases.get_mut(&view.retained_view_entity)
+    else {
+      continue;
+    };
+
+    let mut key =
+      MeshPipelineKey::from_msaa_samples(msaa.samples()) | MeshPipelineKey::from_hdr(view.hdr);
+    if distance_fog {
+      key |= MeshPipelineKey::DISTANCE_FOG;
+    }
+    let pipeline = pipelines.specialize(&pipeline_cache, &chunk_pipeline, key);
+
+    // all chunks are drawn by a single item that is not tied to any entity
+    transparent_phase.add(Transparent3d {
+      entity: (view_entity, MainEntity::from(Entity::PLACEHOLDER)),
+      pipeline,
+      draw_function: draw_chunks,
+      distance: 0.0,
+      batch_range: 0..1,
+      extra_index: PhaseItemExtraIndex::None,
+      indexed: true,
+    });
+  }
+}
+
+#[derive(Resource)]
+pub(super) struct ChunkIndirectPipeline {
+  shader: Handle<Shader>,
+  mesh_pipeline: MeshPipeline,
+  /// Layout of the quad storage buffer, only present for [`ChunkVertexLayout::Quads`].
+  pub(super) quad_layout: Option<BindGroupLayout>,
+}
+
+pub(super) fn init_chunk_indirect(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  mesh_pipeline: Res<MeshPipeline>,
+  render_device: Res<RenderDevice>,
+  layout: Res<ChunkLayout>,
+) {
+  let (data, indices, quad_indices, quad_layout) = match **layout {
+    ChunkVertexLayout::Vertices => (
+      ChunkSlab::new(
+        "chunk_vertex_slab",
+        BufferUsages::VERTEX,
+        INITIAL_VERTEX_CAPACITY,
+      ),
+      Some(ChunkSlab::new(
+        "chunk_index_slab",
+        BufferUsages::INDEX,
+        INITIAL_INDEX_CAPACITY,
+      )),
+      None,
+      None,
+    ),
+    ChunkVertexLayout::Quads => {
+      let pattern: Vec<u32> = (0..MAX_CHUNK_QUADS)
+        .flat_map(|quad| [0, 1, 2, 2, 3, 0].map(|corner| quad * 4 + corner))
+        .collect();
+
+      (
+        ChunkSlab::new(
+          "chunk_quad_slab",
+          BufferUsages::STORAGE,
+          INITIAL_VERTEX_CAPACITY / 4,
+        ),
+        None,
+        Some(
+          render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("chunk_quad_index_buffer"),
+            usage: BufferUsages::INDEX,
+            contents: bytemuck::cast_slice(&pattern),
+          }),
+        ),
+        Some(render_device.create_bind_group_layout(
+          "chunk_quad_layout",
+          &BindGroupLayoutEntries::single(
+            ShaderStages::VERTEX,
+            storage_buffer_read_only_sized(false, None),
+          ),
+        )),
+      )
+    }
+  };
+
+  commands.insert_resource(ChunkIndirectPipeline {
+    shader: asset_server.load(SHADER_PATH),
+    mesh_pipeline: mesh_pipeline.clone(),
+    quad_layout,
+  });
+  commands.insert_resource(ChunkBuffers {
+    data,
+    indices,
+    quad_indices,
+    quad_bind_group: None,
+    translations: HashMap::default(),
+    multi_draw: render_device
+      .features()
+      .contains(WgpuFeatures::MULTI_DRAW_INDIRECT | WgpuFeatures::INDIRECT_FIRST_INSTANCE),
+  });
+}
+
+impl SpecializedRenderPipeline for ChunkIndirectPipeline {
+  type Key = MeshPipelineKey;
+
+  fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+    let view_layout = self.mesh_pipeline.get_view_layout(key.into());
+
+    let mut shader_defs = Vec::new();
+    if key.msaa_samples() > 1 {
+      shader_defs.push("MULTISAMPLED".into());
+    }
+    if key.contains(MeshPipelineKey::DISTANCE_FOG) {
+      shader_defs.push("DISTANCE_FOG".into());
+    }
+
+    let mut layout = vec![
+      view_layout.main_layout.clone(),
+      view_layout.binding_array_layout.clone(),
+    ];
+    if let Some(quad_layout) = &self.quad_layout {
+      shader_defs.push("VERTEX_PULLING".into());
+      layout.push(quad_layout.clone());
+    }
+    let buffers = chunk_vertex_buffers(self.quad_layout.is_some());
+
+    let format = if key.contains(MeshPipelineKey::HDR) {
+      ViewTarget::TEXTURE_FORMAT_HDR
+    } else {
+      TextureFormat::bevy_default()
+    };
+
+    RenderPipelineDescriptor {
+      label: Some("chunk_indirect_pipeline".into()),
+      layout,
+      vertex: VertexState {
+        shader: self.shader.clone(),
+        shader_defs: shader_defs.clone(),
+        buffers,
+        ..default()
+      },
+      fragment: Some(FragmentState {
+        shader: self.shader.clone(),
+        shader_defs,
+        targets: vec![Some(ColorTargetState {
+          format,
+          blend: None,
+          write_mask: ColorWrites::ALL,
+        })],
+        ..default()
+      }),
+      primitive: PrimitiveState {
+        cull_mode: Some(Face::Back),
+        ..default()
+      },
+      depth_stencil: Some(DepthStencilState {
+        format: CORE_3D_DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: CompareFunction::GreaterEqual,
+        stencil: default(),
+        bias: default(),
+      }),
+      multisample: MultisampleState {
+        count: key.msaa_samples(),
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+      },
+      ..default()
+    }
+  }
+}
+
+/// Vertex buffers bound by [`DrawChunkBuffers`], the translation of every chunk
+/// instance and, without vertex pulling, the packed vertices.
+pub(super) fn chunk_vertex_buffers(vertex_pulling: bool) -> Vec<VertexBufferLayout> {
+  let mut buffers = vec![VertexBufferLayout {
+    array_stride: size_of::<ChunkInstance>() as u64,
+    step_mode: VertexStepMode::Instance,
+    attributes: vec![VertexAttribute {
+      format: VertexFormat::Float32x3,
+      offset: 0,
+      shader_location: 1,
+    }],
+  }];
+  if !vertex_pulling {
+    buffers.push(VertexBufferLayout {
+      array_stride: size_of::<u32>() as u64,
+      step_mode: VertexStepMode::Vertex,
+      attributes: vec![VertexAttribute {
+        format: VertexFormat::Uint32,
+        offset: 0,
+        shader_location: 0,
+      }],
+    });
+  }
+  buffers
+}
+
+type DrawChunksIndirect = (
+  SetItemPipeline,
+  SetMeshViewBindGroup<0>,
+  SetMeshViewBindingArrayBindGroup<1>,
+  DrawChunkBuffers,
+);
+
+/// Draws the chunks visible from the view out of the [`ChunkBuffers`], after the
+/// pipeline and the bind groups of the view are set.
+pub(super) struct DrawChunkBuffers;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawChunkBuffers {
+  type Param = SRes<ChunkBuffers>;
+  type ViewQuery = Option<Read<ChunkIndirectDraws>>;
+  type ItemQuery = ();
+
+  #[inline]
+  fn render<'w>(
+    _item: &P,
+    draws: Option<&'w ChunkIndirectDraws>,
+    _entity: Option<()>,
+    buffers: SystemParamItem<'w, '_, Self::Param>,
+    pass: &mut TrackedRenderPass<'w>,
+  ) -> RenderCommandResult {
+    let buffers = buffers.into_inner();
+
+    let Some(draws) = draws else {
+      return RenderCommandResult::Skip;
+    };
+    let Some(data_buffer) = &buffers.data.buffer else {
+      return RenderCommandResult::Skip;
+    };
+
+    pass.set_vertex_buffer(0, draws.instance_buffer.slice(..));
+    match (
+      &buffers.indices,
+      &buffers.quad_indices,
+      &buffers.quad_bind_group,
+    ) {
+      (Some(indices), _, _) => {
+        let Some(index_buffer) = &indices.buffer else {
+          return RenderCommandResult::Skip;
+        };
+        pass.set_vertex_buffer(1, data_buffer.slice(..));
+        pass.set_index_buffer(index_buffer.slice(..), 0, IndexFormat::Uint32);
+      }
+      (None, Some(quad_indices), Some(bind_group)) => {
+        pass.set_bind_group(2, bind_group, &[]);
+        pass.set_index_buffer(quad_indices.slice(..), 0, IndexFormat::Uint32);
+      }
+      _ => return RenderCommandResult::Skip,
+    }
+
+    if buffers.multi_draw {
+      pass.multi_draw_indexed_indirect(&draws.args_buffer, 0, draws.args.len() as u32);
+    } else {
+      for args in &draws.args {
+        pass.draw_indexed(
+          args.first_index..args.first_index + args.index_count,
+          args.base_vertex,
+          args.first_instance..args.first_instance + 1,
+        );
+      }
+    }
+
+    RenderCommandResult::Success
+  }
+}
@@ -0,0 +1,281 @@
+use bevy::{
+  core_pipeline::{
+    core_3d::CORE_3D_DEPTH_FORMAT,
+    prepass::{
+      Opaque3dPrepass, OpaqueNoLightmap3dBatchSetKey, OpaqueNoLightmap3dBinKey,
+      prepass_target_descriptors,
+    },
+  },
+  ecs::system::SystemChangeTick,
+  pbr::{
+    LightEntity, LightKeyCache, MeshPipelineKey, PrepassPipeline, SetPrepassViewBindGroup,
+    SetPrepassViewEmptyBindGroup, Shadow, ShadowBatchSetKey, ShadowBinKey, ViewKeyPrepassCache,
+    ViewLightEntities, init_prepass_pipeline,
+  },
+  prelude::*,
+  render::{
+    Render, RenderApp, RenderStartup, RenderSystems,
+    render_phase::{
+      AddRenderCommand, BinnedRenderPhaseType, DrawFunctions, InputUniformIndex, SetItemPipeline,
+      ViewBinnedRenderPhases,
+    },
+    render_resource::{
+      BindGroupLayout, CompareFunction, DepthStencilState, Face, FragmentState, MultisampleState,
+      PipelineCache, PrimitiveState, RenderPipelineDescriptor, SpecializedRenderPipeline,
+      SpecializedRenderPipelines, VertexState,
+    },
+    sync_world::MainEntity,
+    view::ExtractedView,
+  },
+};
+
+use crate::voxel::chunk::{
+  indirect::{
+    ChunkBuffers, ChunkIndirectPipeline, DrawChunkBuffers, chunk_vertex_buffers,
+    init_chunk_indirect,
+  },
+  material::PREPASS_SHADER_PATH,
+};
+
+/// Draws the chunks of the [`ChunkIndirectPlugin`](super::ChunkIndirectPlugin)
+/// into the cascaded shadow maps of directional lights and the depth and normal
+/// prepass, with the `INDIRECT` variant of `chunk_prepass.wgsl`.
+pub(super) struct IndirectPrepassPlugin;
+
+impl Plugin for IndirectPrepassPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .sub_app_mut(RenderApp)
+      .add_render_command::<Shadow, DrawChunksIndirectPrepass>()
+      .add_render_command::<Opaque3dPrepass, DrawChunksIndirectPrepass>()
+      .init_resource::<SpecializedRenderPipelines<IndirectPrepassPipeline>>()
+      .add_systems(
+        RenderStartup,
+        init_indirect_prepass_pipeline
+          .after(init_prepass_pipeline)
+          .after(init_chunk_indirect),
+      )
+      .add_systems(
+        Render,
+        (queue_indirect_shadows, queue_indirect_prepass).in_set(RenderSystems::QueueMeshes),
+      );
+  }
+}
+
+/// Depth only pipeline of the chunk buffers, with normals for the normal prepass.
+#[derive(Resource)]
+struct IndirectPrepassPipeline {
+  shader: Handle<Shader>,
+  view_layout: BindGroupLayout,
+  empty_layout: BindGroupLayout,
+  /// Layout of the quad storage buffer, see [`ChunkIndirectPipeline`].
+  quad_layout: Option<BindGroupLayout>,
+  depth_clip_control_supported: bool,
+}
+
+fn init_indirect_prepass_pipeline(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  prepass_pipeline: Res<PrepassPipeline>,
+  chunk_pipeline: Res<ChunkIndirectPipeline>,
+) {
+  commands.insert_resource(IndirectPrepassPipeline {
+    shader: asset_server.load(PREPASS_SHADER_PATH),
+    view_layout: prepass_pipeline.view_layout_no_motion_vectors.clone(),
+    empty_layout: prepass_pipeline.empty_layout.clone(),
+    quad_layout: chunk_pipeline.quad_layout.clone(),
+    depth_clip_control_supported: prepass_pipeline.depth_clip_control_supported,
+  });
+}
+
+impl SpecializedRenderPipeline for IndirectPrepassPipeline {
+  type Key = MeshPipelineKey;
+
+  fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+    // cutout blocks are in the same buffers, so the fragment shader always runs
+    // to discard their holes
+    let mut shader_defs = vec!["INDIRECT".into(), "MAY_DISCARD".into()];
+    let mut layout = vec![self.view_layout.clone(), self.empty_layout.clone()];
+    if let Some(quad_layout) = &self.quad_layout {
+      shader_defs.push("VERTEX_PULLING".into());
+      layout.push(quad_layout.clone());
+    }
+
+    let normal_prepass = key.contains(MeshPipelineKey::NORMAL_PREPASS);
+    if normal_prepass {
+      shader_defs.push("NORMAL_PREPASS".into());
+    }
+    // shadow maps of directional lights don't clip depth, in the fragment shader
+    // where the GPU can't turn depth clipping off
+    let unclipped_depth = key.contains(MeshPipelineKey::UNCLIPPED_DEPTH_ORTHO);
+    let emulate_unclipped_depth = unclipped_depth && !self.depth_clip_control_supported;
+    if emulate_unclipped_depth {
+      shader_defs.push("UNCLIPPED_DEPTH_ORTHO_EMULATION".into());
+    }
+    // without outputs the fragment shader only discards
+    if normal_prepass || emulate_unclipped_depth {
+      shader_defs.push("PREPASS_FRAGMENT".into());
+    }
+
+    let mut targets = prepass_target_descriptors(normal_prepass, false, false);
+    if targets.iter().all(Option::is_none) {
+      targets.clear();
+    }
+
+    RenderPipelineDescriptor {
+      label: Some("chunk_indirect_prepass_pipeline".into()),
+      layout,
+      vertex: VertexState {
+        shader: self.shader.clone(),
+        shader_defs: shader_defs.clone(),
+        buffers: chunk_vertex_buffers(self.quad_layout.is_some()),
+        ..default()
+      },
+      fragment: Some(FragmentState {
+        shader: self.shader.clone(),
+        shader_defs,
+        targets,
+        ..default()
+      }),
+      primitive: PrimitiveState {
+        cull_mode: Some(Face::Back),
+        unclipped_depth: unclipped_depth && self.depth_clip_control_supported,
+        ..default()
+      },
+      depth_stencil: Some(DepthStencilState {
+        format: CORE_3D_DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: CompareFunction::GreaterEqual,
+        stencil: default(),
+        bias: default(),
+      }),
+      multisample: MultisampleState {
+        count: key.msaa_samples(),
+        ..default()
+      },
+      ..default()
+    }
+  }
+}
+
+type DrawChunksIndirectPrepass = (
+  SetItemPipeline,
+  SetPrepassViewBindGroup<0>,
+  SetPrepassViewEmptyBindGroup<1>,
+  DrawChunkBuffers,
+);
+
+/// Adds the chunk buffers to the shadow phase of every cascade of the directional
+/// lights of each view. The chunks in a cascade are culled against its frustum
+/// when the draws are prepared.
+#[allow(clippy::too_many_arguments)]
+fn queue_indirect_shadows(
+  shadow_draw_functions: Res<DrawFunctions<Shadow>>,
+  prepass_pipeline: Res<IndirectPrepassPipeline>,
+  mut pipelines: ResMut<SpecializedRenderPipelines<IndirectPrepassPipeline>>,
+  pipeline_cache: Res<PipelineCache>,
+  buffers: Res<ChunkBuffers>,
+  mut shadow_render_phases: ResMut<ViewBinnedRenderPhases<Shadow>>,
+  light_key_cache: Res<LightKeyCache>,
+  view_lights: Query<&ViewLightEntities, With<ExtractedView>>,
+  view_light_entities: Query<(&LightEntity, &ExtractedView)>,
+  ticks: SystemChangeTick,
+) {
+  if buffers.is_empty() {
+    return;
+  }
+  let draw_function = shadow_draw_functions
+    .read()
+    .id::<DrawChunksIndirectPrepass>();
+
+  for view_lights in &view_lights {
+    for &view_light_entity in &view_lights.lights {
+      let Ok((LightEntity::Directional { .. }, light_view)) =
+        view_light_entities.get(view_light_entity)
+      else {
+        continue;
+      };
+      let Some(shadow_phase) = shadow_render_phases.get_mut(&light_view.retained_view_entity)
+      else {
+        continue;
+      };
+      let Some(&light_key) = light_key_cache.get(&light_view.retained_view_entity) else {
+        continue;
+      };
+      let pipeline = pipelines.specialize(&pipeline_cache, &prepass_pipeline, light_key);
+
+      // all chunks are drawn by a single item that is not tied to any entity
+      shadow_phase.add(
+        ShadowBatchSetKey {
+          pipeline,
+          draw_function,
+          material_bind_group_index: None,
+          vertex_slab: default(),
+          index_slab: None,
+        },
+        ShadowBinKey {
+          asset_id: AssetId::<Mesh>::invalid().untyped(),
+        },
+        (view_light_entity, MainEntity::from(Entity::PLACEHOLDER)),
+        InputUniformIndex::default(),
+        BinnedRenderPhaseType::NonMesh,
+        ticks.this_run(),
+      );
+    }
+  }
+}
+
+/// Adds the chunk buffers to the depth and normal prepass of each view. Views with
+/// motion vector or deferred prepasses are skipped, their targets aren't written
+/// by the prepass shader.
+#[allow(clippy::too_many_arguments)]
+fn queue_indirect_prepass(
+  prepass_draw_functions: Res<DrawFunctions<Opaque3dPrepass>>,
+  prepass_pipeline: Res<IndirectPrepassPipeline>,
+  mut pipelines: ResMut<SpecializedRenderPipelines<IndirectPrepassPipeline>>,
+  pipeline_cache: Res<PipelineCache>,
+  buffers: Res<ChunkBuffers>,
+  mut prepass_render_phases: ResMut<ViewBinnedRenderPhases<Opaque3dPrepass>>,
+  view_key_cache: Res<ViewKeyPrepassCache>,
+  views: Query<(Entity, &ExtractedView)>,
+  ticks: SystemChangeTick,
+) {
+  if buffers.is_empty() {
+    return;
+  }
+  let draw_function = prepass_draw_functions
+    .read()
+    .id::<DrawChunksIndirectPrepass>();
+
+  for (view_entity, view) in &views {
+    let Some(prepass_phase) = prepass_render_phases.get_mut(&view.retained_view_entity) else {
+      continue;
+    };
+    let Some(&view_key) = view_key_cache.get(&view.retained_view_entity) else {
+      continue;
+    };
+    if view_key
+      .intersects(MeshPipelineKey::MOTION_VECTOR_PREPASS | MeshPipelineKey::DEFERRED_PREPASS)
+    {
+      continue;
+    }
+    let pipeline = pipelines.specialize(&pipeline_cache, &prepass_pipeline, view_key);
+
+    prepass_phase.add(
+      OpaqueNoLightmap3dBatchSetKey {
+        pipeline,
+        draw_function,
+        material_bind_group_index: None,
+        vertex_slab: default(),
+        index_slab: None,
+      },
+      OpaqueNoLightmap3dBinKey {
+        asset_id: AssetId::<Mesh>::invalid().untyped(),
+      },
+      (view_entity, MainEntity::from(Entity::PLACEHOLDER)),
+      InputUniformIndex::default(),
+      BinnedRenderPhaseType::NonMesh,
+      ticks.this_run(),
+    );
+  }
+}
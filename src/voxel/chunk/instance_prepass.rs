@@ -0,0 +1,323 @@
+use bevy::{
+  core_pipeline::{
+    core_3d::CORE_3D_DEPTH_FORMAT,
+    prepass::{
+      Opaque3dPrepass, OpaqueNoLightmap3dBatchSetKey, OpaqueNoLightmap3dBinKey,
+      prepass_target_descriptors,
+    },
+  },
+  ecs::system::SystemChangeTick,
+  mesh::MeshVertexBufferLayoutRef,
+  pbr::{
+    ExtractedDirectionalLight, LightEntity, LightKeyCache, MeshPipelineKey, PrepassPipeline,
+    RenderCascadesVisibleEntities, RenderMeshInstanceFlags, RenderMeshInstances,
+    SetPrepassViewBindGroup, SetPrepassViewEmptyBindGroup, Shadow, ShadowBatchSetKey, ShadowBinKey,
+    ViewKeyPrepassCache, ViewLightEntities, init_prepass_pipeline,
+  },
+  prelude::*,
+  render::{
+    Render, RenderApp, RenderStartup, RenderSystems,
+    mesh::RenderMesh,
+    render_asset::RenderAssets,
+    render_phase::{
+      AddRenderCommand, BinnedRenderPhaseType, DrawFunctions, InputUniformIndex, SetItemPipeline,
+      ViewBinnedRenderPhases,
+    },
+    render_resource::{
+      BindGroupLayout, CompareFunction, DepthStencilState, FragmentState, MultisampleState,
+      PipelineCache, PrimitiveState, RenderPipelineDescriptor, SpecializedMeshPipeline,
+      SpecializedMeshPipelineError, SpecializedMeshPipelines, VertexState,
+    },
+    view::{ExtractedView, RenderVisibleEntities},
+  },
+};
+
+use crate::voxel::chunk::material::{
+  DrawMeshInstanced, INSTANCE_SHADER_PATH, InstanceMaterialData, instance_buffer_layout,
+};
+
+/// Draws the instanced meshes of the [`ChunkMaterialPlugin`](super::ChunkMaterialPlugin)
+/// into the cascaded shadow maps of directional lights and the depth and normal
+/// prepass, with the `PREPASS_PIPELINE` variant of `chunk_instance.wgsl`.
+pub(super) struct InstancePrepassPlugin;
+
+impl Plugin for InstancePrepassPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .sub_app_mut(RenderApp)
+      .add_render_command::<Shadow, DrawInstancePrepass>()
+      .add_render_command::<Opaque3dPrepass, DrawInstancePrepass>()
+      .init_resource::<SpecializedMeshPipelines<InstancePrepassPipeline>>()
+      .add_systems(
+        RenderStartup,
+        init_instance_prepass_pipeline.after(init_prepass_pipeline),
+      )
+      .add_systems(
+        Render,
+        (queue_instance_shadows, queue_instance_prepass).in_set(RenderSystems::QueueMeshes),
+      );
+  }
+}
+
+/// Depth only pipeline of instanced meshes, with normals for the normal prepass.
+/// The instanced shader only reads the view, so there's no mesh bind group.
+#[derive(Resource)]
+struct InstancePrepassPipeline {
+  shader: Handle<Shader>,
+  view_layout: BindGroupLayout,
+  empty_layout: BindGroupLayout,
+  depth_clip_control_supported: bool,
+}
+
+fn init_instance_prepass_pipeline(
+  mut commands: Commands,
+  asset_server: Res<AssetServer>,
+  prepass_pipeline: Res<PrepassPipeline>,
+) {
+  commands.insert_resource(InstancePrepassPipeline {
+    shader: asset_server.load(INSTANCE_SHADER_PATH),
+    view_layout: prepass_pipeline.view_layout_no_motion_vectors.clone(),
+    empty_layout: prepass_pipeline.empty_layout.clone(),
+    depth_clip_control_supported: prepass_pipeline.depth_clip_control_supported,
+  });
+}
+
+impl SpecializedMeshPipeline for InstancePrepassPipeline {
+  type Key = MeshPipelineKey;
+
+  fn specialize(
+    &self,
+    key: Self::Key,
+    layout: &MeshVertexBufferLayoutRef,
+  ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+    let mut shader_defs = vec!["PREPASS_PIPELINE".into()];
+    let mut attributes = vec![Mesh::ATTRIBUTE_POSITION.at_shader_location(0)];
+
+    let normal_prepass = key.contains(MeshPipelineKey::NORMAL_PREPASS);
+    if normal_prepass {
+      shader_defs.push("NORMAL_PREPASS".into());
+      attributes.push(Mesh::ATTRIBUTE_NORMAL.at_shader_location(1));
+    }
+    // shadow maps of directional lights don't clip depth, in the fragment shader
+    // where the GPU can't turn depth clipping off
+    let unclipped_depth = key.contains(MeshPipelineKey::UNCLIPPED_DEPTH_ORTHO);
+    let emulate_unclipped_depth = unclipped_depth && !self.depth_clip_control_supported;
+    if emulate_unclipped_depth {
+      shader_defs.push("UNCLIPPED_DEPTH_ORTHO_EMULATION".into());
+    }
+    let fragment_required = normal_prepass || emulate_unclipped_depth;
+    if fragment_required {
+      shader_defs.push("PREPASS_FRAGMENT".into());
+    }
+
+    let mut targets = prepass_target_descriptors(normal_prepass, false, false);
+    if targets.iter().all(Option::is_none) {
+      targets.clear();
+    }
+
+    Ok(RenderPipelineDescriptor {
+      label: Some("chunk_instance_prepass_pipeline".into()),
+      layout: vec![self.view_layout.clone(), self.empty_layout.clone()],
+      vertex: VertexState {
+        shader: self.shader.clone(),
+        shader_defs: shader_defs.clone(),
+        buffers: vec![layout.0.get_layout(&attributes)?, instance_buffer_layout()],
+        ..default()
+      },
+      fragment: fragment_required.then(|| FragmentState {
+        shader: self.shader.clone(),
+        shader_defs,
+        targets,
+        ..default()
+      }),
+      primitive: PrimitiveState {
+        topology: key.primitive_topology(),
+        unclipped_depth: unclipped_depth && self.depth_clip_control_supported,
+        // blades of grass are single quads seen from both sides
+        cull_mode: None,
+        ..default()
+      },
+      depth_stencil: Some(DepthStencilState {
+        format: CORE_3D_DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: CompareFunction::GreaterEqual,
+        stencil: default(),
+        bias: default(),
+      }),
+      multisample: MultisampleState {
+        count: key.msaa_samples(),
+        ..default()
+      },
+      ..default()
+    })
+  }
+}
+
+type DrawInstancePrepass = (
+  SetItemPipeline,
+  SetPrepassViewBindGroup<0>,
+  SetPrepassViewEmptyBindGroup<1>,
+  DrawMeshInstanced,
+);
+
+/// Adds the shadow casting instanced meshes in every cascade of the directional
+/// lights of each view to its shadow phase.
+#[allow(clippy::too_many_arguments)]
+fn queue_instance_shadows(
+  shadow_draw_functions: Res<DrawFunctions<Shadow>>,
+  prepass_pipeline: Res<InstancePrepassPipeline>,
+  mut pipelines: ResMut<SpecializedMeshPipelines<InstancePrepassPipeline>>,
+  pipeline_cache: Res<PipelineCache>,
+  meshes: Res<RenderAssets<RenderMesh>>,
+  render_mesh_instances: Res<RenderMeshInstances>,
+  instanced_meshes: Query<(), With<InstanceMaterialData>>,
+  mut shadow_render_phases: ResMut<ViewBinnedRenderPhases<Shadow>>,
+  light_key_cache: Res<LightKeyCache>,
+  view_lights: Query<(Entity, &ViewLightEntities), With<ExtractedView>>,
+  view_light_entities: Query<(&LightEntity, &ExtractedView)>,
+  directional_lights: Query<&RenderCascadesVisibleEntities, With<ExtractedDirectionalLight>>,
+  ticks: SystemChangeTick,
+) {
+  let draw_function = shadow_draw_functions.read().id::<DrawInstancePrepass>();
+
+  for (view_entity, view_lights) in &view_lights {
+    for &view_light_entity in &view_lights.lights {
+      let Ok((
+        &LightEntity::Directional {
+          light_entity,
+          cascade_index,
+        },
+        light_view,
+      )) = view_light_entities.get(view_light_entity)
+      else {
+        continue;
+      };
+      let Some(shadow_phase) = shadow_render_phases.get_mut(&light_view.retained_view_entity)
+      else {
+        continue;
+      };
+      let Some(&light_key) = light_key_cache.get(&light_view.retained_view_entity) else {
+        continue;
+      };
+      let Some(visible_entities) = directional_lights
+        .get(light_entity)
+        .ok()
+        .and_then(|cascades| cascades.entities.get(&view_entity))
+        .and_then(|cascades| cascades.get(cascade_index))
+      else {
+        continue;
+      };
+
+      for &(entity, main_entity) in &visible_entities.entities {
+        if !instanced_meshes.contains(entity) {
+          continue;
+        }
+        let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(main_entity) else {
+          continue;
+        };
+        if !mesh_instance
+          .flags
+          .contains(RenderMeshInstanceFlags::SHADOW_CASTER)
+        {
+          continue;
+        }
+        let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+          continue;
+        };
+        let key = light_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
+        let Ok(pipeline) =
+          pipelines.specialize(&pipeline_cache, &prepass_pipeline, key, &mesh.layout)
+        else {
+          continue;
+        };
+
+        // every instanced mesh is drawn on its own by `DrawMeshInstanced`
+        shadow_phase.add(
+          ShadowBatchSetKey {
+            pipeline,
+            draw_function,
+            material_bind_group_index: None,
+            vertex_slab: default(),
+            index_slab: None,
+          },
+          ShadowBinKey {
+            asset_id: mesh_instance.mesh_asset_id.into(),
+          },
+          (entity, main_entity),
+          InputUniformIndex::default(),
+          BinnedRenderPhaseType::NonMesh,
+          ticks.this_run(),
+        );
+      }
+    }
+  }
+}
+
+/// Adds the visible instanced meshes to the depth and normal prepass of each view.
+/// Views with motion vector or deferred prepasses are skipped, their targets
+/// aren't written by the instanced shader.
+#[allow(clippy::too_many_arguments)]
+fn queue_instance_prepass(
+  prepass_draw_functions: Res<DrawFunctions<Opaque3dPrepass>>,
+  prepass_pipeline: Res<InstancePrepassPipeline>,
+  mut pipelines: ResMut<SpecializedMeshPipelines<InstancePrepassPipeline>>,
+  pipeline_cache: Res<PipelineCache>,
+  meshes: Res<RenderAssets<RenderMesh>>,
+  render_mesh_instances: Res<RenderMeshInstances>,
+  instanced_meshes: Query<(), With<InstanceMaterialData>>,
+  mut prepass_render_phases: ResMut<ViewBinnedRenderPhases<Opaque3dPrepass>>,
+  view_key_cache: Res<ViewKeyPrepassCache>,
+  views: Query<(&ExtractedView, &RenderVisibleEntities)>,
+  ticks: SystemChangeTick,
+) {
+  let draw_function = prepass_draw_functions.read().id::<DrawInstancePrepass>();
+
+  for (view, visible_entities) in &views {
+    let Some(prepass_phase) = prepass_render_phases.get_mut(&view.retained_view_entity) else {
+      continue;
+    };
+    let Some(&view_key) = view_key_cache.get(&view.retained_view_entity) else {
+      continue;
+    };
+    if view_key
+      .intersects(MeshPipelineKey::MOTION_VECTOR_PREPASS | MeshPipelineKey::DEFERRED_PREPASS)
+    {
+      continue;
+    }
+
+    for &(entity, main_entity) in visible_entities.iter::<Mesh3d>() {
+      if !instanced_meshes.contains(entity) {
+        continue;
+      }
+      let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(main_entity) else {
+        continue;
+      };
+      let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+        continue;
+      };
+      let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology());
+      let Ok(pipeline) =
+        pipelines.specialize(&pipeline_cache, &prepass_pipeline, key, &mesh.layout)
+      else {
+        continue;
+      };
+
+      prepass_phase.add(
+        OpaqueNoLightmap3dBatchSetKey {
+          pipeline,
+          draw_function,
+          material_bind_group_index: None,
+          vertex_slab: default(),
+          index_slab: None,
+        },
+        OpaqueNoLightmap3dBinKey {
+          asset_id: mesh_instance.mesh_asset_id.into(),
+        },
+        (entity, main_entity),
+        InputUniformIndex::default(),
+        BinnedRenderPhaseType::NonMesh,
+        ticks.this_run(),
+      );
+    }
+  }
+}
@@ -0,0 +1,32 @@
+use bevy::{math::USizeVec3, prelude::*};
+
+use crate::voxel::chunk::{
+  material::{InstanceData, InstanceMaterialData},
+  props::{PropKind, PropMeshes, pack_prop},
+};
+
+/// Spawns a grid of flowers drawn through the instanced path of the
+/// [`ChunkMaterialPlugin`](super::ChunkMaterialPlugin), one instance per rotation
+/// and variant.
+///
+/// Opt-in with `--instancing-example`, as a starting point for instanced drawing.
+pub struct InstancingExamplePlugin;
+
+impl Plugin for InstancingExamplePlugin {
+  fn build(&self, app: &mut App) {
+    app.add_systems(Startup, spawn_instanced_flowers);
+  }
+}
+
+fn spawn_instanced_flowers(mut commands: Commands, prop_meshes: Res<PropMeshes>) {
+  let instances = (0..16)
+    .map(|i| InstanceData {
+      data: pack_prop(USizeVec3::new(i % 4 * 2, 0, i / 4 * 2), i as u32, i as u32),
+    })
+    .collect();
+  commands.spawn((
+    Mesh3d(prop_meshes.get(PropKind::Flower)),
+    Transform::from_translation(Vec3::new(0.0, 1.0, 0.0)),
+    InstanceMaterialData(instances),
+  ));
+}
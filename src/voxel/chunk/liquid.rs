@@ -0,0 +1,210 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+  asset::RenderAssetUsages,
+  image::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+  mesh::MeshVertexBufferLayoutRef,
+  pbr::{MaterialPipeline, MaterialPipelineKey},
+  prelude::*,
+  render::render_resource::{
+    AsBindGroup, Extent3d, RenderPipelineDescriptor, SpecializedMeshPipelineError,
+    TextureDimension, TextureFormat,
+  },
+  shader::ShaderRef,
+};
+
+use crate::voxel::chunk::{block, mesh::LIQUID_ATTRIBUTE};
+
+const SHADER_PATH: &str = "shaders/liquid.wgsl";
+
+/// Side length of the generated normal map in pixels.
+const NORMAL_MAP_SIZE: u32 = 256;
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct LiquidMaterial {
+  /// Color of a thin layer of liquid, the alpha is its opacity.
+  #[uniform(0)]
+  pub shallow_color: LinearRgba,
+  /// Color the liquid approaches the deeper it gets.
+  #[uniform(0)]
+  pub deep_color: LinearRgba,
+  /// Light emitted independently of the scene lighting.
+  #[uniform(0)]
+  pub emissive: LinearRgba,
+  /// How quickly light is absorbed, per block of depth.
+  #[uniform(0)]
+  pub absorption: f32,
+  #[uniform(0)]
+  pub wave_amplitude: f32,
+  #[uniform(0)]
+  pub wave_length: f32,
+  #[uniform(0)]
+  pub wave_speed: f32,
+  /// Speed of the normal map layers in texture repeats per second.
+  #[uniform(0)]
+  pub normal_scroll_speed: f32,
+  #[uniform(0)]
+  pub normal_strength: f32,
+  #[texture(1)]
+  #[sampler(2)]
+  pub normal_map: Handle<Image>,
+}
+
+impl LiquidMaterial {
+  pub fn water(normal_map: Handle<Image>) -> Self {
+    Self {
+      shallow_color: LinearRgba::new(0.1, 0.45, 0.55, 0.25),
+      deep_color: LinearRgba::new(0.01, 0.06, 0.15, 1.0),
+      emissive: LinearRgba::BLACK,
+      absorption: 0.35,
+      wave_amplitude: 0.04,
+      wave_length: 6.0,
+      wave_speed: 1.5,
+      normal_scroll_speed: 0.03,
+      normal_strength: 0.4,
+      normal_map,
+    }
+  }
+
+  pub fn lava(normal_map: Handle<Image>) -> Self {
+    Self {
+      shallow_color: LinearRgba::new(1.0, 0.35, 0.02, 0.9),
+      deep_color: LinearRgba::new(0.6, 0.08, 0.0, 1.0),
+      emissive: LinearRgba::new(4.0, 1.0, 0.1, 1.0),
+      absorption: 4.0,
+      wave_amplitude: 0.02,
+      wave_length: 10.0,
+      wave_speed: 0.3,
+      normal_scroll_speed: 0.005,
+      normal_strength: 0.2,
+      normal_map,
+    }
+  }
+}
+
+impl Material for LiquidMaterial {
+  fn vertex_shader() -> ShaderRef {
+    SHADER_PATH.into()
+  }
+
+  fn fragment_shader() -> ShaderRef {
+    SHADER_PATH.into()
+  }
+
+  fn alpha_mode(&self) -> AlphaMode {
+    AlphaMode::Blend
+  }
+
+  fn specialize(
+    _pipeline: &MaterialPipeline,
+    descriptor: &mut RenderPipelineDescriptor,
+    layout: &MeshVertexBufferLayoutRef,
+    _key: MaterialPipelineKey<Self>,
+  ) -> Result<(), SpecializedMeshPipelineError> {
+    let vertex_layout = layout.0.get_layout(&[
+      Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+      Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+      LIQUID_ATTRIBUTE.at_shader_location(2),
+    ])?;
+
+    descriptor.vertex.buffers = vec![vertex_layout];
+    // the surface has to be visible from below as well
+    descriptor.primitive.cull_mode = None;
+    Ok(())
+  }
+}
+
+/// Shared material handles for every liquid block.
+#[derive(Resource)]
+pub struct LiquidMaterials {
+  pub water: Handle<LiquidMaterial>,
+  pub lava: Handle<LiquidMaterial>,
+}
+
+impl LiquidMaterials {
+  pub fn get(&self, block: u8) -> Option<Handle<LiquidMaterial>> {
+    match block {
+      block::WATER => Some(self.water.clone()),
+      block::LAVA => Some(self.lava.clone()),
+      _ => None,
+    }
+  }
+}
+
+pub struct LiquidMaterialPlugin;
+
+impl Plugin for LiquidMaterialPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_plugins(MaterialPlugin::<LiquidMaterial> {
+        prepass_enabled: false,
+        shadows_enabled: false,
+        ..default()
+      })
+      .add_systems(PreStartup, init_liquid_materials);
+  }
+}
+
+fn init_liquid_materials(
+  mut commands: Commands,
+  mut images: ResMut<Assets<Image>>,
+  mut materials: ResMut<Assets<LiquidMaterial>>,
+) {
+  let normal_map = images.add(create_normal_map());
+
+  commands.insert_resource(LiquidMaterials {
+    water: materials.add(LiquidMaterial::water(normal_map.clone())),
+    lava: materials.add(LiquidMaterial::lava(normal_map)),
+  });
+}
+
+/// Generates a tileable normal map of small ripples from a sum of sine waves.
+///
+/// Every wave has an integer frequency along both axes so the map repeats seamlessly.
+fn create_normal_map() -> Image {
+  // (frequency x, frequency y, amplitude, phase)
+  const WAVES: [(f32, f32, f32, f32); 6] = [
+    (3.0, 1.0, 0.030, 0.0),
+    (-2.0, 4.0, 0.025, 1.3),
+    (5.0, -3.0, 0.015, 2.1),
+    (1.0, 7.0, 0.012, 4.2),
+    (-8.0, -5.0, 0.008, 0.7),
+    (11.0, 4.0, 0.005, 5.5),
+  ];
+
+  let mut data = Vec::with_capacity((NORMAL_MAP_SIZE * NORMAL_MAP_SIZE * 4) as usize);
+  for y in 0..NORMAL_MAP_SIZE {
+    for x in 0..NORMAL_MAP_SIZE {
+      let uv = Vec2::new(x as f32, y as f32) / NORMAL_MAP_SIZE as f32;
+
+      // gradient of the height field
+      let mut gradient = Vec2::ZERO;
+      for (fx, fy, amplitude, phase) in WAVES {
+        let frequency = Vec2::new(fx, fy) * TAU;
+        gradient += frequency * amplitude * (frequency.dot(uv) + phase).cos();
+      }
+
+      let normal = Vec3::new(-gradient.x, -gradient.y, 1.0).normalize();
+      let encoded = (normal * 0.5 + 0.5) * 255.0;
+      data.extend([encoded.x as u8, encoded.y as u8, encoded.z as u8, 255]);
+    }
+  }
+
+  let mut image = Image::new(
+    Extent3d {
+      width: NORMAL_MAP_SIZE,
+      height: NORMAL_MAP_SIZE,
+      depth_or_array_layers: 1,
+    },
+    TextureDimension::D2,
+    data,
+    TextureFormat::Rgba8Unorm,
+    RenderAssetUsages::RENDER_WORLD,
+  );
+  image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+    address_mode_u: ImageAddressMode::Repeat,
+    address_mode_v: ImageAddressMode::Repeat,
+    ..ImageSamplerDescriptor::linear()
+  });
+  image
+}
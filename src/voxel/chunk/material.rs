@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use bevy::{
   core_pipeline::core_3d::Transparent3d,
   ecs::{
@@ -28,20 +30,93 @@ use bevy::{
       SpecializedMeshPipelines, VertexAttribute, VertexStepMode,
     },
     renderer::RenderDevice,
+    storage::ShaderStorageBuffer,
     sync_world::MainEntity,
-    view::ExtractedView,
+    view::{ExtractedView, RenderVisibleEntities},
   },
   shader::ShaderRef,
 };
 use bytemuck::{Pod, Zeroable};
 
-use crate::voxel::chunk::mesh::DATA_ATTRIBUTE;
+use crate::voxel::chunk::{
+  diagnostics::InstanceUploads, fog::ChunkFog, greedy::QUAD_POSITION_ATTRIBUTE,
+  instance_prepass::InstancePrepassPlugin, mesh::DATA_ATTRIBUTE,
+  translucent::sort_translucent_chunks,
+};
 
 const SHADER_PATH: &str = "shaders/chunk.wgsl";
-const PREPASS_SHADER_PATH: &str = "shaders/chunk_prepass.wgsl";
+pub(super) const PREPASS_SHADER_PATH: &str = "shaders/chunk_prepass.wgsl";
+pub(super) const INSTANCE_SHADER_PATH: &str = "shaders/chunk_instance.wgsl";
+
+/// How the mesh of a [`ChunkMaterial`] stores its quads.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChunkMeshLayout {
+  /// A packed [`DATA_ATTRIBUTE`] per vertex, holding the position, size,
+  /// direction and block of its quad.
+  #[default]
+  PackedVertices,
+  /// Greedy merged quads with only the position per vertex, see
+  /// [`greedy_mesh`](super::greedy::greedy_mesh). Block, ambient occlusion and
+  /// light of each quad are read from [`ChunkMaterial::quad_attributes`].
+  GreedyQuads,
+}
+
+impl FromStr for ChunkMeshLayout {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "packed" => Ok(Self::PackedVertices),
+      "greedy" => Ok(Self::GreedyQuads),
+      _ => Err(format!("unknown chunk mesh layout: {s}")),
+    }
+  }
+}
 
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-pub struct ChunkMaterial {}
+#[bind_group_data(ChunkMaterialKey)]
+pub struct ChunkMaterial {
+  /// [`AlphaMode::Mask`] for chunks containing cutout blocks and
+  /// [`AlphaMode::Blend`] for the translucent mesh of a chunk.
+  pub alpha_mode: AlphaMode,
+  pub layout: ChunkMeshLayout,
+  /// One packed `u32` per quad for [`ChunkMeshLayout::GreedyQuads`], see
+  /// [`pack_quad_attributes`](super::greedy::pack_quad_attributes). The default
+  /// handle is a placeholder for the other layout.
+  #[storage(0, read_only)]
+  pub quad_attributes: Handle<ShaderStorageBuffer>,
+  #[uniform(1)]
+  pub fog: ChunkFog,
+  /// Scale of the baked sky light, set by the
+  /// [`TimeOfDayPlugin`](super::TimeOfDayPlugin) to darken the surface at night.
+  #[uniform(2)]
+  pub sky_light: f32,
+}
+
+impl Default for ChunkMaterial {
+  fn default() -> Self {
+    Self {
+      alpha_mode: AlphaMode::Opaque,
+      layout: ChunkMeshLayout::default(),
+      quad_attributes: Handle::default(),
+      fog: ChunkFog::default(),
+      sky_light: 1.0,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkMaterialKey {
+  layout: ChunkMeshLayout,
+}
+
+impl From<&ChunkMaterial> for ChunkMaterialKey {
+  fn from(material: &ChunkMaterial) -> Self {
+    Self {
+      layout: material.layout,
+    }
+  }
+}
 
 impl Material for ChunkMaterial {
   fn fragment_shader() -> ShaderRef {
@@ -53,18 +128,26 @@ impl Material for ChunkMaterial {
   }
 
   fn alpha_mode(&self) -> AlphaMode {
-    AlphaMode::Opaque
+    self.alpha_mode
   }
 
   fn specialize(
     _pipeline: &MaterialPipeline,
     descriptor: &mut RenderPipelineDescriptor,
     layout: &MeshVertexBufferLayoutRef,
-    _key: MaterialPipelineKey<Self>,
+    key: MaterialPipelineKey<Self>,
   ) -> Result<(), SpecializedMeshPipelineError> {
-    let vertex_layout = layout
-      .0
-      .get_layout(&[DATA_ATTRIBUTE.at_shader_location(0)])?;
+    let attribute = match key.bind_group_data.layout {
+      ChunkMeshLayout::PackedVertices => DATA_ATTRIBUTE,
+      ChunkMeshLayout::GreedyQuads => {
+        descriptor.vertex.shader_defs.push("QUAD_STORAGE".into());
+        if let Some(fragment) = &mut descriptor.fragment {
+          fragment.shader_defs.push("QUAD_STORAGE".into());
+        }
+        QUAD_POSITION_ATTRIBUTE
+      }
+    };
+    let vertex_layout = layout.0.get_layout(&[attribute.at_shader_location(0)])?;
 
     //descriptor.primitive.polygon_mode = bevy::render::render_resource::PolygonMode::Line;
     descriptor.vertex.buffers = vec![vertex_layout];
@@ -80,12 +163,16 @@ impl Material for ChunkMaterial {
   }
 }
 
-#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+/// Per-instance data of an instanced mesh, for props packed by
+/// [`pack_prop`](super::props::pack_prop).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
 #[repr(C)]
 pub struct InstanceData {
   pub data: u32,
 }
 
+/// Instances of the mesh of an entity, drawn in a single instanced draw by the
+/// pipeline in `chunk_instance.wgsl`. Only the translation of the entity is applied.
 #[derive(Component, Deref)]
 pub struct InstanceMaterialData(pub Vec<InstanceData>);
 
@@ -99,11 +186,25 @@ impl ExtractComponent for InstanceMaterialData {
   }
 }
 
-pub struct ChunkMaterialPlugin;
+/// Renders chunk meshes with the [`ChunkMaterial`], using `layout` for the opaque
+/// mesh of new chunks, and instanced meshes with an [`InstanceMaterialData`],
+/// including their shadows and prepass.
+#[derive(Default)]
+pub struct ChunkMaterialPlugin {
+  pub layout: ChunkMeshLayout,
+}
 
 impl Plugin for ChunkMaterialPlugin {
   fn build(&self, app: &mut App) {
-    app.add_plugins(ExtractComponentPlugin::<InstanceMaterialData>::default());
+    app
+      .add_plugins((
+        MaterialPlugin::<ChunkMaterial>::default(),
+        ExtractComponentPlugin::<InstanceMaterialData>::default(),
+        InstancePrepassPlugin,
+      ))
+      .insert_resource(self.layout)
+      .add_systems(PreStartup, init_quad_attributes_placeholder)
+      .add_systems(Update, sort_translucent_chunks);
     app
       .sub_app_mut(RenderApp)
       .add_render_command::<Transparent3d, DrawChunk>()
@@ -119,6 +220,12 @@ impl Plugin for ChunkMaterialPlugin {
   }
 }
 
+/// Fills the default handle of [`ChunkMaterial::quad_attributes`], storage
+/// bindings can't be empty.
+fn init_quad_attributes_placeholder(mut buffers: ResMut<Assets<ShaderStorageBuffer>>) {
+  let _ = buffers.insert(&Handle::default(), ShaderStorageBuffer::from(vec![0u32]));
+}
+
 #[allow(clippy::too_many_arguments)]
 fn queue_chunk(
   transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
@@ -127,13 +234,18 @@ fn queue_chunk(
   pipeline_cache: Res<PipelineCache>,
   meshes: Res<RenderAssets<RenderMesh>>,
   render_mesh_instances: Res<RenderMeshInstances>,
-  material_meshes: Query<(Entity, &MainEntity), With<InstanceMaterialData>>,
+  material_meshes: Query<(), With<InstanceMaterialData>>,
   mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
-  views: Query<(&ExtractedView, &Msaa)>,
+  views: Query<(
+    &ExtractedView,
+    &RenderVisibleEntities,
+    &Msaa,
+    Has<DistanceFog>,
+  )>,
 ) {
   let draw_chunk = transparent_3d_draw_functions.read().id::<DrawChunk>();
 
-  for (view, msaa) in &views {
+  for (view, visible_entities, msaa, distance_fog) in &views {
     let Some(transparent_phase) = transparent_render_phases.get_mut(&view.retained_view_entity)
     else {
       continue;
@@ -141,11 +253,17 @@ fn queue_chunk(
 
     let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
 
-    let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+    let mut view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+    if distance_fog {
+      view_key |= MeshPipelineKey::DISTANCE_FOG;
+    }
     let rangefinder = view.rangefinder3d();
 
-    for (entity, main_entity) in &material_meshes {
-      let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(*main_entity) else {
+    for &(entity, main_entity) in visible_entities.iter::<Mesh3d>() {
+      if !material_meshes.contains(entity) {
+        continue;
+      }
+      let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(main_entity) else {
         continue;
       };
       let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
@@ -156,7 +274,7 @@ fn queue_chunk(
         .specialize(&pipeline_cache, &chunk_pipeline, key, &mesh.layout)
         .unwrap();
       transparent_phase.add(Transparent3d {
-        entity: (entity, *main_entity),
+        entity: (entity, main_entity),
         pipeline,
         draw_function: draw_chunk,
         distance: rangefinder.distance_translation(&mesh_instance.translation),
@@ -168,30 +286,90 @@ fn queue_chunk(
   }
 }
 
+/// Instance data as uploaded, with the translation of the drawn entity since the
+/// instanced shader doesn't read the mesh uniforms.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct GpuInstanceData {
+  translation: Vec3,
+  data: u32,
+}
+
 #[derive(Component)]
-struct InstanceBuffer {
+pub(super) struct InstanceBuffer {
   buffer: Buffer,
-  length: usize,
+  /// Contents of the buffer, it's only rebuilt when these change.
+  instances: Vec<InstanceData>,
+  translation: Vec3,
 }
 
 fn prepare_instance_buffers(
   mut commands: Commands,
-  query: Query<(Entity, &InstanceMaterialData)>,
+  query: Query<(
+    Entity,
+    &MainEntity,
+    &InstanceMaterialData,
+    Option<&InstanceBuffer>,
+  )>,
+  render_mesh_instances: Res<RenderMeshInstances>,
   render_device: Res<RenderDevice>,
+  uploads: Option<Res<InstanceUploads>>,
 ) {
-  for (entity, instance_data) in &query {
+  for (entity, main_entity, instance_data, instance_buffer) in &query {
+    let Some(mesh_instance) = render_mesh_instances.render_mesh_queue_data(*main_entity) else {
+      continue;
+    };
+    let translation = mesh_instance.translation;
+    if instance_buffer.is_some_and(|instance_buffer| {
+      instance_buffer.translation == translation && instance_buffer.instances == instance_data.0
+    }) {
+      continue;
+    }
+
+    let contents: Vec<_> = instance_data
+      .iter()
+      .map(|instance| GpuInstanceData {
+        translation,
+        data: instance.data,
+      })
+      .collect();
+    if let Some(uploads) = &uploads {
+      uploads.add(size_of_val(contents.as_slice()) as u64);
+    }
     let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
       label: Some("chunk_instance_buffer"),
       usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-      contents: bytemuck::cast_slice(instance_data.as_slice()),
+      contents: bytemuck::cast_slice(&contents),
     });
     commands.entity(entity).insert(InstanceBuffer {
       buffer,
-      length: instance_data.len(),
+      instances: instance_data.0.clone(),
+      translation,
     });
   }
 }
 
+/// Layout of an [`InstanceBuffer`], the locations must match `PropVertex` in
+/// `chunk_instance.wgsl`.
+pub(super) fn instance_buffer_layout() -> VertexBufferLayout {
+  VertexBufferLayout {
+    array_stride: size_of::<GpuInstanceData>() as u64,
+    step_mode: VertexStepMode::Instance,
+    attributes: vec![
+      VertexAttribute {
+        format: VertexFormat::Float32x3,
+        offset: 0,
+        shader_location: 4,
+      },
+      VertexAttribute {
+        format: VertexFormat::Uint32,
+        offset: size_of::<Vec3>() as u64,
+        shader_location: 3,
+      },
+    ],
+  }
+}
+
 #[derive(Resource)]
 struct ChunkPipeline {
   shader: Handle<Shader>,
@@ -204,7 +382,7 @@ fn init_chunk_pipeline(
   mesh_pipeline: Res<MeshPipeline>,
 ) {
   commands.insert_resource(ChunkPipeline {
-    shader: asset_server.load("shaders/chunk_instance.wgsl"),
+    shader: asset_server.load(INSTANCE_SHADER_PATH),
     mesh_pipeline: mesh_pipeline.clone(),
   });
 }
@@ -220,16 +398,10 @@ impl SpecializedMeshPipeline for ChunkPipeline {
     let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
 
     descriptor.vertex.shader = self.shader.clone();
-    descriptor.vertex.buffers.push(VertexBufferLayout {
-      array_stride: size_of::<InstanceData>() as u64,
-      step_mode: VertexStepMode::Instance,
-      attributes: vec![VertexAttribute {
-        format: VertexFormat::Uint32,
-        offset: 0,
-        shader_location: 3,
-      }],
-    });
+    descriptor.vertex.buffers.push(instance_buffer_layout());
     descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
+    // blades of grass are single quads seen from both sides
+    descriptor.primitive.cull_mode = None;
     Ok(descriptor)
   }
 }
@@ -242,7 +414,7 @@ type DrawChunk = (
   DrawMeshInstanced,
 );
 
-struct DrawMeshInstanced;
+pub(super) struct DrawMeshInstanced;
 
 impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
   type Param = (
@@ -296,7 +468,7 @@ impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
         pass.draw_indexed(
           index_buffer_slice.range.start..(index_buffer_slice.range.start + count),
           vertex_buffer_slice.range.start as i32,
-          0..instance_buffer.length as u32,
+          0..instance_buffer.instances.len() as u32,
         );
       }
       RenderMeshBufferInfo::NonIndexed => {
@@ -1,4 +1,10 @@
-use crate::voxel::chunk::{CHUNK_SIZE, CHUNK_SIZE_POW, generation::ChunkBlockData};
+use crate::voxel::chunk::{
+  CHUNK_SIZE, CHUNK_SIZE_POW,
+  block::{self, BlockKind},
+  generation::ChunkBlockData,
+  greedy::GreedyQuads,
+  props::Prop,
+};
 use bevy::{
   asset::RenderAssetUsages,
   math::USizeVec3,
@@ -9,26 +15,292 @@ use bevy::{
 pub const DATA_ATTRIBUTE: MeshVertexAttribute =
   MeshVertexAttribute::new("Quad data", 658854091321, VertexFormat::Uint32);
 
+/// Block id of a liquid vertex, with [`LIQUID_SURFACE_BIT`] set on vertices of the surface.
+pub const LIQUID_ATTRIBUTE: MeshVertexAttribute =
+  MeshVertexAttribute::new("Liquid data", 658854091322, VertexFormat::Uint32);
+
+/// Must match `SURFACE_BIT` in `liquid.wgsl`.
+pub const LIQUID_SURFACE_BIT: u32 = 1 << 4;
+
+/// Height of a liquid source surface that isn't covered by the same liquid.
+const LIQUID_SURFACE_HEIGHT: f32 = 0.875;
+
+/// Axes spanned by the width and height of a quad, indexed by its direction.
+/// Must match `quad_axes` in `chunk_util.wgsl`.
+pub(super) const QUAD_AXES: [(USizeVec3, USizeVec3); 6] = [
+  (USizeVec3::Z, USizeVec3::Y),
+  (USizeVec3::Z, USizeVec3::Y),
+  (USizeVec3::X, USizeVec3::Z),
+  (USizeVec3::X, USizeVec3::Z),
+  (USizeVec3::X, USizeVec3::Y),
+  (USizeVec3::X, USizeVec3::Y),
+];
+
+/// Maximum number of opaque and cutout quads a single chunk can produce, every
+/// face of every block. Faces next to cutout blocks are always visible, so a
+/// chunk full of leaves reaches it.
+pub const MAX_CHUNK_QUADS: u32 = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 6) as u32;
+
 pub struct ChunkMeshData {
-  pub mesh: Mesh,
+  /// One packed quad per visible opaque or cutout face, see [`pack_quad`].
+  pub quads: Vec<u32>,
+  /// One packed quad per visible translucent face.
+  pub translucent_quads: Vec<u32>,
+  pub liquid_faces: Vec<LiquidFace>,
+  /// Props still standing on the blocks of the chunk.
+  pub props: Vec<Prop>,
+  /// `quads` merged for the [`ChunkMeshLayout::GreedyQuads`](super::material::ChunkMeshLayout)
+  /// layout, if the chunk is drawn with it.
+  pub greedy_quads: Option<GreedyQuads>,
   pub chunk_pos: IVec3,
 }
 
+/// A visible face of a liquid block.
+#[derive(Clone, Copy, Debug)]
+pub struct LiquidFace {
+  /// Position of the block in chunk space.
+  pub pos: USizeVec3,
+  pub dir: u32,
+  /// Block id of the liquid, without its level.
+  pub block: u8,
+  /// Height of the liquid surface within the block.
+  pub height: f32,
+  /// Height the bottom edge of a side face is raised to, where it borders lower
+  /// liquid of the same kind.
+  pub bottom: f32,
+}
+
+impl ChunkMeshData {
+  /// Number of quads drawn for the opaque and translucent meshes.
+  pub fn quad_count(&self) -> usize {
+    let opaque = match &self.greedy_quads {
+      Some(greedy) => greedy.quads.len(),
+      None => self.quads.len(),
+    };
+    opaque + self.translucent_quads.len()
+  }
+
+  /// Whether any of the opaque quads belongs to an alpha tested block.
+  pub fn has_cutout(&self) -> bool {
+    self
+      .quads
+      .iter()
+      .any(|&quad| block::block_kind(unpack_block(quad)) == BlockKind::Cutout)
+  }
+}
+
+/// Builds a mesh with one [`DATA_ATTRIBUTE`] per vertex from packed quads.
+pub fn quads_mesh(quads: &[u32]) -> Mesh {
+  let (vertices, indices) = expand_quads(quads);
+
+  let mut mesh = Mesh::new(
+    PrimitiveTopology::TriangleList,
+    RenderAssetUsages::RENDER_WORLD,
+  );
+
+  mesh.insert_attribute(DATA_ATTRIBUTE, vertices);
+  mesh.insert_indices(Indices::U32(indices));
+  mesh
+}
+
+/// Expands every quad into four packed vertices and six indices.
+pub fn expand_quads(quads: &[u32]) -> (Vec<u32>, Vec<u32>) {
+  let mut vertices = Vec::with_capacity(quads.len() * 4);
+  let mut indices = Vec::with_capacity(quads.len() * 6);
+
+  for &quad in quads {
+    let (base, width, height, dir) = unpack_quad(quad);
+    let (dir1, dir2) = QUAD_AXES[dir as usize];
+
+    indices.extend(quad_indices(vertices.len() as u32, dir));
+
+    for corner in quad_corners(dir1 * width as usize, dir2 * height as usize) {
+      vertices.push(pack_quad(
+        base + corner,
+        width,
+        height,
+        dir,
+        unpack_block(quad),
+      ));
+    }
+  }
+
+  (vertices, indices)
+}
+
+/// Builds a mesh for faces of a single liquid, with positions, normals and a
+/// [`LIQUID_ATTRIBUTE`] per vertex.
+pub fn liquid_mesh(faces: &[LiquidFace]) -> Mesh {
+  let mut positions = Vec::with_capacity(faces.len() * 4);
+  let mut normals = Vec::with_capacity(faces.len() * 4);
+  let mut data = Vec::with_capacity(faces.len() * 4);
+  let mut indices = Vec::with_capacity(faces.len() * 6);
+
+  for face in faces {
+    let (dir1, dir2) = QUAD_AXES[face.dir as usize];
+    let base = face.pos + face_offset(face.dir);
+    let normal = face_normal(face.dir);
+
+    indices.extend(quad_indices(positions.len() as u32, face.dir));
+
+    for corner in quad_corners(dir1, dir2) {
+      let mut position = (base + corner).as_vec3();
+      let mut vertex_data = face.block as u32;
+      // lower the top edge of the block to the liquid surface
+      if position.y > face.pos.y as f32 {
+        position.y = face.pos.y as f32 + face.height;
+        if face.height < 1.0 {
+          vertex_data |= LIQUID_SURFACE_BIT;
+        }
+      } else {
+        position.y += face.bottom;
+      }
+
+      positions.push(position);
+      normals.push(normal);
+      data.push(vertex_data);
+    }
+  }
+
+  let mut mesh = Mesh::new(
+    PrimitiveTopology::TriangleList,
+    RenderAssetUsages::RENDER_WORLD,
+  );
+
+  mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+  mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+  mesh.insert_attribute(LIQUID_ATTRIBUTE, data);
+  mesh.insert_indices(Indices::U32(indices));
+  mesh
+}
+
+/// Indices of the two triangles of a quad whose first vertex is `start`, wound
+/// counter-clockwise when looking at the quad from direction `dir`.
+#[inline]
+pub(super) fn quad_indices(start: u32, dir: u32) -> [u32; 6] {
+  if dir == 2 || dir == 5 || dir == 0 {
+    [start, start + 3, start + 2, start + 2, start + 1, start]
+  } else {
+    [start, start + 2, start + 3, start + 1, start + 2, start]
+  }
+}
+
+/// Corners of a quad spanned by `dir1` and `dir2`, relative to its base.
+#[inline]
+pub(super) fn quad_corners(dir1: USizeVec3, dir2: USizeVec3) -> [USizeVec3; 4] {
+  [USizeVec3::ZERO, dir1, dir1 + dir2, dir2]
+}
+
+/// Normal of a face in direction `dir`, must match `normals` in `chunk_util.wgsl`.
+#[inline]
+pub fn face_normal(dir: u32) -> Vec3 {
+  match dir {
+    0 => Vec3::X,
+    1 => Vec3::NEG_X,
+    2 => Vec3::Y,
+    3 => Vec3::NEG_Y,
+    4 => Vec3::Z,
+    _ => Vec3::NEG_Z,
+  }
+}
+
+/// Offset from a block to the base of its face in direction `dir`.
+#[inline]
+fn face_offset(dir: u32) -> USizeVec3 {
+  match dir {
+    0 => USizeVec3::X,
+    2 => USizeVec3::Y,
+    4 => USizeVec3::Z,
+    _ => USizeVec3::ZERO,
+  }
+}
+
+/// Translation of the meshes of the chunk at `chunk_pos`. Their quads are in the
+/// padded chunk space, which starts one block before the chunk, so padded
+/// positions line up with the world blocks of [`VoxelWorld`](super::VoxelWorld).
+#[inline]
+pub fn chunk_translation(chunk_pos: IVec3) -> Vec3 {
+  (chunk_pos * CHUNK_SIZE as i32 - IVec3::ONE).as_vec3()
+}
+
+/// Center of a packed quad in chunk space.
+pub fn quad_center(quad: u32) -> Vec3 {
+  let (base, width, height, dir) = unpack_quad(quad);
+  let (dir1, dir2) = QUAD_AXES[dir as usize];
+
+  base.as_vec3() + (dir1.as_vec3() * width as f32 + dir2.as_vec3() * height as f32) * 0.5
+}
+
+/// Packs a quad as `xxxxxyyyyyzzzzzwwwwwhhhhhbbbbddd`, matching `unpack` in `chunk_util.wgsl`.
+#[inline]
+pub fn pack_quad(pos: USizeVec3, width: u32, height: u32, dir: u32, block: u8) -> u32 {
+  let chunk_size_mask: u32 = (1 << CHUNK_SIZE_POW) - 1;
+
+  (pos.x as u32 & chunk_size_mask) << (32 - CHUNK_SIZE_POW)
+    | ((pos.y as u32 & chunk_size_mask) << (32 - 2 * CHUNK_SIZE_POW))
+    | ((pos.z as u32 & chunk_size_mask) << (32 - 3 * CHUNK_SIZE_POW))
+    | ((width & chunk_size_mask) << (32 - 4 * CHUNK_SIZE_POW))
+    | ((height & chunk_size_mask) << (32 - 5 * CHUNK_SIZE_POW))
+    | ((block as u32 & 15) << 3)
+    | (dir & 7)
+}
+
+/// Block id of a packed quad.
+#[inline]
+pub fn unpack_block(data: u32) -> u8 {
+  ((data >> 3) & 15) as u8
+}
+
+/// Inverse of [`pack_quad`], returns the position, width, height and direction.
+#[inline]
+pub fn unpack_quad(data: u32) -> (USizeVec3, u32, u32, u32) {
+  let chunk_size_mask: u32 = (1 << CHUNK_SIZE_POW) - 1;
+  let field = |i: usize| (data >> (32 - i * CHUNK_SIZE_POW)) & chunk_size_mask;
+
+  (
+    USizeVec3::new(field(1) as usize, field(2) as usize, field(3) as usize),
+    field(4),
+    field(5),
+    data & 7,
+  )
+}
+
 impl ChunkBlockData {
-  pub fn create_mesh(self) -> ChunkMeshData {
-    let mut plain_data = Vec::new();
-    let mut indices = Vec::new();
+  /// Surface height of the liquid at `pos`, full height if it's covered by the same liquid.
+  fn liquid_height(&self, pos: USizeVec3) -> f32 {
+    let block = self.get(pos);
+    let covered =
+      pos.y + 1 < CHUNK_SIZE + 2 && !block::face_visible(block, self.get(pos + USizeVec3::Y));
+    if covered {
+      1.0
+    } else {
+      let level = block::liquid_level(block) as f32;
+      LIQUID_SURFACE_HEIGHT * (block::MAX_LIQUID_LEVEL as f32 + 1.0 - level)
+        / (block::MAX_LIQUID_LEVEL as f32 + 1.0)
+    }
+  }
 
-    let chunk_size_mask: u32 = (1 << CHUNK_SIZE_POW) - 1;
+  pub fn create_mesh(&self) -> ChunkMeshData {
+    let mut quads = Vec::new();
+    let mut translucent_quads = Vec::new();
+    let mut liquid_faces = Vec::new();
 
     for x in 1..CHUNK_SIZE + 1 {
       for y in 1..CHUNK_SIZE + 1 {
         for z in 1..CHUNK_SIZE + 1 {
           let pos = USizeVec3::new(x, y, z);
-          if self.empty(pos) {
+          let block = self.get(pos);
+          if block == block::AIR {
             continue;
           }
 
+          let kind = block::block_kind(block);
+          let height = if kind == BlockKind::Liquid {
+            self.liquid_height(pos)
+          } else {
+            1.0
+          };
+
           for dir in 0..6 {
             let neighbor_pos = match dir {
               0 => USizeVec3::new(x + 1, y, z),
@@ -40,61 +312,40 @@ impl ChunkBlockData {
               _ => unreachable!(),
             };
 
-            if neighbor_pos.x >= CHUNK_SIZE + 2
+            let outside = neighbor_pos.x >= CHUNK_SIZE + 2
               || neighbor_pos.y >= CHUNK_SIZE + 2
-              || neighbor_pos.z >= CHUNK_SIZE + 2
-              || self.empty(neighbor_pos)
-            {
-              let (base, dir1, dir2) = match dir {
-                0 => (pos + USizeVec3::X, USizeVec3::Z, USizeVec3::Y),
-                1 => (pos, USizeVec3::Z, USizeVec3::Y),
-                2 => (pos + USizeVec3::Y, USizeVec3::X, USizeVec3::Z),
-                3 => (pos, USizeVec3::X, USizeVec3::Z),
-                4 => (pos + USizeVec3::Z, USizeVec3::X, USizeVec3::Y),
-                5 => (pos, USizeVec3::X, USizeVec3::Y),
-                _ => unreachable!(),
-              };
-
-              let start_index = plain_data.len() as u32;
-              indices.push(start_index);
-              if dir == 2 || dir == 5 || dir == 0 {
-                indices.push(start_index + 3);
-                indices.push(start_index + 2);
-                indices.push(start_index + 2);
-                indices.push(start_index + 1);
-              } else {
-                indices.push(start_index + 2);
-                indices.push(start_index + 3);
-                indices.push(start_index + 1);
-                indices.push(start_index + 2);
+              || neighbor_pos.z >= CHUNK_SIZE + 2;
+
+            // liquid flowing down next to the same liquid shows the step between both surfaces
+            if !outside && kind == BlockKind::Liquid && dir != 2 && dir != 3 {
+              let neighbor = self.get(neighbor_pos);
+              if block::id(neighbor) == block::id(block) {
+                let bottom = self.liquid_height(neighbor_pos);
+                if bottom < height {
+                  liquid_faces.push(LiquidFace {
+                    pos,
+                    dir,
+                    block: block::id(block),
+                    height,
+                    bottom,
+                  });
+                }
+                continue;
               }
-              indices.push(start_index);
-
-              for i in 0..4 {
-                let offset = match i {
-                  0 => USizeVec3::ZERO,
-                  1 => dir1,
-                  2 => dir1 + dir2,
-                  3 => dir2,
-                  _ => unreachable!(),
-                };
-
-                let vertex_pos = base + offset;
-
-                let x = vertex_pos.x as u32;
-                let y = vertex_pos.y as u32;
-                let z = vertex_pos.z as u32;
-                let width = 1;
-                let height = 1;
-
-                let data: u32 = (x & chunk_size_mask) << (32 - CHUNK_SIZE_POW)
-                  | ((y & chunk_size_mask) << (32 - 2 * CHUNK_SIZE_POW))
-                  | ((z & chunk_size_mask) << (32 - 3 * CHUNK_SIZE_POW))
-                  | ((width & chunk_size_mask) << (32 - 4 * CHUNK_SIZE_POW))
-                  | ((height & chunk_size_mask) << (32 - 5 * CHUNK_SIZE_POW))
-                  | (dir & 7);
-
-                plain_data.push(data);
+            }
+
+            if outside || block::face_visible(block, self.get(neighbor_pos)) {
+              let quad = pack_quad(pos + face_offset(dir), 1, 1, dir, block);
+              match kind {
+                BlockKind::Translucent => translucent_quads.push(quad),
+                BlockKind::Liquid => liquid_faces.push(LiquidFace {
+                  pos,
+                  dir,
+                  block: block::id(block),
+                  height,
+                  bottom: 0.0,
+                }),
+                _ => quads.push(quad),
               }
             }
           }
@@ -102,17 +353,36 @@ impl ChunkBlockData {
       }
     }
 
-    let mut mesh = Mesh::new(
-      PrimitiveTopology::TriangleList,
-      RenderAssetUsages::RENDER_WORLD,
-    );
-
-    mesh.insert_attribute(DATA_ATTRIBUTE, plain_data);
-    mesh.insert_indices(Indices::U32(indices));
-
     ChunkMeshData {
-      mesh,
+      quads,
+      translucent_quads,
+      liquid_faces,
+      props: self.standing_props().collect(),
+      greedy_quads: None,
       chunk_pos: self.chunk_pos,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn chunk_full_of_cutout_blocks_stays_within_max_quads() {
+    let mut chunk = ChunkBlockData::flat(IVec3::ZERO, i32::MIN);
+    for x in 0..CHUNK_SIZE + 2 {
+      for y in 0..CHUNK_SIZE + 2 {
+        for z in 0..CHUNK_SIZE + 2 {
+          chunk.set(USizeVec3::new(x, y, z), block::LEAVES);
+        }
+      }
+    }
+
+    let mesh = chunk.create_mesh();
+    assert!(mesh.translucent_quads.is_empty());
+    assert!(mesh.quads.len() <= MAX_CHUNK_QUADS as usize);
+    // every face of every block is visible
+    assert_eq!(mesh.quads.len(), MAX_CHUNK_QUADS as usize);
+  }
+}
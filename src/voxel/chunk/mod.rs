@@ -1,46 +1,48 @@
-use bevy::prelude::*;
-pub use material::ChunkMaterialPlugin;
-
-use crate::voxel::chunk::{
-  generation::ChunkBlockData,
-  material::{ChunkMaterial, InstanceData, InstanceMaterialData},
-};
+pub use debug::DebugOverlayPlugin;
+pub use diagnostics::VoxelDiagnosticsPlugin;
+pub use export::ExportMesh;
+pub use fluid::FluidPlugin;
+pub use fog::VoxelFogPlugin;
+pub use generation::Generator;
+pub use indirect::{ChunkBackend, ChunkIndirectPlugin, ChunkVertexLayout};
+pub use instancing::InstancingExamplePlugin;
+pub use liquid::LiquidMaterialPlugin;
+pub use material::{ChunkMaterialPlugin, ChunkMeshLayout};
+pub use props::PropPlugin;
+pub use region::{ChunkRegion, generate_region, mesh_region};
+pub use schematic::{BlockMapping, BlockVolume};
+pub use sky::SkyPlugin;
+pub use streaming::{ChunkStreamingPlugin, ChunkViewer, VoxelWorldConfig};
+pub use time_of_day::{TimeOfDay, TimeOfDayPlugin};
+pub use vox::VoxPlugin;
+pub use world::{VoxelWorld, VoxelWorldPlugin};
 
+mod allocator;
+mod block;
+mod debug;
+mod diagnostics;
 mod entity;
+mod export;
+mod fluid;
+mod fog;
 mod generation;
+mod greedy;
+mod indirect;
+mod indirect_prepass;
+mod instance_prepass;
+mod instancing;
+mod liquid;
 mod material;
 mod mesh;
+mod props;
+mod region;
+mod schematic;
+mod sky;
+mod streaming;
+mod time_of_day;
+mod translucent;
+mod vox;
+mod world;
 
 const CHUNK_SIZE: usize = 16;
 const CHUNK_SIZE_POW: usize = 5; // log2(16) = 4, plus 1 for first bit
-
-#[derive(Component)]
-pub struct Marker;
-
-pub fn test(
-  query: Query<Entity, With<Marker>>,
-  mut commands: Commands,
-  mut meshes: ResMut<Assets<Mesh>>,
-  //mut materials: ResMut<Assets<ChunkMaterial>>,
-) {
-  commands.spawn((
-    Mesh3d(meshes.add(Plane3d::default())),
-    Transform::from_translation(Vec3::new(0.0, 1.0, 0.0)),
-    InstanceMaterialData(vec![InstanceData { data: 42 }]),
-  ));
-
-  return;
-  /*
-  for x in -10..10 {
-    for z in -10..10 {
-      for y in -2..=1 {
-        let chunk_pos = IVec3::new(x, y, z);
-        let mesh_entity = ChunkBlockData::create(0, chunk_pos)
-          .create_mesh()
-          .create_entity(&mut materials, &mut meshes);
-
-        commands.spawn((Marker, mesh_entity));
-      }
-    }
-  }*/
-}
@@ -0,0 +1,256 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+  asset::RenderAssetUsages,
+  math::USizeVec3,
+  mesh::{Indices, PrimitiveTopology},
+  prelude::*,
+};
+
+use crate::voxel::chunk::{CHUNK_SIZE, CHUNK_SIZE_POW, block, generation::ChunkBlockData};
+
+/// Steps of the rotation of a prop around the vertical axis, must match
+/// `ROTATION_STEPS` in `chunk_instance.wgsl`.
+const ROTATION_STEPS: u32 = 16;
+/// Number of tint and size variants of a prop.
+const VARIANTS: u32 = 16;
+
+const ROTATION_SHIFT: u32 = 3 * CHUNK_SIZE_POW as u32;
+const VARIANT_SHIFT: u32 = ROTATION_SHIFT + 4;
+
+/// Small decorations scattered on top of grass, drawn instanced with one draw
+/// per kind and chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PropKind {
+  GrassTuft,
+  Flower,
+  Pebble,
+}
+
+impl PropKind {
+  pub const ALL: [Self; 3] = [Self::GrassTuft, Self::Flower, Self::Pebble];
+
+  /// Chance of a grass block with air above to get this prop.
+  fn chance(self) -> f32 {
+    match self {
+      Self::GrassTuft => 0.12,
+      Self::Flower => 0.02,
+      Self::Pebble => 0.015,
+    }
+  }
+
+  /// Mesh of the prop, standing on the origin and about a block wide. The alpha
+  /// of the vertex colours is how much the variant tints them.
+  fn mesh(self) -> Mesh {
+    let mut builder = PropMeshBuilder::default();
+    match self {
+      Self::GrassTuft => {
+        for i in 0..3 {
+          let angle = i as f32 * TAU / 6.0;
+          builder.blade(angle, 0.7, 0.55, Vec4::new(0.1, 0.35, 0.05, 0.4));
+        }
+      }
+      Self::Flower => {
+        builder.blade(0.0, 0.08, 0.45, Vec4::new(0.1, 0.4, 0.05, 0.0));
+        builder.blade(TAU / 4.0, 0.08, 0.45, Vec4::new(0.1, 0.4, 0.05, 0.0));
+        builder.cuboid(
+          Vec3::new(-0.1, 0.4, -0.1),
+          Vec3::new(0.1, 0.55, 0.1),
+          Vec4::ONE,
+        );
+      }
+      Self::Pebble => builder.cuboid(
+        Vec3::new(-0.15, 0.0, -0.12),
+        Vec3::new(0.15, 0.12, 0.12),
+        Vec4::new(0.45, 0.45, 0.42, 0.3),
+      ),
+    }
+    builder.build()
+  }
+}
+
+/// A prop standing in the cell above a block, see [`pack_prop`].
+#[derive(Clone, Copy, Debug)]
+pub struct Prop {
+  pub kind: PropKind,
+  pub data: u32,
+}
+
+/// Packs a prop as `.........vvvvrrrrzzzzzyyyyyxxxxx`, matching `unpack_prop` in
+/// `chunk_instance.wgsl`. The position is in the padded chunk space the chunk
+/// meshes use.
+#[inline]
+pub fn pack_prop(pos: USizeVec3, rotation: u32, variant: u32) -> u32 {
+  let chunk_size_mask: u32 = (1 << CHUNK_SIZE_POW) - 1;
+
+  (pos.x as u32 & chunk_size_mask)
+    | ((pos.y as u32 & chunk_size_mask) << CHUNK_SIZE_POW)
+    | ((pos.z as u32 & chunk_size_mask) << (2 * CHUNK_SIZE_POW))
+    | ((rotation % ROTATION_STEPS) << ROTATION_SHIFT)
+    | ((variant % VARIANTS) << VARIANT_SHIFT)
+}
+
+/// Position of a packed prop in the padded chunk space.
+#[inline]
+pub fn unpack_prop_position(data: u32) -> USizeVec3 {
+  let chunk_size_mask: u32 = (1 << CHUNK_SIZE_POW) - 1;
+  let field = |i: usize| ((data >> (i * CHUNK_SIZE_POW)) & chunk_size_mask) as usize;
+
+  USizeVec3::new(field(0), field(1), field(2))
+}
+
+/// Deterministic hash of a world position, so the same seed always scatters the
+/// same props.
+#[inline]
+fn hash(seed: u32, pos: IVec3) -> u32 {
+  let mut hash = seed ^ 0x9E37_79B9;
+  for value in pos.to_array() {
+    hash = (hash ^ value as u32).wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xC2B2_AE35);
+    hash ^= hash >> 16;
+  }
+  hash
+}
+
+impl ChunkBlockData {
+  /// Places props on top faces of grass blocks with air above them.
+  pub fn scatter_props(&mut self, seed: u32) {
+    self.props.clear();
+    for x in 1..CHUNK_SIZE + 1 {
+      for z in 1..CHUNK_SIZE + 1 {
+        for y in 1..CHUNK_SIZE + 1 {
+          let pos = USizeVec3::new(x, y, z);
+          if !self.can_hold_prop(pos) {
+            continue;
+          }
+
+          let world_pos = self.chunk_pos * CHUNK_SIZE as i32 + pos.as_ivec3() - IVec3::ONE;
+          let hash = hash(seed, world_pos);
+          // the low 16 bits pick the kind, the rest rotation and variant
+          let mut roll = (hash & 0xFFFF) as f32 / 65536.0;
+          let Some(kind) = PropKind::ALL.into_iter().find(|kind| {
+            roll -= kind.chance();
+            roll < 0.0
+          }) else {
+            continue;
+          };
+          self.props.push(Prop {
+            kind,
+            data: pack_prop(pos, hash >> 16, hash >> 20),
+          });
+        }
+      }
+    }
+  }
+
+  /// Props whose cell is still empty and still stands on grass, so edits to the
+  /// blocks remove them.
+  pub fn standing_props(&self) -> impl Iterator<Item = Prop> + '_ {
+    self
+      .props
+      .iter()
+      .filter(|prop| self.can_hold_prop(unpack_prop_position(prop.data)))
+      .copied()
+  }
+
+  fn can_hold_prop(&self, pos: USizeVec3) -> bool {
+    self.get(pos) == block::AIR && block::id(self.get(pos - USizeVec3::Y)) == block::GRASS
+  }
+}
+
+/// Shared mesh handles for every [`PropKind`].
+#[derive(Resource)]
+pub struct PropMeshes {
+  pub grass_tuft: Handle<Mesh>,
+  pub flower: Handle<Mesh>,
+  pub pebble: Handle<Mesh>,
+}
+
+impl PropMeshes {
+  pub fn get(&self, kind: PropKind) -> Handle<Mesh> {
+    match kind {
+      PropKind::GrassTuft => self.grass_tuft.clone(),
+      PropKind::Flower => self.flower.clone(),
+      PropKind::Pebble => self.pebble.clone(),
+    }
+  }
+}
+
+/// Creates the [`PropMeshes`] drawn by the instanced path of the
+/// [`ChunkMaterialPlugin`](super::ChunkMaterialPlugin).
+pub struct PropPlugin;
+
+impl Plugin for PropPlugin {
+  fn build(&self, app: &mut App) {
+    app.add_systems(PreStartup, init_prop_meshes);
+  }
+}
+
+fn init_prop_meshes(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+  commands.insert_resource(PropMeshes {
+    grass_tuft: meshes.add(PropKind::GrassTuft.mesh()),
+    flower: meshes.add(PropKind::Flower.mesh()),
+    pebble: meshes.add(PropKind::Pebble.mesh()),
+  });
+}
+
+#[derive(Default)]
+struct PropMeshBuilder {
+  positions: Vec<Vec3>,
+  normals: Vec<Vec3>,
+  colors: Vec<Vec4>,
+  indices: Vec<u32>,
+}
+
+impl PropMeshBuilder {
+  fn quad(&mut self, corners: [Vec3; 4], normal: Vec3, color: Vec4) {
+    let start = self.positions.len() as u32;
+    self.positions.extend(corners);
+    self.normals.extend([normal; 4]);
+    self.colors.extend([color; 4]);
+    self
+      .indices
+      .extend([start, start + 1, start + 2, start + 2, start + 3, start]);
+  }
+
+  /// Vertical quad through the origin, drawn from both sides. Blades point up so
+  /// they are lit like the grass below them.
+  fn blade(&mut self, angle: f32, width: f32, height: f32, color: Vec4) {
+    let side = Vec3::new(angle.cos(), 0.0, angle.sin()) * width * 0.5;
+    let up = Vec3::Y * height;
+    self.quad([-side, side, side + up, -side + up], Vec3::Y, color);
+  }
+
+  fn cuboid(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+    let size = max - min;
+    for normal in [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::Z, Vec3::NEG_Z] {
+      let (u, v) = normal.any_orthonormal_pair();
+      let center = (min + max) * 0.5 + normal * size * 0.5;
+      let u = u * size * 0.5;
+      let v = v * size * 0.5;
+      self.quad(
+        [
+          center - u - v,
+          center + u - v,
+          center + u + v,
+          center - u + v,
+        ],
+        normal,
+        color,
+      );
+    }
+  }
+
+  fn build(self) -> Mesh {
+    let mut mesh = Mesh::new(
+      PrimitiveTopology::TriangleList,
+      RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+    mesh.insert_indices(Indices::U32(self.indices));
+    mesh
+  }
+}
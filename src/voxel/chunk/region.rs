@@ -0,0 +1,90 @@
+use std::{
+  ops::RangeInclusive,
+  time::{Duration, Instant},
+};
+
+use bevy::{
+  prelude::*,
+  tasks::{ComputeTaskPool, ParallelSlice, TaskPool},
+};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE,
+  generation::{ChunkBlockData, Generator},
+  mesh::ChunkMeshData,
+};
+
+/// Box of chunk positions, both corners included.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkRegion {
+  pub min: IVec3,
+  pub max: IVec3,
+}
+
+impl ChunkRegion {
+  /// Square of chunk columns up to `radius` away from the origin, spanning `height_range`.
+  pub fn around_origin(radius: i32, height_range: RangeInclusive<i32>) -> Self {
+    Self {
+      min: IVec3::new(-radius, *height_range.start(), -radius),
+      max: IVec3::new(radius, *height_range.end(), radius),
+    }
+  }
+
+  /// First and last block of the region in world space.
+  pub fn block_bounds(&self) -> (IVec3, IVec3) {
+    let chunk_size = IVec3::splat(CHUNK_SIZE as i32);
+    (
+      self.min * chunk_size,
+      (self.max + IVec3::ONE) * chunk_size - IVec3::ONE,
+    )
+  }
+
+  pub fn positions(&self) -> Vec<IVec3> {
+    let mut positions = Vec::new();
+    for x in self.min.x..=self.max.x {
+      for y in self.min.y..=self.max.y {
+        for z in self.min.z..=self.max.z {
+          positions.push(IVec3::new(x, y, z));
+        }
+      }
+    }
+    positions
+  }
+}
+
+/// Generates every chunk of `region` on the [`ComputeTaskPool`], along with the
+/// time each one took. Works without an [`App`].
+pub fn generate_region(
+  seed: u32,
+  region: &ChunkRegion,
+  generator: &Generator,
+) -> Vec<(ChunkBlockData, Duration)> {
+  generate_chunks(seed, &region.positions(), generator)
+}
+
+/// Generates the chunks at `positions` on the [`ComputeTaskPool`], along with the
+/// time each one took.
+pub fn generate_chunks(
+  seed: u32,
+  positions: &[IVec3],
+  generator: &Generator,
+) -> Vec<(ChunkBlockData, Duration)> {
+  positions.par_chunk_map(task_pool(), 1, |_, positions| {
+    timed(|| generator.generate(seed, positions[0]))
+  })
+}
+
+/// Meshes every chunk on the [`ComputeTaskPool`], along with the time each one took.
+pub fn mesh_region(chunks: &[ChunkBlockData]) -> Vec<(ChunkMeshData, Duration)> {
+  chunks.par_chunk_map(task_pool(), 1, |_, chunk| timed(|| chunk[0].create_mesh()))
+}
+
+fn task_pool() -> &'static ComputeTaskPool {
+  ComputeTaskPool::get_or_init(TaskPool::default)
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+  let start = Instant::now();
+  let value = f();
+  (value, start.elapsed())
+}
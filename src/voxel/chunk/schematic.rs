@@ -0,0 +1,567 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::{self, BufWriter, Read, Write},
+  path::Path,
+};
+
+use bevy::prelude::*;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::voxel::chunk::{block, world::VoxelWorld};
+
+/// Deepest nesting of NBT compounds and lists that is read.
+const MAX_NBT_DEPTH: usize = 512;
+/// Minecraft data version written to schematics, 1.20.1.
+const DATA_VERSION: i32 = 3465;
+const AIR_STATE: &str = "minecraft:air";
+
+/// How block ids are written to other voxel formats.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockMapping {
+  /// Block state written to schematics per block id, ids without an entry become air.
+  pub block_states: BTreeMap<u8, String>,
+  /// Colour written to `.vox` palettes per block id as sRGB bytes, ids without an
+  /// entry use their base colour.
+  pub colors: BTreeMap<u8, [u8; 4]>,
+}
+
+impl Default for BlockMapping {
+  fn default() -> Self {
+    let block_states = [
+      (block::GRASS, "minecraft:grass_block"),
+      (block::WATER, "minecraft:water"),
+      (block::GLASS, "minecraft:glass"),
+      (block::LEAVES, "minecraft:oak_leaves[persistent=true]"),
+      (block::LAVA, "minecraft:lava"),
+    ];
+    Self {
+      block_states: block_states
+        .into_iter()
+        .map(|(block, state)| (block, state.to_string()))
+        .collect(),
+      colors: BTreeMap::new(),
+    }
+  }
+}
+
+impl BlockMapping {
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, SchematicError> {
+    Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+  }
+
+  /// Block id of a schematic block state, the first one mapped to the same state
+  /// or, failing that, the same block without its properties. Unknown states are air.
+  pub fn block(&self, state: &str) -> u8 {
+    let name = |state: &str| state.split('[').next().unwrap_or_default().to_string();
+    self
+      .block_states
+      .iter()
+      .find(|(_, mapped)| *mapped == state)
+      .or_else(|| {
+        self
+          .block_states
+          .iter()
+          .find(|(_, mapped)| name(mapped) == name(state))
+      })
+      .map_or(block::AIR, |(block, _)| *block)
+  }
+
+  pub fn color(&self, block: u8) -> [u8; 4] {
+    let block = block::id(block);
+    self
+      .colors
+      .get(&block)
+      .copied()
+      .unwrap_or_else(|| Srgba::from(block::base_color(block)).to_u8_array())
+  }
+}
+
+/// Copy of the blocks inside a box of the world, liquid states are dropped.
+#[derive(Clone, Debug)]
+pub struct BlockVolume {
+  /// World position of the lowest corner.
+  pub min: IVec3,
+  pub size: UVec3,
+  /// Block ids ordered by x, then z, then y.
+  pub blocks: Vec<u8>,
+}
+
+impl BlockVolume {
+  /// Blocks between `min` and `max` inclusive, unloaded blocks are air.
+  pub fn from_world(world: &VoxelWorld, min: IVec3, max: IVec3) -> Self {
+    let size = (max - min + IVec3::ONE).max(IVec3::ZERO).as_uvec3();
+    let mut blocks = Vec::with_capacity((size.x * size.y * size.z) as usize);
+    for y in 0..size.y as i32 {
+      for z in 0..size.z as i32 {
+        for x in 0..size.x as i32 {
+          let block = world.get_block(min + IVec3::new(x, y, z));
+          blocks.push(block::id(block.unwrap_or(block::AIR)));
+        }
+      }
+    }
+    Self { min, size, blocks }
+  }
+
+  #[inline]
+  pub fn get(&self, pos: UVec3) -> u8 {
+    self.blocks[((pos.y * self.size.z + pos.z) * self.size.x + pos.x) as usize]
+  }
+
+  /// World positions and blocks of everything but air, with the lowest corner
+  /// moved to `origin`.
+  pub fn placed_blocks(&self, origin: IVec3) -> Vec<(IVec3, u8)> {
+    let mut blocks = Vec::new();
+    for y in 0..self.size.y {
+      for z in 0..self.size.z {
+        for x in 0..self.size.x {
+          let pos = UVec3::new(x, y, z);
+          let block = self.get(pos);
+          if block != block::AIR {
+            blocks.push((origin + pos.as_ivec3(), block));
+          }
+        }
+      }
+    }
+    blocks
+  }
+
+  /// Reads a gzipped Sponge schematic, version 2, mapping block states back to
+  /// block ids with `mapping`.
+  pub fn parse_schem(bytes: &[u8], mapping: &BlockMapping) -> Result<Self, SchematicError> {
+    let mut nbt = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut nbt)?;
+    let mut reader = NbtReader {
+      bytes: &nbt,
+      pos: 0,
+    };
+    if reader.u8()? != TAG_COMPOUND {
+      return Err(SchematicError::Format("root is not a compound"));
+    }
+    reader.name()?;
+
+    let mut version = None;
+    let mut size = IVec3::ZERO;
+    let mut min = IVec3::ZERO;
+    let mut palette = BTreeMap::new();
+    let mut block_data: &[u8] = &[];
+    loop {
+      let tag = reader.u8()?;
+      if tag == TAG_END {
+        break;
+      }
+      match (tag, reader.name()?.as_str()) {
+        (TAG_INT, "Version") => version = Some(reader.i32()?),
+        // sizes are unsigned shorts stored as signed ones
+        (TAG_SHORT, "Width") => size.x = reader.i16()? as u16 as i32,
+        (TAG_SHORT, "Height") => size.y = reader.i16()? as u16 as i32,
+        (TAG_SHORT, "Length") => size.z = reader.i16()? as u16 as i32,
+        (TAG_INT_ARRAY, "Offset") => {
+          let len = reader.i32()?;
+          let values = (0..len)
+            .map(|_| reader.i32())
+            .collect::<Result<Vec<_>, _>>()?;
+          if let [x, y, z] = values[..] {
+            min = IVec3::new(x, y, z);
+          }
+        }
+        (TAG_COMPOUND, "Palette") => loop {
+          let tag = reader.u8()?;
+          if tag == TAG_END {
+            break;
+          }
+          let state = reader.name()?;
+          if tag == TAG_INT {
+            palette.insert(reader.i32()?, mapping.block(&state));
+          } else {
+            reader.skip(tag, 0)?;
+          }
+        },
+        (TAG_BYTE_ARRAY, "BlockData") => {
+          let len = reader.i32()?.max(0) as usize;
+          block_data = reader.take(len)?;
+        }
+        (tag, _) => reader.skip(tag, 0)?,
+      }
+    }
+    if version != Some(2) {
+      return Err(SchematicError::Format(
+        "only version 2 schematics are supported",
+      ));
+    }
+
+    // the block count isn't trusted, so blocks are only collected from the data
+    let mut blocks = Vec::new();
+    let mut data = block_data.iter();
+    while data.len() > 0 {
+      let index = read_varint(&mut data)?;
+      blocks.push(palette.get(&index).copied().unwrap_or(block::AIR));
+    }
+    let size = size.as_uvec3();
+    if blocks.len() as u64 != size.x as u64 * size.y as u64 * size.z as u64 {
+      return Err(SchematicError::Format("block data doesn't match the size"));
+    }
+    Ok(Self { min, size, blocks })
+  }
+
+  /// Writes a gzipped Sponge schematic, version 2.
+  pub fn write_schem(
+    &self,
+    path: impl AsRef<Path>,
+    mapping: &BlockMapping,
+  ) -> Result<(), SchematicError> {
+    if self.size.max_element() > u16::MAX as u32 {
+      return Err(SchematicError::TooLarge);
+    }
+
+    // palette indices in order of first use, air is always 0
+    let mut palette: Vec<&str> = vec![AIR_STATE];
+    let mut indices: BTreeMap<u8, i32> = BTreeMap::new();
+    let mut block_data = Vec::with_capacity(self.blocks.len());
+    for &block in &self.blocks {
+      let index = *indices.entry(block).or_insert_with(|| {
+        let state = mapping
+          .block_states
+          .get(&block)
+          .map_or(AIR_STATE, String::as_str);
+        palette
+          .iter()
+          .position(|existing| *existing == state)
+          .unwrap_or_else(|| {
+            palette.push(state);
+            palette.len() - 1
+          }) as i32
+      });
+      write_varint(&mut block_data, index);
+    }
+
+    let mut nbt = Vec::new();
+    nbt.push(TAG_COMPOUND);
+    write_name(&mut nbt, "Schematic");
+    write_int(&mut nbt, "Version", 2);
+    write_int(&mut nbt, "DataVersion", DATA_VERSION);
+    // sizes are unsigned shorts stored as signed ones
+    write_short(&mut nbt, "Width", self.size.x as u16 as i16);
+    write_short(&mut nbt, "Height", self.size.y as u16 as i16);
+    write_short(&mut nbt, "Length", self.size.z as u16 as i16);
+    write_int_array(&mut nbt, "Offset", &self.min.to_array());
+    write_int(&mut nbt, "PaletteMax", palette.len() as i32);
+    nbt.push(TAG_COMPOUND);
+    write_name(&mut nbt, "Palette");
+    for (index, state) in palette.iter().enumerate() {
+      write_int(&mut nbt, state, index as i32);
+    }
+    nbt.push(TAG_END);
+    nbt.push(TAG_BYTE_ARRAY);
+    write_name(&mut nbt, "BlockData");
+    nbt.extend((block_data.len() as i32).to_be_bytes());
+    nbt.extend(block_data);
+    nbt.push(TAG_END);
+
+    let mut file = GzEncoder::new(
+      BufWriter::new(fs::File::create(path)?),
+      Compression::default(),
+    );
+    file.write_all(&nbt)?;
+    file.finish()?.flush()?;
+    Ok(())
+  }
+}
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+fn write_name(nbt: &mut Vec<u8>, name: &str) {
+  nbt.extend((name.len() as u16).to_be_bytes());
+  nbt.extend(name.as_bytes());
+}
+
+fn write_short(nbt: &mut Vec<u8>, name: &str, value: i16) {
+  nbt.push(TAG_SHORT);
+  write_name(nbt, name);
+  nbt.extend(value.to_be_bytes());
+}
+
+fn write_int(nbt: &mut Vec<u8>, name: &str, value: i32) {
+  nbt.push(TAG_INT);
+  write_name(nbt, name);
+  nbt.extend(value.to_be_bytes());
+}
+
+fn write_int_array(nbt: &mut Vec<u8>, name: &str, values: &[i32]) {
+  nbt.push(TAG_INT_ARRAY);
+  write_name(nbt, name);
+  nbt.extend((values.len() as i32).to_be_bytes());
+  for value in values {
+    nbt.extend(value.to_be_bytes());
+  }
+}
+
+/// Appends a palette index as a varint, seven bits per byte starting with the lowest.
+fn write_varint(bytes: &mut Vec<u8>, value: i32) {
+  let mut value = value as u32;
+  while value >= 0x80 {
+    bytes.push((value as u8 & 0x7F) | 0x80);
+    value >>= 7;
+  }
+  bytes.push(value as u8);
+}
+
+/// Reads a palette index written by [`write_varint`].
+fn read_varint<'a>(bytes: &mut impl Iterator<Item = &'a u8>) -> Result<i32, SchematicError> {
+  let mut value = 0u32;
+  for shift in (0..32).step_by(7) {
+    let byte = *bytes
+      .next()
+      .ok_or(SchematicError::Format("block data ends unexpectedly"))?;
+    value |= ((byte & 0x7F) as u32) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(value as i32);
+    }
+  }
+  Err(SchematicError::Format("palette index is too long"))
+}
+
+/// Big endian NBT data.
+struct NbtReader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> NbtReader<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8], SchematicError> {
+    let end = self
+      .pos
+      .checked_add(len)
+      .filter(|end| *end <= self.bytes.len())
+      .ok_or(SchematicError::Format("schematic ends unexpectedly"))?;
+    let bytes = &self.bytes[self.pos..end];
+    self.pos = end;
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> Result<u8, SchematicError> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn i16(&mut self) -> Result<i16, SchematicError> {
+    let bytes = self.take(2)?;
+    Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+  }
+
+  fn i32(&mut self) -> Result<i32, SchematicError> {
+    let bytes = self.take(4)?;
+    Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  fn name(&mut self) -> Result<String, SchematicError> {
+    let len = self.i16()? as u16 as usize;
+    Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+  }
+
+  /// Skips the payload of a tag, `depth` is the nesting of the tag.
+  fn skip(&mut self, tag: u8, depth: usize) -> Result<(), SchematicError> {
+    if depth > MAX_NBT_DEPTH {
+      return Err(SchematicError::Format("NBT is nested too deeply"));
+    }
+    match tag {
+      TAG_BYTE => self.take(1).map(|_| ()),
+      TAG_SHORT => self.take(2).map(|_| ()),
+      TAG_INT | TAG_FLOAT => self.take(4).map(|_| ()),
+      TAG_LONG | TAG_DOUBLE => self.take(8).map(|_| ()),
+      TAG_BYTE_ARRAY => self.skip_array(1),
+      TAG_INT_ARRAY => self.skip_array(4),
+      TAG_LONG_ARRAY => self.skip_array(8),
+      TAG_STRING => self.name().map(|_| ()),
+      TAG_LIST => {
+        let element = self.u8()?;
+        let len = self.i32()?.max(0);
+        if element != TAG_END {
+          for _ in 0..len {
+            self.skip(element, depth + 1)?;
+          }
+        }
+        Ok(())
+      }
+      TAG_COMPOUND => loop {
+        let tag = self.u8()?;
+        if tag == TAG_END {
+          return Ok(());
+        }
+        self.name()?;
+        self.skip(tag, depth + 1)?;
+      },
+      _ => Err(SchematicError::Format("unknown NBT tag")),
+    }
+  }
+
+  fn skip_array(&mut self, element_size: usize) -> Result<(), SchematicError> {
+    let len = self.i32()?.max(0) as usize;
+    self.take(len.saturating_mul(element_size)).map(|_| ())
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum SchematicError {
+  #[error("could not read or write file: {0}")]
+  Io(#[from] io::Error),
+  #[error("could not parse block mapping: {0}")]
+  Parse(#[from] ron::de::SpannedError),
+  #[error("volume is too large for a schematic")]
+  TooLarge,
+  #[error("invalid schematic: {0}")]
+  Format(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+  }
+
+  /// A 2x1x2 schematic with `palette` and one block per palette index, along with
+  /// metadata that is skipped.
+  fn minimal_schematic(version: i32, palette: &[&str]) -> Vec<u8> {
+    let mut nbt = vec![TAG_COMPOUND];
+    write_name(&mut nbt, "Schematic");
+    write_int(&mut nbt, "Version", version);
+    nbt.push(TAG_COMPOUND);
+    write_name(&mut nbt, "Metadata");
+    nbt.push(TAG_STRING);
+    write_name(&mut nbt, "Name");
+    write_name(&mut nbt, "test");
+    nbt.push(TAG_LIST);
+    write_name(&mut nbt, "Tags");
+    nbt.push(TAG_INT);
+    nbt.extend(2i32.to_be_bytes());
+    nbt.extend([0, 0, 0, 1, 0, 0, 0, 2]);
+    nbt.push(TAG_END);
+    write_short(&mut nbt, "Width", 2);
+    write_short(&mut nbt, "Height", 1);
+    write_short(&mut nbt, "Length", 2);
+    write_int_array(&mut nbt, "Offset", &[5, -3, 7]);
+    nbt.push(TAG_COMPOUND);
+    write_name(&mut nbt, "Palette");
+    for (index, state) in palette.iter().enumerate() {
+      write_int(&mut nbt, state, index as i32);
+    }
+    nbt.push(TAG_END);
+    nbt.push(TAG_BYTE_ARRAY);
+    write_name(&mut nbt, "BlockData");
+    nbt.extend(4i32.to_be_bytes());
+    nbt.extend([0, 1, 2, 3]);
+    nbt.push(TAG_END);
+    gzip(&nbt)
+  }
+
+  #[test]
+  fn parses_minimal_schematic() {
+    let palette = [
+      "minecraft:air",
+      "minecraft:glass",
+      // only the properties differ from the mapping
+      "minecraft:oak_leaves[distance=1]",
+      "minecraft:stone",
+    ];
+    let volume =
+      BlockVolume::parse_schem(&minimal_schematic(2, &palette), &BlockMapping::default()).unwrap();
+
+    assert_eq!(volume.min, IVec3::new(5, -3, 7));
+    assert_eq!(volume.size, UVec3::new(2, 1, 2));
+    // unknown states are air
+    assert_eq!(
+      volume.blocks,
+      vec![block::AIR, block::GLASS, block::LEAVES, block::AIR]
+    );
+
+    // the mapping decides which block a state becomes
+    let mut mapping = BlockMapping::default();
+    mapping
+      .block_states
+      .insert(block::GRASS, "minecraft:stone".to_string());
+    let volume = BlockVolume::parse_schem(&minimal_schematic(2, &palette), &mapping).unwrap();
+    assert_eq!(volume.blocks[3], block::GRASS);
+    assert_eq!(
+      volume.placed_blocks(IVec3::ZERO),
+      vec![
+        (IVec3::new(1, 0, 0), block::GLASS),
+        (IVec3::new(0, 0, 1), block::LEAVES),
+        (IVec3::new(1, 0, 1), block::GRASS),
+      ]
+    );
+  }
+
+  #[test]
+  fn schematic_round_trip() {
+    let size = UVec3::new(3, 2, 2);
+    let volume = BlockVolume {
+      min: IVec3::new(-4, 10, 2),
+      size,
+      blocks: vec![
+        block::GRASS,
+        block::GRASS,
+        block::AIR,
+        block::WATER,
+        block::AIR,
+        block::LAVA,
+        block::GLASS,
+        block::AIR,
+        block::AIR,
+        block::AIR,
+        block::LEAVES,
+        block::AIR,
+      ],
+    };
+
+    let path = std::env::temp_dir().join(format!("voxel_volume_{}.schem", std::process::id()));
+    let mapping = BlockMapping::default();
+    volume.write_schem(&path, &mapping).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let read = BlockVolume::parse_schem(&bytes, &mapping).unwrap();
+
+    assert_eq!(read.min, volume.min);
+    assert_eq!(read.size, volume.size);
+    assert_eq!(read.blocks, volume.blocks);
+  }
+
+  #[test]
+  fn rejects_invalid_schematics() {
+    let palette = ["minecraft:air", "minecraft:glass"];
+    let mapping = BlockMapping::default();
+    assert!(matches!(
+      BlockVolume::parse_schem(&minimal_schematic(3, &palette), &mapping),
+      Err(SchematicError::Format(_))
+    ));
+    assert!(BlockVolume::parse_schem(b"not gzip", &mapping).is_err());
+
+    let bytes = minimal_schematic(2, &palette);
+    let mut nbt = Vec::new();
+    GzDecoder::new(bytes.as_slice())
+      .read_to_end(&mut nbt)
+      .unwrap();
+    for len in 0..nbt.len() {
+      assert!(
+        BlockVolume::parse_schem(&gzip(&nbt[..len]), &mapping).is_err(),
+        "truncated to {len} bytes"
+      );
+    }
+  }
+}
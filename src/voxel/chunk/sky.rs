@@ -0,0 +1,146 @@
+use bevy::{
+  camera::visibility::NoFrustumCulling,
+  mesh::MeshVertexBufferLayoutRef,
+  pbr::{MaterialPipeline, MaterialPipelineKey},
+  prelude::*,
+  render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderType, SpecializedMeshPipelineError,
+  },
+  shader::ShaderRef,
+};
+
+use crate::voxel::chunk::fog::VoxelFog;
+
+const SHADER_PATH: &str = "shaders/sky.wgsl";
+
+/// Colours of the procedural sky dome. The horizon is the colour of the
+/// [`VoxelFog`], so the terrain fades into the sky.
+#[derive(Resource, Clone, Debug)]
+pub struct Sky {
+  pub zenith: Color,
+  pub horizon: Color,
+  /// Colour below the horizon, seen where no chunks are loaded.
+  pub ground: Color,
+  /// Direction towards the sun, which gets a disk and a glow around it.
+  pub sun_direction: Vec3,
+  pub sun_color: Color,
+}
+
+impl Default for Sky {
+  fn default() -> Self {
+    Self {
+      zenith: Color::srgb(0.25, 0.45, 0.85),
+      horizon: VoxelFog::default().color,
+      ground: Color::srgb(0.35, 0.4, 0.45),
+      sun_direction: Vec3::new(0.3, 1.0, 0.5).normalize(),
+      sun_color: Color::srgb(1.0, 0.9, 0.7),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, ShaderType)]
+struct SkyUniform {
+  zenith: Vec4,
+  horizon: Vec4,
+  ground: Vec4,
+  sun_color: Vec4,
+  sun_direction: Vec3,
+}
+
+impl From<&Sky> for SkyUniform {
+  fn from(sky: &Sky) -> Self {
+    Self {
+      zenith: sky.zenith.to_linear().to_vec4(),
+      horizon: sky.horizon.to_linear().to_vec4(),
+      ground: sky.ground.to_linear().to_vec4(),
+      sun_color: sky.sun_color.to_linear().to_vec4(),
+      sun_direction: sky.sun_direction.normalize_or(Vec3::Y),
+    }
+  }
+}
+
+/// Material of the sky dome, drawn around every view behind everything else.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+struct SkyMaterial {
+  #[uniform(0)]
+  sky: SkyUniform,
+}
+
+impl Material for SkyMaterial {
+  fn vertex_shader() -> ShaderRef {
+    SHADER_PATH.into()
+  }
+
+  fn fragment_shader() -> ShaderRef {
+    SHADER_PATH.into()
+  }
+
+  fn specialize(
+    _pipeline: &MaterialPipeline,
+    descriptor: &mut RenderPipelineDescriptor,
+    _layout: &MeshVertexBufferLayoutRef,
+    _key: MaterialPipelineKey<Self>,
+  ) -> Result<(), SpecializedMeshPipelineError> {
+    // the dome is seen from the inside
+    descriptor.primitive.cull_mode = None;
+    if let Some(depth_stencil) = &mut descriptor.depth_stencil {
+      depth_stencil.depth_write_enabled = false;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Component)]
+struct SkyDome;
+
+/// Draws a procedural sky dome with the colours of the [`Sky`] resource, which
+/// also sets the colour of the [`VoxelFog`].
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_plugins(MaterialPlugin::<SkyMaterial> {
+        prepass_enabled: false,
+        shadows_enabled: false,
+        ..default()
+      })
+      .init_resource::<Sky>()
+      .add_systems(Startup, spawn_sky_dome)
+      .add_systems(Update, update_sky);
+  }
+}
+
+fn spawn_sky_dome(
+  mut commands: Commands,
+  sky: Res<Sky>,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut materials: ResMut<Assets<SkyMaterial>>,
+) {
+  // the shader centers the dome on the view, so it's never culled
+  commands.spawn((
+    Mesh3d(meshes.add(Sphere::new(1.0).mesh().uv(32, 16))),
+    MeshMaterial3d(materials.add(SkyMaterial {
+      sky: SkyUniform::from(&*sky),
+    })),
+    NoFrustumCulling,
+    SkyDome,
+  ));
+}
+
+fn update_sky(
+  sky: Res<Sky>,
+  mut fog: ResMut<VoxelFog>,
+  domes: Query<&MeshMaterial3d<SkyMaterial>, With<SkyDome>>,
+  mut materials: ResMut<Assets<SkyMaterial>>,
+) {
+  if !sky.is_changed() {
+    return;
+  }
+  fog.color = sky.horizon;
+  for dome in &domes {
+    if let Some(material) = materials.get_mut(dome) {
+      material.sky = SkyUniform::from(&*sky);
+    }
+  }
+}
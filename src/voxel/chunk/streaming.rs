@@ -0,0 +1,206 @@
+use std::ops::RangeInclusive;
+
+use bevy::{
+  diagnostic::DiagnosticsStore,
+  platform::{collections::HashSet, time::Instant},
+  prelude::*,
+};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE,
+  diagnostics::{VoxelDiagnosticsPlugin, add_measurement},
+  generation::Generator,
+  indirect::ChunkBackend,
+  region::generate_chunks,
+  world::{ChunkCoord, VoxelWorld},
+};
+
+/// Chunks around entities with this component are kept loaded.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ChunkViewer;
+
+/// Seed, extent and generator of the voxel world, set through the
+/// [`VoxelPlugin`](crate::voxel::VoxelPlugin) builder.
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelWorldConfig {
+  pub seed: u32,
+  /// Horizontal distance in chunks around each [`ChunkViewer`] that is loaded.
+  pub render_distance: i32,
+  /// Chunk heights that are loaded, the terrain doesn't extend beyond them.
+  pub height_range: RangeInclusive<i32>,
+  /// Most chunks generated in a single frame while streaming, the closest ones
+  /// come first. Chunks in range at startup are all generated at once.
+  pub chunks_per_frame: usize,
+  pub generator: Generator,
+  /// How the chunks are rendered, the indirect backend drops translucent faces,
+  /// liquids and props.
+  pub backend: ChunkBackend,
+}
+
+impl Default for VoxelWorldConfig {
+  fn default() -> Self {
+    Self {
+      seed: 0,
+      render_distance: 10,
+      height_range: -2..=1,
+      chunks_per_frame: 8,
+      generator: Generator::default(),
+      backend: ChunkBackend::default(),
+    }
+  }
+}
+
+/// Number of chunks that are in range of a viewer but not generated yet.
+#[derive(Resource, Default, Debug)]
+pub struct ChunkStreamingQueue {
+  pub queued: usize,
+}
+
+/// Loads the chunks in range of any [`ChunkViewer`] into the [`VoxelWorld`] and
+/// unloads the ones out of range of all of them.
+///
+/// New chunks are spawned as bare [`ChunkCoord`] entities and meshed by the
+/// [`VoxelWorldPlugin`](super::VoxelWorldPlugin) for the [`ChunkBackend`] of the
+/// config.
+pub struct ChunkStreamingPlugin;
+
+impl Plugin for ChunkStreamingPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<VoxelWorldConfig>()
+      .init_resource::<ChunkStreamingQueue>()
+      .add_systems(
+        PostStartup,
+        bootstrap_chunks.after(TransformSystems::Propagate),
+      )
+      .add_systems(Update, stream_chunks);
+  }
+}
+
+/// Chunk column each viewer is in.
+fn viewer_centers<'a>(viewers: impl Iterator<Item = &'a GlobalTransform>) -> Vec<IVec3> {
+  viewers
+    .map(|transform| {
+      (transform.translation() / CHUNK_SIZE as f32)
+        .floor()
+        .as_ivec3()
+    })
+    .collect()
+}
+
+/// Chunks within the render distance of any center that aren't loaded yet.
+fn missing_chunks(
+  centers: &[IVec3],
+  config: &VoxelWorldConfig,
+  world: &VoxelWorld,
+) -> HashSet<IVec3> {
+  let render_distance = config.render_distance;
+  let mut missing: HashSet<IVec3> = HashSet::default();
+  for center in centers {
+    for x in -render_distance..=render_distance {
+      for z in -render_distance..=render_distance {
+        if x * x + z * z > render_distance.pow(2) {
+          continue;
+        }
+        for y in config.height_range.clone() {
+          let chunk_pos = IVec3::new(center.x + x, y, center.z + z);
+          if world.chunk(chunk_pos).is_none() {
+            missing.insert(chunk_pos);
+          }
+        }
+      }
+    }
+  }
+  missing
+}
+
+/// Generates every chunk in range of the viewers spawned at startup in parallel, so
+/// the first frames don't wait for streaming. Without viewers the chunks around the
+/// origin are generated.
+fn bootstrap_chunks(
+  mut commands: Commands,
+  config: Res<VoxelWorldConfig>,
+  mut world: ResMut<VoxelWorld>,
+  viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+  mut diagnostics: Option<ResMut<DiagnosticsStore>>,
+) {
+  let mut centers = viewer_centers(viewers.iter());
+  if centers.is_empty() {
+    centers.push(IVec3::ZERO);
+  }
+
+  let missing: Vec<IVec3> = missing_chunks(&centers, &config, &world)
+    .into_iter()
+    .collect();
+  for (chunk, time) in generate_chunks(config.seed, &missing, &config.generator) {
+    add_measurement(
+      &mut diagnostics,
+      &VoxelDiagnosticsPlugin::GENERATION_TIME,
+      time.as_secs_f64() * 1e3,
+    );
+    commands.spawn(ChunkCoord(chunk.chunk_pos));
+    world.insert(chunk);
+  }
+}
+
+pub(super) fn stream_chunks(
+  mut commands: Commands,
+  config: Res<VoxelWorldConfig>,
+  mut queue: ResMut<ChunkStreamingQueue>,
+  mut world: ResMut<VoxelWorld>,
+  viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+  chunks: Query<(Entity, &ChunkCoord)>,
+  mut diagnostics: Option<ResMut<DiagnosticsStore>>,
+) {
+  let centers = viewer_centers(viewers.iter());
+
+  // horizontal distance to the closest viewer, squared
+  let distance_squared = |chunk_pos: IVec3| {
+    centers
+      .iter()
+      .map(|center| (chunk_pos - center).xz().length_squared())
+      .min()
+  };
+
+  // keep one extra ring loaded so chunks on the edge don't flicker in and out
+  let unload_distance = config.render_distance + 1;
+  let unloaded: HashSet<IVec3> = world
+    .chunk_positions()
+    .filter(|chunk_pos| {
+      distance_squared(*chunk_pos).is_none_or(|distance| distance > unload_distance.pow(2))
+    })
+    .collect();
+  if !unloaded.is_empty() {
+    for chunk_pos in &unloaded {
+      world.remove(*chunk_pos);
+    }
+    for (entity, coord) in &chunks {
+      if unloaded.contains(&coord.0) {
+        commands.entity(entity).despawn();
+      }
+    }
+  }
+
+  let mut missing: Vec<IVec3> = missing_chunks(&centers, &config, &world)
+    .into_iter()
+    .collect();
+  missing.sort_unstable_by_key(|chunk_pos| (distance_squared(*chunk_pos), chunk_pos.to_array()));
+  queue.queued = missing.len().saturating_sub(config.chunks_per_frame);
+  add_measurement(
+    &mut diagnostics,
+    &VoxelDiagnosticsPlugin::CHUNKS_QUEUED,
+    queue.queued as f64,
+  );
+
+  for chunk_pos in missing.into_iter().take(config.chunks_per_frame) {
+    let start = Instant::now();
+    let chunk = config.generator.generate(config.seed, chunk_pos);
+    add_measurement(
+      &mut diagnostics,
+      &VoxelDiagnosticsPlugin::GENERATION_TIME,
+      start.elapsed().as_secs_f64() * 1e3,
+    );
+    world.insert(chunk);
+    commands.spawn(ChunkCoord(chunk_pos));
+  }
+}
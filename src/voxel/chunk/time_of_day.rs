@@ -0,0 +1,215 @@
+use std::f32::consts::TAU;
+
+use bevy::{light::CascadeShadowConfigBuilder, prelude::*};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE, fog::VoxelFog, material::ChunkMaterial, sky::Sky, streaming::VoxelWorldConfig,
+};
+
+/// Pauses or resumes the day/night cycle.
+const TOGGLE_PAUSE: KeyCode = KeyCode::F6;
+/// Turns the time back while held.
+const SCRUB_BACK: KeyCode = KeyCode::BracketLeft;
+/// Turns the time forward while held.
+const SCRUB_FORWARD: KeyCode = KeyCode::BracketRight;
+/// Fraction of a day scrubbed per second.
+const SCRUB_SPEED: f32 = 0.1;
+
+/// Tilt of the path of the sun towards +Z, in radians.
+const SUN_TILT: f32 = 0.4;
+/// Illuminance of the sun at noon, in lux.
+const SUN_ILLUMINANCE: f32 = 5000.0;
+const NOON_SUN_COLOR: Color = Color::srgb(1.0, 0.97, 0.9);
+const SUNSET_SUN_COLOR: Color = Color::srgb(1.0, 0.55, 0.3);
+
+const DAY_AMBIENT: f32 = 300.0;
+const NIGHT_AMBIENT: f32 = 60.0;
+const DAY_AMBIENT_COLOR: Color = Color::WHITE;
+const NIGHT_AMBIENT_COLOR: Color = Color::srgb(0.5, 0.6, 1.0);
+
+const DAY_ZENITH: Color = Color::srgb(0.25, 0.45, 0.85);
+const DAY_HORIZON: Color = Color::srgb(0.62, 0.75, 0.9);
+const SUNSET_HORIZON: Color = Color::srgb(0.95, 0.55, 0.35);
+const NIGHT_ZENITH: Color = Color::srgb(0.01, 0.015, 0.04);
+const NIGHT_HORIZON: Color = Color::srgb(0.04, 0.05, 0.1);
+
+/// Baked sky light that remains at night.
+const NIGHT_SKY_LIGHT: f32 = 0.15;
+/// [`ChunkMaterial::sky_light`] only changes in these steps, as every change
+/// rebuilds the bind groups of all chunk materials.
+const SKY_LIGHT_STEPS: f32 = 32.0;
+
+/// Time of the day/night cycle, which moves the [`Sun`] and colours the sky, the
+/// ambient light and the fog.
+#[derive(Resource, Clone, Debug)]
+pub struct TimeOfDay {
+  /// Fraction of the day, 0 is midnight and 0.5 noon.
+  pub time: f32,
+  /// Seconds a full day takes.
+  pub day_length: f32,
+  pub paused: bool,
+}
+
+impl Default for TimeOfDay {
+  fn default() -> Self {
+    Self {
+      time: 0.35,
+      day_length: 600.0,
+      paused: false,
+    }
+  }
+}
+
+impl TimeOfDay {
+  /// Direction towards the sun, which rises in +X at 0.25 and sets in -X at 0.75.
+  pub fn sun_direction(&self) -> Vec3 {
+    let angle = (self.time - 0.25) * TAU;
+    Vec3::new(
+      angle.cos(),
+      angle.sin() * SUN_TILT.cos(),
+      angle.sin() * SUN_TILT.sin(),
+    )
+  }
+
+  /// How bright the day is, from 0 at night to 1 once the sun is up, with a short
+  /// twilight around sunrise and sunset.
+  pub fn daylight(&self) -> f32 {
+    smoothstep(-0.1, 0.2, self.sun_direction().y)
+  }
+
+  /// How close the sun is to the horizon, 1 at sunrise and sunset.
+  fn twilight(&self) -> f32 {
+    (1.0 - self.sun_direction().y.abs() / 0.25).max(0.0)
+  }
+
+  /// Scale of the baked sky light of chunks.
+  pub fn sky_light(&self) -> f32 {
+    NIGHT_SKY_LIGHT + (1.0 - NIGHT_SKY_LIGHT) * self.daylight()
+  }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+  let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+  t * t * (3.0 - 2.0 * t)
+}
+
+/// Directional light of the sun, casting shadows on the chunks around the views.
+#[derive(Component)]
+pub struct Sun;
+
+/// Runs the day/night cycle of the [`TimeOfDay`], starting at `time_of_day`.
+/// F6 pauses it and `[` and `]` scrub through the day while held.
+#[derive(Default)]
+pub struct TimeOfDayPlugin {
+  pub time_of_day: TimeOfDay,
+}
+
+impl Plugin for TimeOfDayPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .insert_resource(self.time_of_day.clone())
+      .add_systems(Startup, spawn_sun)
+      .add_systems(
+        Update,
+        (
+          advance_time_of_day,
+          apply_time_of_day.run_if(resource_changed::<TimeOfDay>),
+        )
+          .chain(),
+      )
+      // after the chunk materials of the frame were created
+      .add_systems(PostUpdate, update_chunk_sky_light);
+  }
+}
+
+fn spawn_sun(mut commands: Commands, config: Res<VoxelWorldConfig>) {
+  commands.spawn((
+    Sun,
+    DirectionalLight {
+      shadows_enabled: true,
+      ..default()
+    },
+    CascadeShadowConfigBuilder {
+      first_cascade_far_bound: 2.0 * CHUNK_SIZE as f32,
+      maximum_distance: config.render_distance as f32 * CHUNK_SIZE as f32,
+      ..default()
+    }
+    .build(),
+  ));
+}
+
+fn advance_time_of_day(
+  time: Res<Time>,
+  keys: Res<ButtonInput<KeyCode>>,
+  mut time_of_day: ResMut<TimeOfDay>,
+) {
+  if keys.just_pressed(TOGGLE_PAUSE) {
+    time_of_day.paused = !time_of_day.paused;
+  }
+
+  let mut delta = 0.0;
+  if !time_of_day.paused && time_of_day.day_length > 0.0 {
+    delta += time.delta_secs() / time_of_day.day_length;
+  }
+  if keys.pressed(SCRUB_FORWARD) {
+    delta += SCRUB_SPEED * time.delta_secs();
+  }
+  if keys.pressed(SCRUB_BACK) {
+    delta -= SCRUB_SPEED * time.delta_secs();
+  }
+  if delta != 0.0 {
+    time_of_day.time = (time_of_day.time + delta).rem_euclid(1.0);
+  }
+}
+
+fn apply_time_of_day(
+  time_of_day: Res<TimeOfDay>,
+  mut suns: Query<(&mut DirectionalLight, &mut Transform), With<Sun>>,
+  mut ambient: ResMut<AmbientLight>,
+  mut fog: ResMut<VoxelFog>,
+  sky: Option<ResMut<Sky>>,
+) {
+  let sun_direction = time_of_day.sun_direction();
+  let daylight = time_of_day.daylight();
+  let twilight = time_of_day.twilight();
+
+  let sun_color = SUNSET_SUN_COLOR.mix(&NOON_SUN_COLOR, smoothstep(0.0, 0.3, sun_direction.y));
+  for (mut light, mut transform) in &mut suns {
+    light.illuminance = SUN_ILLUMINANCE * daylight;
+    light.color = sun_color;
+    *transform = Transform::default().looking_to(-sun_direction, Vec3::Y);
+  }
+
+  ambient.brightness = NIGHT_AMBIENT + (DAY_AMBIENT - NIGHT_AMBIENT) * daylight;
+  ambient.color = NIGHT_AMBIENT_COLOR.mix(&DAY_AMBIENT_COLOR, daylight);
+
+  let horizon = NIGHT_HORIZON
+    .mix(&DAY_HORIZON, daylight)
+    .mix(&SUNSET_HORIZON, 0.6 * twilight);
+  fog.color = horizon;
+  if let Some(mut sky) = sky {
+    sky.zenith = NIGHT_ZENITH.mix(&DAY_ZENITH, daylight);
+    sky.horizon = horizon;
+    sky.sun_direction = sun_direction;
+    sky.sun_color = sun_color.mix(&Color::BLACK, 1.0 - daylight);
+  }
+}
+
+/// Sets [`ChunkMaterial::sky_light`] of every chunk material, including the ones
+/// created this frame, to the current [`TimeOfDay::sky_light`].
+fn update_chunk_sky_light(
+  time_of_day: Res<TimeOfDay>,
+  mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+  let sky_light = (time_of_day.sky_light() * SKY_LIGHT_STEPS).round() / SKY_LIGHT_STEPS;
+  let outdated: Vec<_> = materials
+    .iter()
+    .filter(|(_, material)| material.sky_light != sky_light)
+    .map(|(id, _)| id)
+    .collect();
+  for id in outdated {
+    if let Some(material) = materials.get_mut(id) {
+      material.sky_light = sky_light;
+    }
+  }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+
+use crate::voxel::chunk::mesh::{quad_center, quads_mesh};
+
+/// Distance the camera has to move, in blocks, before translucent quads are sorted again.
+const RESORT_DISTANCE: f32 = 1.0;
+
+/// Translucent quads of a chunk, kept on the CPU so they can be sorted back to front.
+#[derive(Component)]
+pub struct TranslucentChunk {
+  quads: Vec<u32>,
+  /// Camera position in chunk space the quads were last sorted for.
+  sorted_from: Option<Vec3>,
+}
+
+impl TranslucentChunk {
+  pub fn new(quads: Vec<u32>) -> Self {
+    Self {
+      quads,
+      sorted_from: None,
+    }
+  }
+
+  fn sort(&mut self, camera: Vec3) {
+    let mut keyed: Vec<_> = self
+      .quads
+      .iter()
+      .map(|&quad| (quad_center(quad).distance_squared(camera), quad))
+      .collect();
+    keyed.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    self.quads = keyed.into_iter().map(|(_, quad)| quad).collect();
+    self.sorted_from = Some(camera);
+  }
+}
+
+/// Sorts translucent quads back to front for the active 3D camera with the lowest
+/// [`Camera::order`]. A mesh has a single order, so with split-screen the other
+/// viewports see the quads sorted for the first one.
+pub fn sort_translucent_chunks(
+  cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+  mut chunks: Query<(&mut TranslucentChunk, &Mesh3d, &GlobalTransform)>,
+  mut meshes: ResMut<Assets<Mesh>>,
+) {
+  let Some((_, camera)) = cameras
+    .iter()
+    .filter(|(camera, _)| camera.is_active)
+    .min_by_key(|(camera, _)| camera.order)
+  else {
+    return;
+  };
+
+  for (mut chunk, mesh, transform) in &mut chunks {
+    let camera_pos = transform
+      .affine()
+      .inverse()
+      .transform_point3(camera.translation());
+    if chunk
+      .sorted_from
+      .is_some_and(|sorted_from| sorted_from.distance(camera_pos) < RESORT_DISTANCE)
+    {
+      continue;
+    }
+
+    chunk.sort(camera_pos);
+    let _ = meshes.insert(mesh.id(), quads_mesh(&chunk.quads));
+  }
+}
@@ -0,0 +1,854 @@
+use std::{
+  f32::consts::FRAC_PI_2,
+  fs,
+  io::{self, BufWriter, Write},
+  path::Path,
+};
+
+use bevy::{
+  asset::{AssetLoader, LoadContext, io::Reader},
+  platform::collections::HashMap,
+  prelude::*,
+};
+use thiserror::Error;
+
+use crate::voxel::chunk::{
+  block,
+  generation::ChunkBlockData,
+  schematic::{BlockMapping, BlockVolume, SchematicError},
+  streaming::ChunkViewer,
+  world::{VoxelWorld, local_position},
+};
+
+/// Deepest scene graph that is followed, deeper nodes are ignored.
+const MAX_SCENE_DEPTH: usize = 64;
+/// Distance in front of the camera at which dropped files are placed.
+const DROP_DISTANCE: f32 = 24.0;
+
+/// Voxels of a MagicaVoxel `.vox` file with the transforms of its scene graph applied.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct VoxModel {
+  /// Position of each voxel, converted to Y up, and its palette index.
+  pub voxels: Vec<(IVec3, u8)>,
+  /// Colour of each palette index, index 0 is empty. Files without a palette are
+  /// all white.
+  pub palette: [Srgba; 256],
+}
+
+/// Node of the scene graph, see the `nTRN`, `nGRP` and `nSHP` chunks.
+enum SceneNode {
+  Transform {
+    child: i32,
+    rotation: Mat3,
+    translation: Vec3,
+  },
+  Group(Vec<i32>),
+  Shape(Vec<i32>),
+}
+
+/// A model from a `SIZE` and `XYZI` chunk pair.
+struct Model {
+  size: IVec3,
+  /// Position and palette index of every voxel, Z up.
+  voxels: Vec<([u8; 3], u8)>,
+}
+
+impl VoxModel {
+  pub fn parse(bytes: &[u8]) -> Result<Self, VoxError> {
+    let mut reader = ByteReader { bytes, pos: 0 };
+    if reader.take(4)? != b"VOX " {
+      return Err(VoxError::Header);
+    }
+    let _version = reader.i32()?;
+
+    let (id, _content, children) = reader.chunk()?;
+    if id != b"MAIN" {
+      return Err(VoxError::Header);
+    }
+
+    let mut models = Vec::new();
+    let mut size = None;
+    let mut palette = [Srgba::WHITE; 256];
+    palette[0] = Srgba::NONE;
+    let mut nodes = HashMap::new();
+
+    let mut reader = ByteReader {
+      bytes: children,
+      pos: 0,
+    };
+    while reader.pos < reader.bytes.len() {
+      let (id, content, _children) = reader.chunk()?;
+      let mut content = ByteReader {
+        bytes: content,
+        pos: 0,
+      };
+      match id {
+        b"SIZE" => {
+          size = Some(IVec3::new(content.i32()?, content.i32()?, content.i32()?));
+        }
+        b"XYZI" => {
+          let count = content.i32()?.max(0) as usize;
+          // the count isn't trusted, a voxel takes four bytes
+          let mut voxels = Vec::with_capacity(count.min(content.remaining() / 4));
+          for _ in 0..count {
+            let bytes = content.take(4)?;
+            voxels.push(([bytes[0], bytes[1], bytes[2]], bytes[3]));
+          }
+          models.push(Model {
+            size: size.take().ok_or(VoxError::Format("XYZI without SIZE"))?,
+            voxels,
+          });
+        }
+        b"RGBA" => {
+          // palette index i is stored at i - 1
+          for color in palette.iter_mut().skip(1) {
+            let bytes = content.take(4)?;
+            *color = Srgba::rgba_u8(bytes[0], bytes[1], bytes[2], bytes[3]);
+          }
+        }
+        b"nTRN" => {
+          let id = content.i32()?;
+          let _attributes = content.dict()?;
+          let child = content.i32()?;
+          let _reserved = content.i32()?;
+          let _layer = content.i32()?;
+          let frames = content.i32()?;
+          // only the first animation frame is used
+          let frame = if frames > 0 {
+            content.dict()?
+          } else {
+            HashMap::new()
+          };
+          let rotation = frame
+            .get("_r")
+            .and_then(|r| r.parse().ok())
+            .map_or(Mat3::IDENTITY, decode_rotation);
+          let translation = frame.get("_t").map_or(Vec3::ZERO, |t| {
+            let mut parts = t.split_whitespace().map(|part| part.parse().unwrap_or(0.0));
+            Vec3::new(
+              parts.next().unwrap_or(0.0),
+              parts.next().unwrap_or(0.0),
+              parts.next().unwrap_or(0.0),
+            )
+          });
+          nodes.insert(
+            id,
+            SceneNode::Transform {
+              child,
+              rotation,
+              translation,
+            },
+          );
+        }
+        b"nGRP" => {
+          let id = content.i32()?;
+          let _attributes = content.dict()?;
+          let count = content.i32()?.max(0);
+          let children = (0..count)
+            .map(|_| content.i32())
+            .collect::<Result<_, _>>()?;
+          nodes.insert(id, SceneNode::Group(children));
+        }
+        b"nSHP" => {
+          let id = content.i32()?;
+          let _attributes = content.dict()?;
+          let count = content.i32()?.max(0);
+          let mut shape_models = Vec::new();
+          for _ in 0..count {
+            shape_models.push(content.i32()?);
+            let _attributes = content.dict()?;
+          }
+          nodes.insert(id, SceneNode::Shape(shape_models));
+        }
+        _ => {}
+      }
+    }
+
+    let mut voxels = Vec::new();
+    if nodes.contains_key(&0) {
+      place_node(
+        &nodes,
+        &models,
+        0,
+        Mat3::IDENTITY,
+        Vec3::ZERO,
+        0,
+        &mut voxels,
+      );
+    } else {
+      // files without a scene graph keep every model at the origin
+      for model in &models {
+        for &([x, y, z], index) in &model.voxels {
+          voxels.push((
+            to_y_up(Vec3::new(x as f32, y as f32, z as f32) + 0.5),
+            index,
+          ));
+        }
+      }
+    }
+
+    Ok(Self { voxels, palette })
+  }
+
+  /// Block for each palette index, the one whose base colour is closest.
+  ///
+  /// Blocks only store an id, so voxels can't keep their own colour.
+  pub fn palette_blocks(&self) -> [u8; 256] {
+    const CANDIDATES: [u8; 5] = [
+      block::GRASS,
+      block::WATER,
+      block::GLASS,
+      block::LEAVES,
+      block::LAVA,
+    ];
+
+    let mut blocks = [block::AIR; 256];
+    for (block, color) in blocks.iter_mut().zip(self.palette).skip(1) {
+      let color = Vec4::from_array(LinearRgba::from(color).to_f32_array());
+      *block = CANDIDATES
+        .into_iter()
+        .min_by(|a, b| {
+          let distance =
+            |block| Vec4::from_array(block::base_color(block).to_f32_array()).distance(color);
+          distance(*a).total_cmp(&distance(*b))
+        })
+        .unwrap_or(block::GRASS);
+    }
+    blocks
+  }
+
+  /// World positions and blocks of every voxel, rotated around the model origin and
+  /// moved to `origin`. Rotations that aren't multiples of 90 degrees leave gaps.
+  pub fn placed_blocks(
+    &self,
+    origin: IVec3,
+    rotation: Quat,
+    palette_blocks: &[u8; 256],
+  ) -> Vec<(IVec3, u8)> {
+    self
+      .voxels
+      .iter()
+      .map(|&(pos, index)| {
+        let center = rotation * (pos.as_vec3() + 0.5);
+        (
+          center.floor().as_ivec3() + origin,
+          palette_blocks[index as usize],
+        )
+      })
+      .collect()
+  }
+}
+
+/// Adds the voxels below a scene graph node, `rotation` and `translation` are
+/// the accumulated transforms of its parents in Z up space.
+fn place_node(
+  nodes: &HashMap<i32, SceneNode>,
+  models: &[Model],
+  id: i32,
+  rotation: Mat3,
+  translation: Vec3,
+  depth: usize,
+  voxels: &mut Vec<(IVec3, u8)>,
+) {
+  if depth > MAX_SCENE_DEPTH {
+    return;
+  }
+  match nodes.get(&id) {
+    Some(SceneNode::Transform {
+      child,
+      rotation: local_rotation,
+      translation: local_translation,
+    }) => place_node(
+      nodes,
+      models,
+      *child,
+      rotation * *local_rotation,
+      rotation * *local_translation + translation,
+      depth + 1,
+      voxels,
+    ),
+    Some(SceneNode::Group(children)) => {
+      for child in children {
+        place_node(
+          nodes,
+          models,
+          *child,
+          rotation,
+          translation,
+          depth + 1,
+          voxels,
+        );
+      }
+    }
+    Some(SceneNode::Shape(shape_models)) => {
+      for model in shape_models
+        .iter()
+        .filter_map(|id| models.get(*id as usize))
+      {
+        // models are centered on their translation, rounding down
+        let center = (model.size / 2).as_vec3();
+        for &([x, y, z], index) in &model.voxels {
+          let local = Vec3::new(x as f32, y as f32, z as f32) + 0.5 - center;
+          voxels.push((to_y_up(rotation * local + translation), index));
+        }
+      }
+    }
+    None => {}
+  }
+}
+
+/// Cell containing a point given in the Z up space of `.vox` files.
+fn to_y_up(point: Vec3) -> IVec3 {
+  Vec3::new(point.x, point.z, -point.y).floor().as_ivec3()
+}
+
+/// Decodes the packed rotation of a `nTRN` frame.
+///
+/// Bits 0-1 and 2-3 are the column of the non-zero entry in the first and second
+/// row, the third row uses the remaining column. Bits 4 to 6 make the entry of the
+/// first, second or third row negative.
+fn decode_rotation(packed: u8) -> Mat3 {
+  let first = (packed & 3) as usize;
+  let second = ((packed >> 2) & 3) as usize;
+  let third = 3 - first - second;
+  if first > 2 || second > 2 || first == second {
+    return Mat3::IDENTITY;
+  }
+
+  let row = |column: usize, negative: bool| {
+    let mut row = Vec3::ZERO;
+    row[column] = if negative { -1.0 } else { 1.0 };
+    row
+  };
+  Mat3::from_cols(
+    row(first, packed & 0x10 != 0),
+    row(second, packed & 0x20 != 0),
+    row(third, packed & 0x40 != 0),
+  )
+  .transpose()
+}
+
+/// Largest model along each axis.
+const MAX_MODEL_SIZE: u32 = 256;
+
+impl BlockVolume {
+  /// Writes a `.vox` file, split into models of at most 256 voxels per axis that
+  /// are placed next to each other in the scene graph. Palette index is the block
+  /// id, coloured by `mapping`.
+  pub fn write_vox(&self, path: impl AsRef<Path>, mapping: &BlockMapping) -> Result<(), VoxError> {
+    // Z up, with y running against world z
+    let vox_size = UVec3::new(self.size.x, self.size.z, self.size.y);
+    let tiles = vox_size.map(|size| size.div_ceil(MAX_MODEL_SIZE));
+
+    let mut models = Vec::new();
+    let mut scene = Vec::new();
+    let mut shapes = Vec::new();
+    for tile_z in 0..tiles.z {
+      for tile_y in 0..tiles.y {
+        for tile_x in 0..tiles.x {
+          let offset = UVec3::new(tile_x, tile_y, tile_z) * MAX_MODEL_SIZE;
+          let size = (vox_size - offset).min(UVec3::splat(MAX_MODEL_SIZE));
+
+          let mut voxels = Vec::new();
+          for z in 0..size.z {
+            for y in 0..size.y {
+              for x in 0..size.x {
+                let vox = offset + UVec3::new(x, y, z);
+                let block = self.get(UVec3::new(vox.x, vox.z, self.size.z - 1 - vox.y));
+                if block != block::AIR {
+                  voxels.extend([x as u8, y as u8, z as u8, block]);
+                }
+              }
+            }
+          }
+          if voxels.is_empty() {
+            continue;
+          }
+
+          let mut size_content = Vec::new();
+          write_ints(&mut size_content, &size.as_ivec3().to_array());
+          write_chunk(&mut models, b"SIZE", &size_content, &[]);
+          let mut xyzi = Vec::new();
+          write_ints(&mut xyzi, &[(voxels.len() / 4) as i32]);
+          xyzi.extend(voxels);
+          write_chunk(&mut models, b"XYZI", &xyzi, &[]);
+
+          // models are centred on their translation, rounding down
+          let center = (offset + size / 2).as_ivec3();
+          shapes.push(format!("{} {} {}", center.x, center.y, center.z));
+        }
+      }
+    }
+
+    // root transform, then a group with a transform and shape per model
+    let node_count = 2 + 2 * shapes.len() as i32;
+    write_transform(&mut scene, 0, 1, None);
+    let mut group = Vec::new();
+    write_ints(&mut group, &[1]);
+    write_dict(&mut group, &[]);
+    write_ints(&mut group, &[shapes.len() as i32]);
+    write_ints(&mut group, &(2..node_count).step_by(2).collect::<Vec<_>>());
+    write_chunk(&mut scene, b"nGRP", &group, &[]);
+    for (model, translation) in shapes.iter().enumerate() {
+      let id = 2 + 2 * model as i32;
+      write_transform(&mut scene, id, id + 1, Some(translation));
+      let mut shape = Vec::new();
+      write_ints(&mut shape, &[id + 1]);
+      write_dict(&mut shape, &[]);
+      write_ints(&mut shape, &[1, model as i32]);
+      write_dict(&mut shape, &[]);
+      write_chunk(&mut scene, b"nSHP", &shape, &[]);
+    }
+
+    // palette index i is stored at i - 1
+    let mut palette = Vec::with_capacity(4 * 256);
+    for index in 1..=256 {
+      palette.extend(mapping.color(index as u8));
+    }
+    write_chunk(&mut scene, b"RGBA", &palette, &[]);
+
+    models.extend(scene);
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    file.write_all(b"VOX ")?;
+    file.write_all(&200i32.to_le_bytes())?;
+    let mut main = Vec::new();
+    write_chunk(&mut main, b"MAIN", &[], &models);
+    file.write_all(&main)?;
+    file.flush()?;
+    Ok(())
+  }
+}
+
+fn write_ints(bytes: &mut Vec<u8>, values: &[i32]) {
+  for value in values {
+    bytes.extend(value.to_le_bytes());
+  }
+}
+
+fn write_dict(bytes: &mut Vec<u8>, entries: &[(&str, &str)]) {
+  write_ints(bytes, &[entries.len() as i32]);
+  for (key, value) in entries {
+    for string in [key, value] {
+      write_ints(bytes, &[string.len() as i32]);
+      bytes.extend(string.as_bytes());
+    }
+  }
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+  bytes.extend(id);
+  write_ints(bytes, &[content.len() as i32, children.len() as i32]);
+  bytes.extend(content);
+  bytes.extend(children);
+}
+
+/// Writes a `nTRN` chunk with a single frame. Transforms without a translation are
+/// the root, which isn't on a layer.
+fn write_transform(bytes: &mut Vec<u8>, id: i32, child: i32, translation: Option<&str>) {
+  let mut content = Vec::new();
+  write_ints(&mut content, &[id]);
+  write_dict(&mut content, &[]);
+  match translation {
+    Some(translation) => {
+      write_ints(&mut content, &[child, -1, 0, 1]);
+      write_dict(&mut content, &[("_t", translation)]);
+    }
+    None => {
+      write_ints(&mut content, &[child, -1, -1, 1]);
+      write_dict(&mut content, &[]);
+    }
+  }
+  write_chunk(bytes, b"nTRN", &content, &[]);
+}
+
+/// Id, content and children of a chunk.
+type Chunk<'a> = (&'a [u8], &'a [u8], &'a [u8]);
+
+struct ByteReader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+  fn remaining(&self) -> usize {
+    self.bytes.len() - self.pos
+  }
+
+  fn take(&mut self, len: usize) -> Result<&'a [u8], VoxError> {
+    let end = self.pos.checked_add(len).ok_or(VoxError::UnexpectedEnd)?;
+    let bytes = self
+      .bytes
+      .get(self.pos..end)
+      .ok_or(VoxError::UnexpectedEnd)?;
+    self.pos = end;
+    Ok(bytes)
+  }
+
+  fn i32(&mut self) -> Result<i32, VoxError> {
+    let bytes = self.take(4)?;
+    Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+  }
+
+  fn string(&mut self) -> Result<String, VoxError> {
+    let len = self.i32()?.max(0) as usize;
+    Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+  }
+
+  fn dict(&mut self) -> Result<HashMap<String, String>, VoxError> {
+    let count = self.i32()?.max(0);
+    (0..count)
+      .map(|_| Ok((self.string()?, self.string()?)))
+      .collect()
+  }
+
+  /// Reads a chunk header and returns its id, content and children.
+  fn chunk(&mut self) -> Result<Chunk<'a>, VoxError> {
+    let id = self.take(4)?;
+    let content_len = self.i32()?.max(0) as usize;
+    let children_len = self.i32()?.max(0) as usize;
+    Ok((id, self.take(content_len)?, self.take(children_len)?))
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum VoxError {
+  #[error("could not read vox file: {0}")]
+  Io(#[from] io::Error),
+  #[error("not a vox file")]
+  Header,
+  #[error("vox file ends unexpectedly")]
+  UnexpectedEnd,
+  #[error("invalid vox file: {0}")]
+  Format(&'static str),
+}
+
+#[derive(Default)]
+struct VoxLoader;
+
+impl AssetLoader for VoxLoader {
+  type Asset = VoxModel;
+  type Settings = ();
+  type Error = VoxError;
+
+  async fn load(
+    &self,
+    reader: &mut dyn Reader,
+    _settings: &(),
+    _load_context: &mut LoadContext<'_>,
+  ) -> Result<VoxModel, VoxError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).await?;
+    VoxModel::parse(&bytes)
+  }
+
+  fn extensions(&self) -> &[&str] {
+    &["vox"]
+  }
+}
+
+impl ChunkBlockData {
+  /// Writes the blocks that fall into this chunk or its border, returns whether
+  /// any block changed. Also works on freshly generated chunks to place structures.
+  pub fn stamp(&mut self, blocks: &[(IVec3, u8)]) -> bool {
+    let mut changed = false;
+    for &(pos, block) in blocks {
+      if let Some(local) = local_position(pos, self.chunk_pos)
+        && self.get(local) != block
+      {
+        self.set(local, block);
+        changed = true;
+      }
+    }
+    changed
+  }
+}
+
+/// Loads MagicaVoxel `.vox` files as [`VoxModel`] assets and stamps `.vox` files
+/// and Sponge schematics dropped onto the window into the [`VoxelWorld`] in front
+/// of the camera.
+pub struct VoxPlugin;
+
+impl Plugin for VoxPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_asset::<VoxModel>()
+      .init_asset_loader::<VoxLoader>()
+      .add_systems(Update, stamp_dropped_files);
+  }
+}
+
+fn stamp_dropped_files(
+  mut drops: MessageReader<FileDragAndDrop>,
+  viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+  mut world: ResMut<VoxelWorld>,
+) {
+  for drop in drops.read() {
+    let FileDragAndDrop::DroppedFile { path_buf, .. } = drop else {
+      continue;
+    };
+    let extension = path_buf.extension().and_then(|ext| ext.to_str());
+    if !matches!(extension, Some("vox" | "schem")) {
+      continue;
+    }
+    let Some(viewer) = viewers.iter().next() else {
+      continue;
+    };
+
+    // face the model the same way as the camera, in quarter turns
+    let (yaw, _, _) = viewer.rotation().to_euler(EulerRot::YXZ);
+    let rotation = Quat::from_rotation_y((yaw / FRAC_PI_2).round() * FRAC_PI_2);
+    let origin = (viewer.translation() + viewer.forward() * DROP_DISTANCE)
+      .floor()
+      .as_ivec3();
+
+    let blocks = if extension == Some("vox") {
+      fs::read(path_buf)
+        .map_err(VoxError::from)
+        .and_then(|bytes| VoxModel::parse(&bytes))
+        .map(|model| model.placed_blocks(origin, rotation, &model.palette_blocks()))
+        .map_err(|error| error.to_string())
+    } else {
+      // schematics keep their orientation
+      fs::read(path_buf)
+        .map_err(SchematicError::from)
+        .and_then(|bytes| BlockVolume::parse_schem(&bytes, &BlockMapping::default()))
+        .map(|volume| volume.placed_blocks(origin))
+        .map_err(|error| error.to_string())
+    };
+    let blocks = match blocks {
+      Ok(blocks) => blocks,
+      Err(error) => {
+        error!("Could not load {}: {error}", path_buf.display());
+        continue;
+      }
+    };
+
+    world.stamp(&blocks);
+    info!(
+      "Placed {} voxels from {} at {origin}",
+      blocks.len(),
+      path_buf.display()
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A `.vox` file with `chunks` as children of the `MAIN` chunk.
+  fn vox_file(chunks: &[u8]) -> Vec<u8> {
+    let mut bytes = b"VOX ".to_vec();
+    write_ints(&mut bytes, &[150]);
+    write_chunk(&mut bytes, b"MAIN", &[], chunks);
+    bytes
+  }
+
+  /// A `SIZE` and `XYZI` chunk pair.
+  fn model(size: [i32; 3], voxels: &[[u8; 4]]) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    let mut size_content = Vec::new();
+    write_ints(&mut size_content, &size);
+    write_chunk(&mut chunks, b"SIZE", &size_content, &[]);
+    let mut xyzi = Vec::new();
+    write_ints(&mut xyzi, &[voxels.len() as i32]);
+    xyzi.extend(voxels.iter().flatten());
+    write_chunk(&mut chunks, b"XYZI", &xyzi, &[]);
+    chunks
+  }
+
+  /// A `nTRN` chunk with a single frame holding `frame`.
+  fn transform(id: i32, child: i32, frame: &[(&str, &str)]) -> Vec<u8> {
+    let mut content = Vec::new();
+    write_ints(&mut content, &[id]);
+    write_dict(&mut content, &[]);
+    write_ints(&mut content, &[child, -1, 0, 1]);
+    write_dict(&mut content, frame);
+    let mut chunk = Vec::new();
+    write_chunk(&mut chunk, b"nTRN", &content, &[]);
+    chunk
+  }
+
+  /// Root transform, a group with a single transform with `frame` and a shape
+  /// showing model 0.
+  fn scene(frame: &[(&str, &str)]) -> Vec<u8> {
+    let mut chunks = transform(0, 1, &[]);
+    let mut group = Vec::new();
+    write_ints(&mut group, &[1]);
+    write_dict(&mut group, &[]);
+    write_ints(&mut group, &[1, 2]);
+    write_chunk(&mut chunks, b"nGRP", &group, &[]);
+    chunks.extend(transform(2, 3, frame));
+    let mut shape = Vec::new();
+    write_ints(&mut shape, &[3]);
+    write_dict(&mut shape, &[]);
+    write_ints(&mut shape, &[1, 0]);
+    write_dict(&mut shape, &[]);
+    write_chunk(&mut chunks, b"nSHP", &shape, &[]);
+    chunks
+  }
+
+  #[test]
+  fn parses_size_and_xyzi() {
+    let bytes = vox_file(&model([2, 3, 4], &[[0, 0, 0, 1], [1, 2, 3, 5]]));
+    let model = VoxModel::parse(&bytes).unwrap();
+
+    // Z up becomes Y up, with vox y running against world z
+    assert_eq!(
+      model.voxels,
+      vec![(IVec3::new(0, 0, -1), 1), (IVec3::new(1, 3, -3), 5)]
+    );
+  }
+
+  #[test]
+  fn default_palette_without_rgba() {
+    let model = VoxModel::parse(&vox_file(&model([1, 1, 1], &[[0, 0, 0, 7]]))).unwrap();
+    assert_eq!(model.palette[0], Srgba::NONE);
+    assert!(
+      model.palette[1..]
+        .iter()
+        .all(|color| *color == Srgba::WHITE)
+    );
+  }
+
+  #[test]
+  fn parses_rgba_palette() {
+    let mut chunks = model([1, 1, 1], &[[0, 0, 0, 1]]);
+    let rgba: Vec<u8> = (0..=255u8).flat_map(|i| [i, 10, 20, 128]).collect();
+    write_chunk(&mut chunks, b"RGBA", &rgba, &[]);
+    let model = VoxModel::parse(&vox_file(&chunks)).unwrap();
+
+    // palette index i is stored at i - 1
+    assert_eq!(model.palette[0], Srgba::NONE);
+    assert_eq!(model.palette[1], Srgba::rgba_u8(0, 10, 20, 128));
+    assert_eq!(model.palette[255], Srgba::rgba_u8(254, 10, 20, 128));
+  }
+
+  #[test]
+  fn applies_scene_graph_translation() {
+    // the center voxel of a 3x3x3 model sits on the translation
+    let mut chunks = model([3, 3, 3], &[[1, 1, 1, 1], [0, 0, 0, 2]]);
+    chunks.extend(scene(&[("_t", "10 -4 3")]));
+    let model = VoxModel::parse(&vox_file(&chunks)).unwrap();
+
+    assert_eq!(
+      model.voxels,
+      vec![(IVec3::new(10, 3, 3), 1), (IVec3::new(9, 2, 4), 2)]
+    );
+  }
+
+  #[test]
+  fn applies_scene_graph_rotation() {
+    // a quarter turn around Z moves +X to +Y
+    let mut chunks = model([3, 3, 3], &[[2, 1, 1, 1]]);
+    chunks.extend(scene(&[("_r", "17")]));
+    let model = VoxModel::parse(&vox_file(&chunks)).unwrap();
+
+    // vox +Y is world -Z
+    assert_eq!(model.voxels, vec![(IVec3::new(-1, 0, -2), 1)]);
+  }
+
+  #[test]
+  fn decodes_rotations() {
+    // rows use columns 0, 1 and 2 without any negative entries
+    assert_eq!(decode_rotation(0b0000100), Mat3::IDENTITY);
+    let quarter_turn = decode_rotation(0b0010001);
+    assert_eq!(quarter_turn * Vec3::X, Vec3::Y);
+    assert_eq!(quarter_turn * Vec3::Y, Vec3::NEG_X);
+    assert_eq!(quarter_turn * Vec3::Z, Vec3::Z);
+    // mirrored along Z
+    assert_eq!(decode_rotation(0b1000100) * Vec3::Z, Vec3::NEG_Z);
+    // two rows with the same column or a column out of range
+    assert_eq!(decode_rotation(0b0000000), Mat3::IDENTITY);
+    assert_eq!(decode_rotation(0b0000011), Mat3::IDENTITY);
+  }
+
+  #[test]
+  fn rejects_invalid_files() {
+    assert!(matches!(
+      VoxModel::parse(b"RIFF\x96\0\0\0"),
+      Err(VoxError::Header)
+    ));
+
+    let mut chunks = model([3, 3, 3], &[[1, 1, 1, 1]]);
+    chunks.extend(scene(&[("_t", "1 2 3")]));
+    let bytes = vox_file(&chunks);
+    assert!(VoxModel::parse(&bytes).is_ok());
+    for len in 0..bytes.len() {
+      assert!(
+        VoxModel::parse(&bytes[..len]).is_err(),
+        "truncated to {len} bytes"
+      );
+    }
+  }
+
+  #[test]
+  fn rejects_oversized_chunks() {
+    // more voxels than the chunk holds
+    let mut chunks = Vec::new();
+    let mut size = Vec::new();
+    write_ints(&mut size, &[1, 1, 1]);
+    write_chunk(&mut chunks, b"SIZE", &size, &[]);
+    let mut xyzi = Vec::new();
+    write_ints(&mut xyzi, &[i32::MAX]);
+    xyzi.extend([0, 0, 0, 1]);
+    write_chunk(&mut chunks, b"XYZI", &xyzi, &[]);
+    assert!(matches!(
+      VoxModel::parse(&vox_file(&chunks)),
+      Err(VoxError::UnexpectedEnd)
+    ));
+
+    // a chunk longer than the file, the content length of `SIZE` follows the
+    // header, the `MAIN` chunk header and the `SIZE` id
+    let mut bytes = vox_file(&model([1, 1, 1], &[[0, 0, 0, 1]]));
+    bytes[24..28].copy_from_slice(&i32::MAX.to_le_bytes());
+    assert!(matches!(
+      VoxModel::parse(&bytes),
+      Err(VoxError::UnexpectedEnd)
+    ));
+  }
+
+  #[test]
+  fn write_vox_round_trip() {
+    // wider than a single model
+    let size = UVec3::new(260, 3, 2);
+    let mut volume = BlockVolume {
+      min: IVec3::ZERO,
+      size,
+      blocks: vec![block::AIR; (size.x * size.y * size.z) as usize],
+    };
+    let mut placed = Vec::new();
+    for (pos, block) in [
+      (UVec3::new(0, 0, 0), block::GRASS),
+      (UVec3::new(1, 2, 1), block::GLASS),
+      (UVec3::new(255, 1, 0), block::LAVA),
+      (UVec3::new(256, 0, 1), block::WATER),
+      (UVec3::new(259, 2, 1), block::LEAVES),
+    ] {
+      volume.blocks[((pos.y * size.z + pos.z) * size.x + pos.x) as usize] = block;
+      placed.push((pos, block));
+    }
+
+    let path = std::env::temp_dir().join(format!("voxel_volume_{}.vox", std::process::id()));
+    let mapping = BlockMapping::default();
+    volume.write_vox(&path, &mapping).unwrap();
+    let bytes = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let model = VoxModel::parse(&bytes).unwrap();
+
+    // the scene origin is at the far z edge of the volume
+    let mut voxels: Vec<_> = model
+      .voxels
+      .iter()
+      .map(|&(pos, index)| ((pos + IVec3::Z * size.z as i32).as_uvec3(), index))
+      .collect();
+    voxels.sort_by_key(|(pos, _)| pos.to_array());
+    placed.sort_by_key(|(pos, _)| pos.to_array());
+    assert_eq!(voxels, placed);
+
+    for (_, block) in placed {
+      let [r, g, b, a] = mapping.color(block);
+      assert_eq!(model.palette[block as usize], Srgba::rgba_u8(r, g, b, a));
+    }
+  }
+}
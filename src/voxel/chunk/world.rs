@@ -0,0 +1,319 @@
+use bevy::{
+  diagnostic::DiagnosticsStore,
+  math::USizeVec3,
+  platform::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+  },
+  prelude::*,
+  render::storage::ShaderStorageBuffer,
+};
+
+use crate::voxel::chunk::{
+  CHUNK_SIZE, block,
+  diagnostics::{VoxelDiagnosticsPlugin, add_measurement},
+  fluid,
+  fog::VoxelFog,
+  generation::ChunkBlockData,
+  indirect::ChunkBackend,
+  liquid::LiquidMaterials,
+  material::{ChunkMaterial, ChunkMeshLayout},
+  mesh::ChunkMeshData,
+  props::PropMeshes,
+  streaming::VoxelWorldConfig,
+};
+
+/// Position of the chunk an entity was meshed from.
+#[derive(Component, Clone, Copy, Debug, Deref)]
+pub struct ChunkCoord(pub IVec3);
+
+/// Number of opaque and translucent quads in the meshes of a chunk entity.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ChunkQuadCount(pub usize);
+
+/// Block data of all loaded chunks, addressed by world position.
+///
+/// Every chunk keeps a copy of the blocks bordering it, so changing a block
+/// touches up to eight chunks.
+#[derive(Resource, Default)]
+pub struct VoxelWorld {
+  chunks: HashMap<IVec3, ChunkBlockData>,
+  /// Chunks whose blocks changed since they were last meshed.
+  changed: HashSet<IVec3>,
+  /// Blocks that changed or were loaded since the
+  /// [`FluidSimulation`](super::fluid::FluidSimulation) last stepped.
+  fluid_wakes: HashSet<IVec3>,
+}
+
+impl VoxelWorld {
+  /// Adds a chunk, flowing liquid in it continues to flow or drains away.
+  pub fn insert(&mut self, chunk: ChunkBlockData) {
+    let origin = chunk.chunk_pos * CHUNK_SIZE as i32;
+    for x in 0..CHUNK_SIZE {
+      for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+          let local = USizeVec3::new(x, y, z);
+          if fluid::is_flowing(chunk.get(local + USizeVec3::ONE)) {
+            self.fluid_wakes.insert(origin + local.as_ivec3());
+          }
+        }
+      }
+    }
+
+    self.changed.insert(chunk.chunk_pos);
+    self.chunks.insert(chunk.chunk_pos, chunk);
+  }
+
+  pub fn remove(&mut self, chunk_pos: IVec3) -> Option<ChunkBlockData> {
+    self.changed.remove(&chunk_pos);
+    self.chunks.remove(&chunk_pos)
+  }
+
+  pub fn chunk(&self, chunk_pos: IVec3) -> Option<&ChunkBlockData> {
+    self.chunks.get(&chunk_pos)
+  }
+
+  /// Blocks that changed or were loaded since the last call, the fluid simulation
+  /// looks at them and their neighbors next.
+  pub(super) fn take_fluid_wakes(&mut self) -> HashSet<IVec3> {
+    std::mem::take(&mut self.fluid_wakes)
+  }
+
+  /// Number of chunks whose blocks changed and that wait to be re-meshed.
+  pub fn changed_count(&self) -> usize {
+    self.changed.len()
+  }
+
+  /// Positions of all loaded chunks.
+  pub fn chunk_positions(&self) -> impl Iterator<Item = IVec3> + '_ {
+    self.chunks.keys().copied()
+  }
+
+  /// Block at a world position, `None` if its chunk isn't loaded.
+  pub fn get_block(&self, pos: IVec3) -> Option<u8> {
+    let chunk_pos = pos.div_euclid(IVec3::splat(CHUNK_SIZE as i32));
+    let chunk = self.chunks.get(&chunk_pos)?;
+    Some(chunk.get(local_position(pos, chunk_pos)?))
+  }
+
+  /// Whether the block at a world position stops movement, unloaded blocks don't.
+  pub fn is_solid(&self, pos: IVec3) -> bool {
+    self.get_block(pos).is_some_and(block::is_solid)
+  }
+
+  /// Replaces the block at a world position, returns `false` if its chunk isn't loaded.
+  pub fn set_block(&mut self, pos: IVec3, block: u8) -> bool {
+    let chunk_size = IVec3::splat(CHUNK_SIZE as i32);
+    match self.get_block(pos) {
+      None => return false,
+      Some(previous) if previous != block => {
+        self.fluid_wakes.insert(pos);
+      }
+      Some(_) => {}
+    }
+
+    // the owning chunk and every neighbor whose border contains the block
+    let min = (pos - IVec3::ONE).div_euclid(chunk_size);
+    let max = (pos + IVec3::ONE).div_euclid(chunk_size);
+    for x in min.x..=max.x {
+      for y in min.y..=max.y {
+        for z in min.z..=max.z {
+          let chunk_pos = IVec3::new(x, y, z);
+          let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+            continue;
+          };
+          let Some(local) = local_position(pos, chunk_pos) else {
+            continue;
+          };
+          if chunk.get(local) != block {
+            chunk.set(local, block);
+            self.changed.insert(chunk_pos);
+          }
+        }
+      }
+    }
+    true
+  }
+}
+
+impl VoxelWorld {
+  /// Writes blocks at world positions into every loaded chunk containing them,
+  /// blocks in unloaded chunks are dropped.
+  pub fn stamp(&mut self, blocks: &[(IVec3, u8)]) {
+    let Some((min, max)) = blocks.iter().fold(None, |bounds, &(pos, _)| match bounds {
+      None => Some((pos, pos)),
+      Some((min, max)) => Some((pos.min(min), pos.max(max))),
+    }) else {
+      return;
+    };
+    self.fluid_wakes.extend(blocks.iter().map(|&(pos, _)| pos));
+
+    // chunks whose border reaches into the box are updated too
+    let chunk_size = IVec3::splat(CHUNK_SIZE as i32);
+    let min = (min - IVec3::ONE).div_euclid(chunk_size);
+    let max = (max + IVec3::ONE).div_euclid(chunk_size);
+    for (chunk_pos, chunk) in &mut self.chunks {
+      if chunk_pos.cmpge(min).all() && chunk_pos.cmple(max).all() && chunk.stamp(blocks) {
+        self.changed.insert(*chunk_pos);
+      }
+    }
+  }
+}
+
+/// Position of a world block inside the padded data of a chunk.
+#[inline]
+pub(super) fn local_position(pos: IVec3, chunk_pos: IVec3) -> Option<USizeVec3> {
+  let local = pos - chunk_pos * CHUNK_SIZE as i32 + IVec3::ONE;
+  (local.cmpge(IVec3::ZERO).all() && local.cmplt(IVec3::splat(CHUNK_SIZE as i32 + 2)).all())
+    .then(|| local.as_usizevec3())
+}
+
+/// Keeps the [`VoxelWorld`] and re-meshes chunk entities whose blocks changed for
+/// the [`ChunkBackend`] of the [`VoxelWorldConfig`].
+pub struct VoxelWorldPlugin;
+
+impl Plugin for VoxelWorldPlugin {
+  fn build(&self, app: &mut App) {
+    app.init_resource::<VoxelWorld>().add_systems(
+      PostUpdate,
+      (
+        remesh_changed_chunks.run_if(uses_backend(ChunkBackend::Meshes)),
+        remesh_indirect_chunks.run_if(uses_backend(ChunkBackend::Indirect)),
+      ),
+    );
+  }
+}
+
+fn uses_backend(backend: ChunkBackend) -> impl Fn(Res<VoxelWorldConfig>) -> bool {
+  move |config| config.backend == backend
+}
+
+/// Meshes a chunk for `layout` and records how long it took and how many quads it has.
+fn timed_mesh(
+  chunk: &ChunkBlockData,
+  layout: ChunkMeshLayout,
+  diagnostics: &mut Option<ResMut<DiagnosticsStore>>,
+) -> ChunkMeshData {
+  let start = Instant::now();
+  let mut mesh = chunk.create_mesh();
+  if layout == ChunkMeshLayout::GreedyQuads {
+    mesh.greedy_quads = Some(chunk.greedy_quads(&mesh.quads));
+  }
+  add_measurement(
+    diagnostics,
+    &VoxelDiagnosticsPlugin::MESHING_TIME,
+    start.elapsed().as_secs_f64() * 1e3,
+  );
+  add_measurement(
+    diagnostics,
+    &VoxelDiagnosticsPlugin::QUADS_PER_CHUNK,
+    mesh.quad_count() as f64,
+  );
+  mesh
+}
+
+#[allow(clippy::too_many_arguments)]
+fn remesh_changed_chunks(
+  mut commands: Commands,
+  mut world: ResMut<VoxelWorld>,
+  chunks: Query<(Entity, &ChunkCoord)>,
+  mut materials: ResMut<Assets<ChunkMaterial>>,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+  layout: Res<ChunkMeshLayout>,
+  liquid_materials: Res<LiquidMaterials>,
+  prop_meshes: Res<PropMeshes>,
+  fog: Res<VoxelFog>,
+  mut diagnostics: Option<ResMut<DiagnosticsStore>>,
+) {
+  if world.changed.is_empty() {
+    return;
+  }
+
+  for (entity, coord) in &chunks {
+    let Some(chunk) = world
+      .chunk(coord.0)
+      .filter(|_| world.changed.contains(&coord.0))
+    else {
+      continue;
+    };
+
+    let bundle = timed_mesh(chunk, *layout, &mut diagnostics).create_entity(
+      &mut materials,
+      &mut meshes,
+      &mut buffers,
+      &liquid_materials,
+      &prop_meshes,
+      fog.chunk,
+    );
+    commands
+      .entity(entity)
+      .despawn_related::<Children>()
+      .insert(bundle);
+  }
+
+  world.changed.clear();
+}
+
+/// Meshes changed chunks into the shared buffers of the indirect backend, the
+/// meshes always use [`ChunkMeshLayout::PackedVertices`].
+fn remesh_indirect_chunks(
+  mut commands: Commands,
+  mut world: ResMut<VoxelWorld>,
+  chunks: Query<(Entity, &ChunkCoord)>,
+  mut diagnostics: Option<ResMut<DiagnosticsStore>>,
+) {
+  if world.changed.is_empty() {
+    return;
+  }
+
+  for (entity, coord) in &chunks {
+    let Some(chunk) = world
+      .chunk(coord.0)
+      .filter(|_| world.changed.contains(&coord.0))
+    else {
+      continue;
+    };
+
+    commands.entity(entity).insert(
+      timed_mesh(chunk, ChunkMeshLayout::PackedVertices, &mut diagnostics).create_indirect_entity(),
+    );
+  }
+
+  world.changed.clear();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::voxel::chunk::indirect::IndirectChunkMesh;
+
+  #[test]
+  fn indirect_backend_meshes_streamed_chunks() {
+    let mut app = App::new();
+    app
+      .insert_resource(VoxelWorldConfig {
+        backend: ChunkBackend::Indirect,
+        ..default()
+      })
+      .add_plugins(VoxelWorldPlugin);
+    app
+      .world_mut()
+      .resource_mut::<VoxelWorld>()
+      .insert(ChunkBlockData::flat(IVec3::ZERO, 3));
+    // streaming spawns bare chunk entities
+    let entity = app.world_mut().spawn(ChunkCoord(IVec3::ZERO)).id();
+
+    app.update();
+
+    let mesh = app
+      .world()
+      .get::<IndirectChunkMesh>(entity)
+      .expect("chunk wasn't meshed for the indirect backend");
+    assert_eq!(mesh.chunk_pos, IVec3::ZERO);
+    // only the top face of every column is visible
+    assert_eq!(mesh.quads.len(), CHUNK_SIZE * CHUNK_SIZE);
+    assert!(app.world().get::<Mesh3d>(entity).is_none());
+    assert_eq!(app.world().resource::<VoxelWorld>().changed_count(), 0);
+  }
+}
@@ -1,15 +1,112 @@
+use std::ops::RangeInclusive;
+
 use bevy::prelude::*;
 
-use crate::voxel::chunk::{ChunkMaterialPlugin, test};
+use crate::voxel::chunk::{
+  ChunkIndirectPlugin, ChunkMaterialPlugin, ChunkStreamingPlugin, FluidPlugin,
+  LiquidMaterialPlugin, PropPlugin, SkyPlugin, TimeOfDayPlugin, VoxPlugin, VoxelFogPlugin,
+  VoxelWorldPlugin,
+};
+
+pub use chunk::{
+  BlockMapping, BlockVolume, ChunkBackend, ChunkMeshLayout, ChunkRegion, ChunkVertexLayout,
+  ChunkViewer, DebugOverlayPlugin, ExportMesh, Generator, InstancingExamplePlugin, TimeOfDay,
+  VoxelDiagnosticsPlugin, VoxelWorld, VoxelWorldConfig, generate_region, mesh_region,
+};
 
 mod chunk;
 
-pub struct VoxelPlugin;
+/// Generates, streams, meshes and renders the voxel world described by its
+/// [`VoxelWorldConfig`].
+#[derive(Default)]
+pub struct VoxelPlugin {
+  config: VoxelWorldConfig,
+  mesh_layout: ChunkMeshLayout,
+  vertex_layout: ChunkVertexLayout,
+  sky: bool,
+  time_of_day: Option<TimeOfDay>,
+}
+
+impl VoxelPlugin {
+  pub fn with_seed(mut self, seed: u32) -> Self {
+    self.config.seed = seed;
+    self
+  }
+
+  pub fn with_render_distance(mut self, render_distance: i32) -> Self {
+    self.config.render_distance = render_distance;
+    self
+  }
+
+  pub fn with_height_range(mut self, height_range: RangeInclusive<i32>) -> Self {
+    self.config.height_range = height_range;
+    self
+  }
+
+  pub fn with_generator(mut self, generator: Generator) -> Self {
+    self.config.generator = generator;
+    self
+  }
+
+  /// How the chunks are rendered.
+  pub fn with_backend(mut self, backend: ChunkBackend) -> Self {
+    self.config.backend = backend;
+    self
+  }
+
+  /// How the opaque meshes of chunk entities store their quads.
+  pub fn with_mesh_layout(mut self, mesh_layout: ChunkMeshLayout) -> Self {
+    self.mesh_layout = mesh_layout;
+    self
+  }
+
+  /// Layout of the shared buffers of the indirect chunk backend, unused with
+  /// [`ChunkBackend::Meshes`].
+  pub fn with_vertex_layout(mut self, vertex_layout: ChunkVertexLayout) -> Self {
+    self.vertex_layout = vertex_layout;
+    self
+  }
+
+  /// Draws a procedural sky dome whose horizon colours the fog.
+  pub fn with_sky(mut self, sky: bool) -> Self {
+    self.sky = sky;
+    self
+  }
+
+  /// Runs a day/night cycle starting from `time_of_day`, with a sun casting
+  /// shadows on the chunks.
+  pub fn with_time_of_day(mut self, time_of_day: TimeOfDay) -> Self {
+    self.time_of_day = Some(time_of_day);
+    self
+  }
+}
 
 impl Plugin for VoxelPlugin {
   fn build(&self, app: &mut App) {
-    app
-      .add_systems(Startup, test)
-      .add_plugins(ChunkMaterialPlugin);
+    app.insert_resource(self.config.clone()).add_plugins((
+      ChunkMaterialPlugin {
+        layout: self.mesh_layout,
+      },
+      LiquidMaterialPlugin,
+      PropPlugin,
+      VoxelWorldPlugin,
+      ChunkStreamingPlugin,
+      FluidPlugin,
+      VoxPlugin,
+      VoxelFogPlugin,
+    ));
+    if self.config.backend == ChunkBackend::Indirect {
+      app.add_plugins(ChunkIndirectPlugin {
+        layout: self.vertex_layout,
+      });
+    }
+    if self.sky {
+      app.add_plugins(SkyPlugin);
+    }
+    if let Some(time_of_day) = &self.time_of_day {
+      app.add_plugins(TimeOfDayPlugin {
+        time_of_day: time_of_day.clone(),
+      });
+    }
   }
 }